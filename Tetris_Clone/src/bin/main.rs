@@ -1,20 +1,47 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::fs;
 use std::io::{self, Write};
-use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyEventKind},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, ModifierKeyCode, MouseButton, MouseEvent, MouseEventKind},
     execute, queue, style, terminal,
 };
-use rand::{prelude::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
+use tetris_tui::bot::{play_game, play_move, Bot, HeuristicBot};
+use tetris_tui::cast::{CastWriter, TeeWriter};
+use tetris_tui::game::{
+    load_pieces, load_setup, save_file, standard_pieces, Color, Game, GameEvent, GameMode, GameOptions, GameOverReason,
+    GravityCurve, GravityDir, GravityMode, PauseSelection, Piece, PlayerInput, RandomizerKind, RotationDir,
+    ScoreBreakdown, ScoringConfig, Setup, Stats, AUTOSAVE_FILE, MARATHON_LINES, MAX_COLUMNS, MAX_LINES,
+    MIN_BOARD_SIZE, SAVE_SLOTS,
+};
+use tetris_tui::input::{Action, Key, KeyBindings};
+use tetris_tui::render::{
+    board_cell_at, board_center, draw_game, draw_game_smooth, draw_split, fits_screen, format_clock, format_time,
+    min_split_size, Controls, FrameBuffer, Paint, RenderStyle, Renderer, SmoothFall, Theme,
+};
+use tetris_tui::net::{Connection, NetMatch, Outcome};
+use tetris_tui::sound::SoundEngine;
+use tetris_tui::versus::{self, Versus};
 
 // --- CONFIGURATION & COMMAND-LINE ARGS ---
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Columns, lines, theme, DAS/ARR, gravity curve, mode and controls can also be set in \
+                  tetris_config.toml; flags given here override the file."
+)]
 struct Args {
     /// Number of columns on the board
     #[arg(long, default_value_t = 10)]
@@ -22,455 +49,1808 @@ struct Args {
     /// Number of lines on the board
     #[arg(long, default_value_t = 20)]
     lines: usize,
+    /// Hide the ghost piece that marks the hard-drop landing spot
+    #[arg(long)]
+    no_ghost: bool,
+    /// Warn when the stack reaches this many rows from the top (0 = off)
+    #[arg(long, default_value_t = 4)]
+    danger_rows: usize,
+    /// Keep the danger warning lit instead of flashing it
+    #[arg(long)]
+    no_danger_flash: bool,
+    /// Always use the compact layout: score, level and next on the top edge of the board
+    /// and a one-line hint below it instead of the side panel. Without it, the compact
+    /// layout is only used when the terminal is too small for the full one
+    #[arg(long)]
+    compact: bool,
+    /// Quit a game or match straight away instead of asking "Quit? (Y/N)" first
+    #[arg(long)]
+    no_confirm_quit: bool,
+    /// Milliseconds a grounded piece may rest before it locks
+    #[arg(long, default_value_t = 500)]
+    lock_delay_ms: u64,
+    /// Delayed auto shift: milliseconds Left/Right must be held before auto-repeat starts
+    #[arg(long, default_value_t = 170)]
+    das_ms: u64,
+    /// Auto repeat rate: milliseconds between moves once auto-repeat has started (0 = instant)
+    #[arg(long, default_value_t = 30)]
+    arr_ms: u64,
+    /// Keep a held Left/Right key's DAS charge when the next piece appears, so it moves
+    /// at once instead of charging again
+    #[arg(long)]
+    das_carry: bool,
+    /// How many times faster pieces fall while soft drop is held
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+    soft_drop_factor: u32,
+    /// Keep rotating while a rotate key is held, every --rotate-repeat-ms, as long as the
+    /// piece isn't resting on the stack. Needs a terminal that reports key releases
+    #[arg(long)]
+    rotate_repeat: bool,
+    /// Milliseconds between turns with --rotate-repeat
+    #[arg(long, default_value_t = 250, value_parser = clap::value_parser!(u64).range(1..))]
+    rotate_repeat_ms: u64,
+    /// Initial rotation/hold: a rotate or hold pressed up to this long before the next
+    /// piece appears (while rows clear, or after hold was used) applies to it on spawn (0 = off)
+    #[arg(long, default_value_t = 100)]
+    initial_action_ms: u64,
+    /// Milliseconds full rows flash before they are cleared (0 = no animation)
+    #[arg(long, default_value_t = 200)]
+    line_clear_ms: u64,
+    /// Seed for the piece sequence, to replay a game; random if omitted
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How pieces are dealt: bag7 (each piece once per bag of seven), pure (independent
+    /// rolls) or history (rerolls pieces dealt recently; never opens with S, Z or O)
+    #[arg(long, default_value = "bag7")]
+    randomizer: RandomizerKind,
+    /// Number of upcoming pieces to preview (trimmed to what fits in the terminal)
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u16).range(1..=7))]
+    preview_count: u16,
+    /// Hide the next queue (toggle in game with N); scores from games that never
+    /// showed it are marked as blind
+    #[arg(long)]
+    hide_next: bool,
+    /// Color theme: classic, pastel, monochrome or colorblind
+    #[arg(long, default_value = "classic")]
+    theme: Theme,
+    /// How blocks are drawn: blocks, or outline to draw each piece as an outline
+    #[arg(long, default_value = "blocks")]
+    render_style: RenderStyle,
+    /// Draw every cell at twice the size, for big screens and streams; needs a
+    /// terminal about twice as large
+    #[arg(long)]
+    big: bool,
+    /// Accessibility mode: white on black only, heavy borders, no background pattern
+    /// and a boxed score. Works with any theme and with --big
+    #[arg(long)]
+    high_contrast: bool,
+    /// Draw with plain ASCII (+-| borders, [] blocks) for terminals without Unicode.
+    /// Chosen automatically when the locale isn't UTF-8
+    #[arg(long, conflicts_with = "unicode")]
+    ascii: bool,
+    /// Mirror the board left to right, pieces included, with Left and Right swapped to
+    /// match. Only the display changes, so seeds and replays work as usual
+    #[arg(long)]
+    mirror: bool,
+    /// Which way pieces fall on screen: down, up, left or right. Only the display turns;
+    /// --columns and --lines still count across and along the fall, and full lines
+    /// clear as columns when pieces fall sideways
+    #[arg(long, default_value = "down")]
+    gravity_dir: GravityDir,
+    /// Draw with box-drawing and block characters even if the locale doesn't look like UTF-8
+    #[arg(long)]
+    unicode: bool,
+    /// Marathon is won by clearing a number of lines as the speed rises; sprint races to
+    /// clear a number of lines against the clock;
+    /// ultra scores as much as possible before time runs out; zen never ends (topping
+    /// out empties the board) and never speeds up
+    #[arg(long, value_enum, default_value_t = ModeArg::Marathon)]
+    mode: ModeArg,
+    /// Level to start at; gravity starts as fast as it would be at that level
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=MAX_START_LEVEL as i64))]
+    start_level: u32,
+    /// How gravity speeds up with the level: linear, classic-nes or guideline
+    #[arg(long, default_value = "guideline")]
+    gravity_curve: GravityCurve,
+    /// Milliseconds per gravity step at the start level instead of the curve's (at least
+    /// 20). Gravity still speeds up from there unless --no-speedup is also given
+    #[arg(long, value_parser = clap::value_parser!(u64).range(20..))]
+    gravity_ms: Option<u64>,
+    /// Keep gravity at its starting speed for the whole game; levels still rise and
+    /// still raise the score
+    #[arg(long)]
+    no_speedup: bool,
+    /// How blocks fall after a line clear: naive moves the rows above down; sticky lets
+    /// each connected cluster fall on its own, which can set off chain clears
+    #[arg(long, default_value = "naive")]
+    gravity_mode: GravityMode,
+    /// Hide locked blocks shortly after they land; the reveal key shows them briefly
+    #[arg(long)]
+    invisible: bool,
+    /// How long locked blocks stay visible in invisible mode
+    #[arg(long, default_value_t = 1000)]
+    fade_ms: u64,
+    /// How many times the board can be revealed in invisible mode
+    #[arg(long, default_value_t = 3)]
+    reveals: u32,
+    /// Times hold may be used in the whole game (0 = unlimited)
+    #[arg(long, default_value_t = 0)]
+    max_holds: u32,
+    /// Practice mode: the undo key takes back recent placements, the edit key turns
+    /// gravity off to build a board by hand (save then exports it as a setup file), and
+    /// scores and times aren't recorded on the leaderboards
+    #[arg(long)]
+    practice: bool,
+    /// Lines to clear to win marathon mode (0 = endless)
+    #[arg(long, default_value_t = MARATHON_LINES)]
+    marathon_lines: u32,
+    /// Lines to clear in sprint mode
+    #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u32).range(1..))]
+    sprint_lines: u32,
+    /// Seconds of play in ultra mode
+    #[arg(long, default_value_t = 120, value_parser = clap::value_parser!(u64).range(1..))]
+    time_limit: u64,
+    /// Garbage lines pushed up from the bottom per minute, for digging practice (0 = off)
+    #[arg(long, default_value_t = 0)]
+    garbage: u32,
+    /// Milliseconds incoming garbage waits, shown in the meter beside the board, before
+    /// the next lock pushes it in
+    #[arg(long, default_value_t = 1000)]
+    garbage_delay_ms: u64,
+    /// JSON file with a custom piece set to play with instead of the seven tetrominos
+    #[arg(long, value_name = "FILE")]
+    pieces: Option<PathBuf>,
+    /// TOML file with a board layout and the first pieces to deal, to practice a
+    /// situation from; every restart starts from it again. Its `board` string has one
+    /// line per row, bottom-aligned and as wide as the board: `.` empty, `#` garbage or a
+    /// piece letter. `pieces` lists piece names, such as ["T", "I"]
+    #[arg(long, value_name = "FILE")]
+    setup: Option<PathBuf>,
+    /// Directory for saves, scores and stats (default: the platform's data directory)
+    #[arg(long)]
+    save_dir: Option<PathBuf>,
+    /// Run without a terminal UI: read JSON commands from stdin, write JSON events to stdout
+    #[arg(long)]
+    json_events: bool,
+    /// Turn off sound effects (only built with the `sound` feature)
+    #[arg(long)]
+    mute: bool,
+    /// Audio file to loop as background music; the music key toggles it. Without this
+    /// the music key plays a metronome that speeds up with the level
+    #[arg(long, value_name = "FILE")]
+    music: Option<PathBuf>,
+    /// Frames drawn per second
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..=240))]
+    fps: u32,
+    /// Save CPU by drawing only when input arrives or the game changes by itself (at
+    /// most --fps times a second). Running clocks then update only a few times a second
+    #[arg(long)]
+    low_cpu: bool,
+    /// Draw the falling piece easing down between rows, so fast drops at high levels
+    /// don't skip rows on screen. Draws every frame, so --low-cpu has no effect
+    #[arg(long)]
+    smooth: bool,
+    /// Show the frame rate, time spent per frame and terminal writes per frame in the
+    /// bottom-right corner
+    #[arg(long)]
+    debug_overlay: bool,
+    /// Play with the mouse: left-click a column to move there, left-click the piece's
+    /// own columns to rotate, right-click to hard drop. Blocks terminal text selection.
+    #[arg(long)]
+    mouse: bool,
+    /// Start games straight away instead of counting down 3-2-1-GO first
+    #[arg(long)]
+    no_countdown: bool,
+    /// Record everything drawn, menus included, as an asciicast v2 file that
+    /// `asciinema play` can replay
+    #[arg(long, value_name = "FILE")]
+    export_cast: Option<PathBuf>,
+    /// Let the built-in bot play this many games without a UI, then print timing stats
+    #[arg(long, value_name = "GAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    bench: Option<u32>,
+    /// Host a match against another player over the network: wait on this TCP port for
+    /// them to --connect. Both play on this end's board size and randomizer, with the
+    /// same pieces
+    #[arg(long, value_name = "PORT", conflicts_with_all = ["connect", "versus", "json_events", "bench"])]
+    host: Option<u16>,
+    /// Join a network match hosted at this address, such as 192.168.1.20:7878. Meant
+    /// for a LAN; higher latency delays garbage and the opponent's board
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["versus", "json_events", "bench"])]
+    connect: Option<String>,
+    /// Two players on one keyboard, boards side by side: player one on the arrows, Enter
+    /// and `/` `.`, player two on WASD, Space, Q and E. Clears send garbage to the other
+    /// player and the first to top out loses. Always endless marathon
+    #[arg(long, conflicts_with_all = ["json_events", "bench"])]
+    versus: bool,
+    /// Rewrite "tetris_live.json" in the save directory with the game being played at
+    /// most every this many milliseconds, for another instance to show with --watch
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+    autosave_interval: Option<u64>,
+    /// Show the game another instance is playing with --autosave-interval, read from
+    /// this file (its "tetris_live.json") as it changes. Only the quit key does
+    /// anything. Both instances need the same --pieces
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["versus", "host", "connect", "json_events", "bench"])]
+    watch: Option<PathBuf>,
 }
 
-// --- COLOR & PIECE DEFINITIONS ---
-
-// Added Serialize and Deserialize for saving/loading the game state.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-struct Color(u8, u8, u8);
-
-struct Piece {
-    rotations: &'static [(usize, &'static [u8])],
-    color: Color,
-}
-
-// Static definitions for the 7 classic Tetris pieces (tetrominos).
-static PIECES: [Piece; 7] = [
-    // I-Block
-    Piece { rotations: &[(4, &[1,1,1,1]), (1, &[1,1,1,1])], color: Color(3, 252, 248) },
-    // O-Block
-    Piece { rotations: &[(2, &[1,1,1,1])], color: Color(252, 244, 3) },
-    // T-Block
-    Piece { rotations: &[(3, &[0,1,0,1,1,1]), (2, &[1,0,1,1,1,0]), (3, &[1,1,1,0,1,0]), (2, &[0,1,1,1,0,1])], color: Color(161, 3, 252) },
-    // L-Block
-    Piece { rotations: &[(3, &[0,0,1,1,1,1]), (2, &[1,0,1,0,1,1]), (3, &[1,1,1,1,0,0]), (2, &[1,1,0,1,0,1])], color: Color(252, 161, 3) },
-    // J-Block
-    Piece { rotations: &[(3, &[1,0,0,1,1,1]), (2, &[1,1,1,0,1,0]), (3, &[1,1,1,0,0,1]), (2, &[0,1,0,1,1,1])], color: Color(3, 48, 252) },
-    // S-Block
-    Piece { rotations: &[(3, &[0,1,1,1,1,0]), (2, &[1,0,1,1,0,1])], color: Color(3, 252, 28) },
-    // Z-Block
-    Piece { rotations: &[(3, &[1,1,0,0,1,1]), (2, &[0,1,1,1,1,0])], color: Color(252, 3, 3) },
-];
-
-// --- ACTIVE PIECE ---
-
-// Added Serialize and Deserialize for saving/loading the game state.
-#[derive(Clone, Serialize, Deserialize)]
-struct ActivePiece {
-    id: usize,
-    rotation: usize,
-    x: isize,
-    y: isize,
-}
-
-impl ActivePiece {
-    fn new(id: usize, board_width: usize) -> Self {
-        let width = PIECES[id].rotations[0].0;
-        ActivePiece {
-            id,
-            rotation: 0,
-            x: (board_width as isize - width as isize) / 2,
-            y: 0,
-        }
-    }
-
-    fn definition(&self) -> &Piece { &PIECES[self.id] }
-    fn width(&self) -> usize { self.definition().rotations[self.rotation].0 }
-    fn bitmap(&self) -> &'static [u8] { self.definition().rotations[self.rotation].1 }
-
-    fn blocks(&self) -> impl Iterator<Item = (isize, isize)> + '_ {
-        let width = self.width();
-        self.bitmap()
-            .iter()
-            .enumerate()
-            .filter(|&(_, &cell)| cell == 1)
-            .map(move |(i, _)| (self.x + (i % width) as isize, self.y + (i / width) as isize))
-    }
-}
-
-// --- SAVEGAME STATE ---
-// A separate struct for serialization that holds all data needed to restore a game.
-#[derive(Serialize, Deserialize)]
-struct SerializableGameState {
-    board: Vec<Option<Color>>,
-    width: usize,
-    height: usize,
-    active_piece: ActivePiece,
-    next_piece_id: usize,
-    is_game_over: bool,
-    gravity_delay_ms: u64,
-    speed_up_counter: usize,
-    score: u32,
-}
-
-// --- GAME STATE & LOGIC ---
-
-struct Game {
-    board: Vec<Option<Color>>,
-    width: usize,
-    height: usize,
-    active_piece: ActivePiece,
-    next_piece_id: usize,
-    rng: ThreadRng,
-    is_game_over: bool,
-    paused: bool,
-    gravity_delay: Duration,
-    last_gravity_time: Instant,
-    speed_up_counter: usize,
-    score: u32,
-    status_message: Option<(String, Instant)>,
-}
-
-impl Game {
-    fn new(width: usize, height: usize) -> Self {
-        let mut rng = rand::thread_rng();
-        let first_piece_id = rng.gen_range(0..PIECES.len());
-        let next_piece_id = rng.gen_range(0..PIECES.len());
-        Game {
-            board: vec![None; width * height],
-            width,
-            height,
-            active_piece: ActivePiece::new(first_piece_id, width),
-            rng,
-            is_game_over: false,
-            paused: false,
-            gravity_delay: Duration::from_millis(1000),
-            last_gravity_time: Instant::now(),
-            speed_up_counter: 0,
-            score: 0,
-            next_piece_id,
-            status_message: None,
-        }
-    }
-
-    fn check_collision(&self, piece: &ActivePiece) -> bool {
-        piece.blocks().any(|(x, y)| {
-            x < 0
-                || x >= self.width as isize
-                || y >= self.height as isize
-                || (y >= 0 && self.board[(y as usize * self.width) + x as usize].is_some())
-        })
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum ModeArg {
+    Marathon,
+    Sprint,
+    Ultra,
+    Zen,
+}
+
+/// Whether the locale, read through `var`, says the terminal can't show Unicode: the
+/// first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set names no UTF-8 encoding. With
+/// none set, only a Windows console outside Windows Terminal is assumed to lack it.
+fn locale_lacks_unicode(var: impl Fn(&str) -> Option<String>) -> bool {
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].into_iter().filter_map(&var).find(|value| !value.is_empty());
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_ascii_lowercase();
+            !locale.contains("utf-8") && !locale.contains("utf8")
+        }
+        None => cfg!(windows) && var("WT_SESSION").is_none(),
     }
-    
-    fn spawn_new_piece(&mut self) {
-        self.speed_up_counter += 1;
-        if self.speed_up_counter >= 10 {
-            let new_millis = self.gravity_delay.as_millis().saturating_sub(75).max(150) as u64;
-            self.gravity_delay = Duration::from_millis(new_millis);
-            self.speed_up_counter = 0;
+}
+
+/// Gameplay options chosen on the command line, fixed for the lifetime of a game.
+fn game_options(
+    args: &Args,
+    data_dir: &Path,
+    pieces: Arc<[Piece]>,
+    setup: Option<Arc<Setup>>,
+    scoring: ScoringConfig,
+) -> GameOptions {
+    GameOptions {
+        show_ghost: !args.no_ghost,
+        danger_rows: args.danger_rows,
+        danger_flash: !args.no_danger_flash,
+        lock_delay: Duration::from_millis(args.lock_delay_ms),
+        das: Duration::from_millis(args.das_ms),
+        arr: Duration::from_millis(args.arr_ms),
+        das_carry: args.das_carry,
+        soft_drop_factor: args.soft_drop_factor,
+        rotate_repeat: args.rotate_repeat.then(|| Duration::from_millis(args.rotate_repeat_ms)),
+        initial_action_window: Duration::from_millis(args.initial_action_ms),
+        line_clear_delay: Duration::from_millis(args.line_clear_ms),
+        seed: args.seed,
+        randomizer: args.randomizer,
+        preview_count: args.preview_count as usize,
+        hide_next: args.hide_next,
+        theme: args.theme,
+        render_style: args.render_style,
+        big: args.big,
+        high_contrast: args.high_contrast,
+        ascii: args.ascii || (!args.unicode && locale_lacks_unicode(|name| std::env::var(name).ok())),
+        mirror: args.mirror,
+        gravity_dir: args.gravity_dir,
+        compact: args.compact,
+        confirm_quit: !args.no_confirm_quit,
+        record_events: args.json_events,
+        garbage_rate: args.garbage,
+        garbage_delay: Duration::from_millis(args.garbage_delay_ms),
+        start_level: args.start_level,
+        gravity_curve: args.gravity_curve,
+        scoring,
+        gravity: args.gravity_ms.map(Duration::from_millis),
+        speedup: !args.no_speedup,
+        gravity_mode: args.gravity_mode,
+        invisible: args.invisible,
+        fade_delay: Duration::from_millis(args.fade_ms),
+        reveals: args.reveals,
+        max_holds: args.max_holds,
+        setup,
+        practice: args.practice,
+        mode: match args.mode {
+            ModeArg::Marathon => GameMode::Marathon { lines: args.marathon_lines },
+            ModeArg::Sprint => GameMode::Sprint { lines: args.sprint_lines },
+            ModeArg::Ultra => GameMode::Ultra { time_limit: Duration::from_secs(args.time_limit) },
+            ModeArg::Zen => GameMode::Zen,
+        },
+        save_dir: data_dir.to_path_buf(),
+        pieces,
+        // Decided once the terminal has been asked whether it reports key releases.
+        auto_shift: false,
+    }
+}
+
+const CONFIG_FILE: &str = "tetris_config.toml";
+
+/// Settings from "tetris_config.toml", for flags that would otherwise be typed on every
+/// launch. Each setting comes from, in increasing precedence: the built-in default, the
+/// file, then the command line. Key bindings go in a `[controls]` table laid out like
+/// "controls.toml", and replace that file when present. Line-clear points go in a
+/// `[scoring]` table, which only the file can set.
+#[derive(Debug, Default)]
+struct Config {
+    columns: Option<usize>,
+    lines: Option<usize>,
+    theme: Option<Theme>,
+    das_ms: Option<u64>,
+    arr_ms: Option<u64>,
+    gravity_curve: Option<GravityCurve>,
+    mode: Option<ModeArg>,
+    controls: Option<KeyBindings>,
+    scoring: ScoringConfig,
+}
+
+impl Config {
+    /// Reads the config file from the working directory. A missing file is an empty config.
+    fn load() -> (Config, Vec<String>) {
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(text) => Config::from_toml(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Config::default(), Vec::new()),
+            Err(e) => (Config::default(), vec![e.to_string()]),
         }
+    }
 
-        self.active_piece = ActivePiece::new(self.next_piece_id, self.width);
-        self.next_piece_id = self.rng.gen_range(0..PIECES.len());
+    /// Parses the settings, returning a warning for each one that was left out because
+    /// it was unknown or invalid. The other settings still apply.
+    fn from_toml(text: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let table: toml::Table = match toml::from_str(text) {
+            Ok(table) => table,
+            Err(e) => return (config, vec![e.to_string()]),
+        };
+        let mut warnings = Vec::new();
+        for (key, value) in table {
+            let result = match key.as_str() {
+                "columns" => board_size(&value).map(|n| config.columns = Some(n)),
+                "lines" => board_size(&value).map(|n| config.lines = Some(n)),
+                "theme" => config_str(&value).and_then(str::parse).map(|t| config.theme = Some(t)),
+                "das_ms" => config_u64(&value).map(|ms| config.das_ms = Some(ms)),
+                "arr_ms" => config_u64(&value).map(|ms| config.arr_ms = Some(ms)),
+                "gravity_curve" => config_str(&value).and_then(str::parse).map(|c| config.gravity_curve = Some(c)),
+                "mode" => config_str(&value)
+                    .and_then(|name| ModeArg::from_str(name, true))
+                    .map(|mode| config.mode = Some(mode)),
+                "controls" => toml::to_string(&value)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| KeyBindings::from_toml(&text))
+                    .map(|bindings| config.controls = Some(bindings)),
+                "scoring" => value
+                    .try_into()
+                    .map_err(|e: toml::de::Error| e.message().to_string())
+                    .map(|scoring| config.scoring = scoring),
+                _ => Err("unknown setting".to_string()),
+            };
+            if let Err(msg) = result {
+                warnings.push(format!("`{}`: {}; ignoring it", key, msg));
+            }
+        }
+        (config, warnings)
+    }
 
-        if self.check_collision(&self.active_piece) {
-            self.is_game_over = true;
+    /// Fills in `args` from the file wherever `matches` shows the flag wasn't given.
+    fn apply(&self, args: &mut Args, matches: &ArgMatches) {
+        fn merge<T: Copy>(setting: Option<T>, arg: &mut T, id: &str, matches: &ArgMatches) {
+            if let (Some(value), false) = (setting, matches.value_source(id) == Some(ValueSource::CommandLine)) {
+                *arg = value;
+            }
         }
+        merge(self.columns, &mut args.columns, "columns", matches);
+        merge(self.lines, &mut args.lines, "lines", matches);
+        merge(self.theme, &mut args.theme, "theme", matches);
+        merge(self.das_ms, &mut args.das_ms, "das_ms", matches);
+        merge(self.arr_ms, &mut args.arr_ms, "arr_ms", matches);
+        merge(self.gravity_curve, &mut args.gravity_curve, "gravity_curve", matches);
+        merge(self.mode, &mut args.mode, "mode", matches);
+    }
+}
+
+fn config_u64(value: &toml::Value) -> Result<u64, String> {
+    value
+        .as_integer()
+        .and_then(|n| u64::try_from(n).ok())
+        .ok_or_else(|| format!("expected a non-negative integer, got {}", value))
+}
+
+fn config_str(value: &toml::Value) -> Result<&str, String> {
+    value.as_str().ok_or_else(|| format!("expected a string, got {}", value))
+}
+
+/// A board dimension from the config file; unlike on the command line, one that is too
+/// small is skipped rather than fatal.
+fn board_size(value: &toml::Value) -> Result<usize, String> {
+    let size = config_u64(value)? as usize;
+    if size < MIN_BOARD_SIZE {
+        return Err(format!("must be at least {} (got {})", MIN_BOARD_SIZE, size));
     }
+    Ok(size)
+}
+
+/// Highest `--start-level`; gravity is close to its floor by then.
+const MAX_START_LEVEL: u32 = 20;
 
-    fn try_move(&mut self, dx: isize, dy: isize) -> bool {
-        let mut test_piece = self.active_piece.clone();
-        test_piece.x += dx;
-        test_piece.y += dy;
-        if !self.check_collision(&test_piece) {
-            self.active_piece = test_piece;
-            return true;
+/// Checks the requested board dimensions, returning the (columns, lines) to play with.
+/// Boards too small to spawn pieces are rejected; oversized ones are clamped.
+fn validate_args(args: &Args) -> Result<(usize, usize), String> {
+    if args.columns < MIN_BOARD_SIZE {
+        return Err(format!("--columns must be at least {} (got {})", MIN_BOARD_SIZE, args.columns));
+    }
+    if args.lines < MIN_BOARD_SIZE {
+        return Err(format!("--lines must be at least {} (got {})", MIN_BOARD_SIZE, args.lines));
+    }
+    Ok((args.columns.min(MAX_COLUMNS), args.lines.min(MAX_LINES)))
+}
+
+/// Checks that every rotation of every piece fits on a `columns` by `lines` board.
+fn check_pieces_fit(pieces: &[Piece], columns: usize, lines: usize) -> Result<(), String> {
+    for (id, piece) in pieces.iter().enumerate() {
+        for rotation in 0..piece.rotations.len() {
+            let (width, height) = piece.size(rotation);
+            if width > columns || height > lines {
+                return Err(format!("piece {} rotation {} is too big for a {}x{} board", id, rotation, columns, lines));
+            }
         }
-        false
     }
+    Ok(())
+}
 
-    fn try_rotate(&mut self) {
-        let mut test_piece = self.active_piece.clone();
-        let num_rotations = test_piece.definition().rotations.len();
-        test_piece.rotation = (test_piece.rotation + 1) % num_rotations;
+// --- RENDERING & INPUT ---
 
-        // Wall kick attempts
-        for offset in [0, 1, -1, 2, -2] {
-            let original_x = self.active_piece.x;
-            test_piece.x = original_x + offset;
-            if !self.check_collision(&test_piece) {
-                self.active_piece = test_piece;
-                return;
+/// Draws frames to a crossterm terminal, queueing commands until `present` flushes them.
+struct CrosstermRenderer<'a, W: Write> {
+    out: &'a mut W,
+}
+
+impl<W: Write> CrosstermRenderer<'_, W> {
+    fn color(paint: Paint) -> style::Color {
+        match paint {
+            Paint::Rgb(Color(r, g, b)) => style::Color::Rgb { r, g, b },
+            Paint::White => style::Color::White,
+            Paint::DarkGrey => style::Color::DarkGrey,
+            Paint::Yellow => style::Color::Yellow,
+            Paint::Red => style::Color::Red,
+            Paint::Green => style::Color::Green,
+            Paint::Cyan => style::Color::Cyan,
+            Paint::Magenta => style::Color::Magenta,
+        }
+    }
+}
+
+impl<W: Write> Renderer for CrosstermRenderer<'_, W> {
+    fn clear(&mut self) -> io::Result<()> {
+        queue!(self.out, cursor::Hide, terminal::Clear(terminal::ClearType::All))
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, paint: Paint) -> io::Result<()> {
+        self.draw_text(x, y, glyph, paint)
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, paint: Paint) -> io::Result<()> {
+        queue!(self.out, cursor::MoveTo(x, y), style::SetForegroundColor(Self::color(paint)), style::Print(text))
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Draws frames into a [`FrameBuffer`] and writes only the cells that changed since
+/// the last one to the terminal.
+struct DiffRenderer<'a, W: Write> {
+    out: &'a mut W,
+    frame: &'a mut FrameBuffer,
+    /// Text drawn over the bottom-right corner of the frame.
+    overlay: Option<&'a str>,
+    /// Spans written to the terminal by the last `present`.
+    written: usize,
+}
+
+impl<W: Write> Renderer for DiffRenderer<'_, W> {
+    fn clear(&mut self) -> io::Result<()> {
+        self.frame.clear()
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, paint: Paint) -> io::Result<()> {
+        self.frame.draw_cell(x, y, glyph, paint)
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, paint: Paint) -> io::Result<()> {
+        self.frame.draw_text(x, y, text, paint)
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        if let Some(text) = self.overlay {
+            let (columns, rows) = self.frame.size()?;
+            let x = columns.saturating_sub(text.chars().count() as u16);
+            self.frame.draw_text(x, rows.saturating_sub(1), text, Paint::DarkGrey)?;
+        }
+        if self.frame.needs_full_redraw() {
+            queue!(self.out, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        }
+        let changes = self.frame.changes();
+        self.written = changes.len();
+        for span in changes {
+            let color = CrosstermRenderer::<W>::color(span.paint);
+            queue!(self.out, cursor::MoveTo(span.x, span.y), style::SetForegroundColor(color), style::Print(span.text))?;
+        }
+        self.out.flush()
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.frame.size()
+    }
+}
+
+/// Draws everything in dark grey and leaves `present` to the caller, so a game can
+/// serve as a dimmed backdrop with another screen drawn on top of it.
+struct Backdrop<'a, W: Write>(CrosstermRenderer<'a, W>);
+
+impl<W: Write> Renderer for Backdrop<'_, W> {
+    fn clear(&mut self) -> io::Result<()> {
+        self.0.clear()
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, _paint: Paint) -> io::Result<()> {
+        self.0.draw_cell(x, y, glyph, Paint::DarkGrey)
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, _paint: Paint) -> io::Result<()> {
+        self.0.draw_text(x, y, text, Paint::DarkGrey)
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.0.size()
+    }
+}
+
+// --- GAME LOOP ---
+
+/// Simulation step. 250 updates a second keeps DAS, ARR and lock delays within 4ms
+/// of their settings, independent of how often frames are drawn.
+const TICK: Duration = Duration::from_millis(4);
+/// Cap on simulation steps per frame beyond those the frame's wait accounts for.
+/// After a longer stall (say the process was suspended) the game skips the lost time
+/// instead of fast-forwarding through it.
+const MAX_STEPS_PER_FRAME: u32 = 25;
+/// Longest a `--low-cpu` loop sleeps when nothing is scheduled, so that running
+/// clocks on screen still move.
+const MAX_IDLE_WAIT: Duration = Duration::from_millis(250);
+
+/// How the run loop spaces out frames.
+#[derive(Debug, Clone, Copy)]
+struct Pacing {
+    /// Shortest time between drawn frames.
+    frame_time: Duration,
+    /// Rather than drawing every `frame_time`, wait for input or until the game has
+    /// something to do by itself. Costs almost no CPU while nothing happens.
+    event_driven: bool,
+    /// Ease the falling piece between rows, which needs every frame drawn.
+    smooth: bool,
+}
+
+impl Pacing {
+    fn from_args(args: &Args) -> Self {
+        Pacing {
+            frame_time: Duration::from_secs(1) / args.fps,
+            event_driven: args.low_cpu && !args.smooth,
+            smooth: args.smooth,
+        }
+    }
+
+    /// How long to wait for input before drawing the next frame.
+    fn wait(&self, game: &Game) -> Duration {
+        if !self.event_driven {
+            return self.frame_time;
+        }
+        game.time_until_next_change().unwrap_or(MAX_IDLE_WAIT).min(MAX_IDLE_WAIT).max(self.frame_time)
+    }
+
+    /// Simulation steps to allow per frame: enough for the longest wait, plus some slack.
+    fn max_steps(&self) -> u32 {
+        let longest = if self.event_driven { MAX_IDLE_WAIT.max(self.frame_time) } else { self.frame_time };
+        (longest.as_nanos() / TICK.as_nanos()) as u32 + MAX_STEPS_PER_FRAME
+    }
+}
+
+/// Turns real elapsed time into a whole number of fixed simulation steps, carrying
+/// the remainder over to the next frame.
+struct FixedTimestep {
+    tick: Duration,
+    accumulator: Duration,
+    /// Most steps returned by one `advance`; time beyond that is dropped.
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    fn new(tick: Duration, max_steps: u32) -> Self {
+        FixedTimestep { tick, accumulator: Duration::ZERO, max_steps }
+    }
+
+    /// Adds `elapsed` real time and returns how many steps of `tick` are due.
+    fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut steps = 0;
+        while self.accumulator >= self.tick {
+            if steps == self.max_steps {
+                self.accumulator = Duration::ZERO;
+                break;
             }
+            self.accumulator -= self.tick;
+            steps += 1;
         }
+        steps
     }
+}
+
+/// Frames averaged over by the debug overlay.
+const FRAME_STATS_WINDOW: usize = 30;
+
+/// Rolling frame measurements for `--debug-overlay`.
+#[derive(Default)]
+struct FrameStats {
+    /// Time from each frame's start to the next one's, and how much of it went into
+    /// updating and drawing, newest last.
+    frames: VecDeque<(Duration, Duration)>,
+    last_start: Option<Instant>,
+    /// Spans the last frame wrote to the terminal.
+    written: usize,
+}
 
-    fn lock_piece(&mut self) {
-        let color = self.active_piece.definition().color;
-        for (x, y) in self.active_piece.blocks() {
-            if y >= 0 {
-                self.board[(y as usize * self.width) + x as usize] = Some(color);
+impl FrameStats {
+    /// Notes a frame that started at `start`, took `work` and wrote `written` spans.
+    fn record(&mut self, start: Instant, work: Duration, written: usize) {
+        if let Some(last) = self.last_start {
+            if self.frames.len() == FRAME_STATS_WINDOW {
+                self.frames.pop_front();
             }
+            self.frames.push_back((start - last, work));
         }
-        self.clear_lines();
-        self.spawn_new_piece();
+        self.last_start = Some(start);
+        self.written = written;
+    }
+
+    fn overlay(&self) -> String {
+        let count = self.frames.len().max(1) as u32;
+        let interval: Duration = self.frames.iter().map(|&(interval, _)| interval).sum::<Duration>() / count;
+        let work: Duration = self.frames.iter().map(|&(_, work)| work).sum::<Duration>() / count;
+        let fps = if interval.is_zero() { 0.0 } else { 1.0 / interval.as_secs_f64() };
+        format!("{:.0} fps {:.2}ms {} writes", fps, work.as_secs_f64() * 1000.0, self.written)
     }
+}
 
-    fn clear_lines(&mut self) {
-        let mut new_board = vec![None; self.width * self.height];
-        let mut cleared_lines_count = 0;
-        let mut new_row_index = self.height - 1;
+/// What the game screen lists about `bindings`' keys.
+fn controls_for(bindings: &KeyBindings, ascii: bool) -> Controls {
+    Controls { lines: bindings.help_lines(ascii), hint: bindings.hint_line(ascii) }
+}
 
-        for y in (0..self.height).rev() {
-            let row_start = y * self.width;
-            let row = &self.board[row_start..row_start + self.width];
+/// Advances `game` in fixed steps by the real time since `last_step`.
+fn catch_up(game: &mut Game, timestep: &mut FixedTimestep, last_step: &mut Instant) {
+    let now = Instant::now();
+    for _ in 0..timestep.advance(now - *last_step) {
+        game.update(TICK);
+    }
+    *last_step = now;
+}
 
-            if row.iter().all(|cell| cell.is_some()) {
-                cleared_lines_count += 1;
-            } else {
-                if new_row_index < self.height {
-                    let new_row_start = new_row_index * self.width;
-                    new_board[new_row_start..new_row_start + self.width].copy_from_slice(row);
+/// Runs the game until the player quits, returning [`Flow::Quit`] or [`Flow::QuitToMenu`].
+/// Input is handled as soon as it arrives, the game advances in fixed [`TICK`] steps,
+/// and frames are drawn as `pacing` says. Each frame also goes through `live`, if given.
+fn run<W: Write>(
+    game: &mut Game,
+    bindings: &KeyBindings,
+    pacing: Pacing,
+    mut frame_stats: Option<FrameStats>,
+    mut live: Option<&mut LiveSave>,
+    sound: &mut SoundEngine,
+    writer: &mut W,
+) -> io::Result<Flow> {
+    let controls = controls_for(bindings, game.options().ascii);
+    let mut prompt = None;
+    let (mut columns, mut rows) = terminal::size()?;
+    // Starts out unknown to the screen, so the first frame is drawn in full.
+    let mut frame = FrameBuffer::new(columns, rows);
+    // Set when the game was paused because the terminal shrank, so growing it again resumes.
+    let mut paused_for_size = false;
+    let mut timestep = FixedTimestep::new(TICK, pacing.max_steps());
+    let mut last_step = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut next_frame = Instant::now();
+    let mut fall = pacing.smooth.then(SmoothFall::default);
+    if let Some(warning) = sound.take_warning() {
+        game.set_status_message(warning);
+    }
+    loop {
+        next_frame += pacing.wait(game);
+        while event::poll(next_frame.saturating_duration_since(Instant::now()))? {
+            // Bring the game up to date first so the input lands at the right game time,
+            // however long the wait for it was.
+            catch_up(game, &mut timestep, &mut last_step);
+            match event::read()? {
+                Event::Key(key) => match handle_key(game, bindings, &mut prompt, key) {
+                    Flow::Continue => {}
+                    Flow::ToggleMusic => game.set_status_message(sound.toggle_music()),
+                    Flow::Restart => {
+                        *game = Game::new(game.width(), game.height(), game.options().clone());
+                        prompt = None;
+                    }
+                    flow @ (Flow::Quit | Flow::QuitToMenu) => return Ok(flow),
+                },
+                Event::Resize(new_columns, new_rows) => {
+                    (columns, rows) = (new_columns, new_rows);
+                    // The terminal may have reflowed what was on screen; start over.
+                    frame = FrameBuffer::new(columns, rows);
                 }
-                new_row_index = new_row_index.saturating_sub(1);
+                Event::Mouse(mouse) => handle_mouse(game, columns, rows, mouse),
+                _ => {}
             }
         }
-        self.board = new_board;
 
-        let points = match cleared_lines_count {
-            1 => 100,
-            2 => 300,
-            3 => 500,
-            4 => 800,
-            _ => 0,
-        };
-        self.score += points;
-    }
-    
-    fn update(&mut self) {
-        // Clear status message after a couple of seconds
-        if let Some((_, time)) = self.status_message {
-            if time.elapsed() > Duration::from_secs(2) {
-                self.status_message = None;
+        // draw_game shows "Terminal too small" instead of the board; keep the game
+        // frozen until there is room again.
+        let fits = fits_screen(game, columns, rows);
+        if !fits && !game.is_paused() && !game.is_game_over() {
+            game.toggle_pause();
+            paused_for_size = true;
+        } else if fits && paused_for_size && !game.is_confirming_quit() {
+            paused_for_size = false;
+            if game.is_paused() {
+                game.toggle_pause();
             }
         }
-        if self.is_game_over || self.paused {
-            return;
+
+        let now = Instant::now();
+        catch_up(game, &mut timestep, &mut last_step);
+        if let Some(fall) = &mut fall {
+            fall.advance(game, now - last_frame);
+        }
+        sound.update_music(now - last_frame, game.level(), !game.is_paused() && !game.is_game_over());
+        last_frame = now;
+        sound.play_events(&game.take_events());
+        if let Some(live) = &mut live {
+            live.update(game);
+        }
+        // The prompt lives in the status line; once that times out, so does the prompt.
+        if game.status_message().is_none() {
+            prompt = None;
+        }
+        let overlay = frame_stats.as_ref().map(FrameStats::overlay);
+        let mut renderer = DiffRenderer { out: writer, frame: &mut frame, overlay: overlay.as_deref(), written: 0 };
+        match &fall {
+            Some(fall) => draw_game_smooth(game, &controls, fall, &mut renderer)?,
+            None => draw_game(game, &controls, &mut renderer)?,
+        }
+        if let Some(stats) = &mut frame_stats {
+            stats.record(now, now.elapsed(), renderer.written);
         }
-        if self.last_gravity_time.elapsed() >= self.gravity_delay {
-            if !self.try_move(0, 1) {
-                self.lock_piece();
+        // Frames we fell behind on are dropped rather than drawn back to back.
+        next_frame = next_frame.max(now);
+    }
+}
+
+/// Applies a mouse click to the game (only reported with `--mouse`). Left-clicking a
+/// board column the active piece doesn't cover slides the piece toward it until it
+/// covers that column or is blocked; left-clicking one it covers rotates it.
+/// Right-clicking anywhere hard-drops.
+fn handle_mouse(game: &mut Game, columns: u16, rows: u16, mouse: MouseEvent) {
+    if game.is_paused() || game.is_game_over() {
+        return;
+    }
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            let Some((target, _)) = board_cell_at(game, columns, rows, mouse.column, mouse.row) else { return };
+            let target = target as isize;
+            let covers_target = |game: &Game| game.active_piece().blocks(game.pieces()).any(|(x, _)| x == target);
+            if covers_target(game) {
+                game.try_rotate(RotationDir::Clockwise);
+                return;
             }
-            self.last_gravity_time = Instant::now();
+            let left = game.active_piece().blocks(game.pieces()).map(|(x, _)| x).min().unwrap_or(target);
+            let dir = if target < left { -1 } else { 1 };
+            while !covers_target(game) && game.try_shift(dir) {}
         }
+        MouseEventKind::Down(MouseButton::Right) => game.hard_drop(),
+        _ => {}
+    }
+}
+
+/// Translates a crossterm key code into the engine's terminal-neutral key.
+fn to_key(code: KeyCode) -> Option<Key> {
+    match code {
+        KeyCode::Char(c) => Some(Key::char(c)),
+        KeyCode::Left => Some(Key::Left),
+        KeyCode::Right => Some(Key::Right),
+        KeyCode::Up => Some(Key::Up),
+        KeyCode::Down => Some(Key::Down),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Esc => Some(Key::Esc),
+        KeyCode::Tab => Some(Key::Tab),
+        KeyCode::Backspace => Some(Key::Backspace),
+        KeyCode::Modifier(ModifierKeyCode::LeftShift | ModifierKeyCode::RightShift) => Some(Key::Shift),
+        _ => None,
     }
+}
+
+/// A question in the status line that the player's next key press answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    /// A save waiting for the player to pick a slot.
+    Save,
+    /// A load waiting for the player to pick a slot.
+    Load,
+    /// A quick restart waiting for the restart key to be pressed again.
+    Restart,
+}
+
+/// Finishes a pending save or load with the slot chosen by `key`. Any key other than
+/// a slot digit cancels it.
+fn finish_slot_prompt(game: &mut Game, prompt: Prompt, key: KeyEvent) {
+    let slot = match key.code {
+        KeyCode::Char(c) => c.to_digit(10).filter(|&d| (1..=SAVE_SLOTS as u32).contains(&d)),
+        _ => None,
+    };
+    let Some(slot) = slot.map(|d| d as u8) else {
+        let what = if prompt == Prompt::Save { "Save" } else { "Load" };
+        game.set_status_message(format!("{} cancelled", what));
+        return;
+    };
+    let msg = match prompt {
+        Prompt::Save => match game.save_game(slot) {
+            Ok(_) => format!("Saved to slot {}", slot),
+            Err(e) => format!("Save Failed: {}", e),
+        },
+        Prompt::Load => match game.load_game(slot) {
+            Ok(true) => format!("Loaded slot {}", slot),
+            Ok(false) => format!("Loaded slot {} (checksum mismatch)", slot),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => format!("Slot {} is empty", slot),
+            Err(e) => format!("Load Failed: {}", e),
+        },
+        Prompt::Restart => unreachable!("restart prompts are answered in handle_key"),
+    };
+    game.set_status_message(msg);
+}
+
+/// What the run loop should do after a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    /// Start a fresh game with the same settings.
+    Restart,
+    /// Leave the game and go back to the start screen.
+    QuitToMenu,
+    Quit,
+    /// Turn the background music on or off.
+    ToggleMusic,
+}
 
-    fn render<W: Write>(&self, w: &mut W) -> io::Result<()> {
-        queue!(w, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+/// Handles a key press while the pause menu is open: Up/Down move the highlight and
+/// Enter picks the entry. Returns `None` for keys the menu doesn't use.
+fn handle_pause_menu(game: &mut Game, prompt: &mut Option<Prompt>, code: KeyCode) -> Option<Flow> {
+    match code {
+        KeyCode::Up => game.move_pause_selection(-1),
+        KeyCode::Down => game.move_pause_selection(1),
+        KeyCode::Enter => match game.pause_selection() {
+            PauseSelection::Resume => game.toggle_pause(),
+            PauseSelection::Restart => return Some(Flow::Restart),
+            PauseSelection::Save => {
+                *prompt = Some(Prompt::Save);
+                game.set_status_message(format!("Save to slot? (1-{})", SAVE_SLOTS));
+            }
+            PauseSelection::QuitToMenu => return Some(Flow::QuitToMenu),
+        },
+        _ => return None,
+    }
+    Some(Flow::Continue)
+}
 
-        let board_top_y: u16 = 1;
-        let board_left_x: u16 = 1;
+/// The answer `key` gives to "Quit? (Y/N)": Y is yes, N and Esc are no, and any other
+/// key, or a release, is no answer.
+fn quit_answer(key: KeyEvent) -> Option<bool> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    match key.code {
+        KeyCode::Char('y' | 'Y') => Some(true),
+        KeyCode::Char('n' | 'N') | KeyCode::Esc => Some(false),
+        _ => None,
+    }
+}
 
-        queue!(w, cursor::MoveTo(board_left_x, board_top_y - 1), style::Print(format!("╔{}╗", "═".repeat(self.width * 2))))?;
-        for y in 0..self.height {
-            queue!(w, cursor::MoveTo(board_left_x, board_top_y + y as u16), style::Print("║"))?;
-            for x in 0..self.width {
-                let bg_char = if (x + y) % 2 == 0 { "·" } else { " " };
-                queue!(w, style::SetForegroundColor(style::Color::DarkGrey), style::Print(bg_char.repeat(2)))?;
+/// Applies a single key event to the game and tells the run loop how to go on.
+/// Save and Load first ask for a slot, and Restart asks to be pressed again; the next
+/// key press answers `prompt`. Quit asks "Quit? (Y/N)" on the board unless
+/// [`GameOptions::confirm_quit`] is off, and then only Y, N and Esc do anything.
+fn handle_key(game: &mut Game, bindings: &KeyBindings, prompt: &mut Option<Prompt>, key: KeyEvent) -> Flow {
+    if game.is_confirming_quit() {
+        match quit_answer(key) {
+            Some(true) => return Flow::Quit,
+            Some(false) => game.cancel_quit(),
+            None => {}
+        }
+        return Flow::Continue;
+    }
+    if key.kind == KeyEventKind::Press {
+        match prompt.take() {
+            Some(Prompt::Restart) => {
+                if to_key(key.code).and_then(|k| bindings.action_for(k)) == Some(Action::Restart) {
+                    return Flow::Restart;
+                }
+                game.set_status_message("Restart cancelled".to_string());
+                return Flow::Continue;
+            }
+            Some(slot_prompt) => {
+                finish_slot_prompt(game, slot_prompt, key);
+                return Flow::Continue;
+            }
+            None => {}
+        }
+        if game.is_paused() && !game.is_game_over() {
+            if let Some(flow) = handle_pause_menu(game, prompt, key.code) {
+                return flow;
             }
-            queue!(w, style::SetForegroundColor(style::Color::White), style::Print("║"))?;
         }
-        queue!(w, cursor::MoveTo(board_left_x, board_top_y + self.height as u16), style::Print(format!("╚{}╝","═".repeat(self.width * 2))))?;
+    }
+    let Some(action) = to_key(key.code).and_then(|k| bindings.action_for(k)) else {
+        return Flow::Continue;
+    };
+    let pressed = key.kind == KeyEventKind::Press;
+
+    if action == Action::Quit {
+        // A finished game has nothing left to lose.
+        if !game.options().confirm_quit || game.is_game_over() {
+            return Flow::Quit;
+        }
+        if pressed {
+            game.ask_to_quit();
+        }
+        return Flow::Continue;
+    }
+    if action == Action::Music {
+        return if pressed { Flow::ToggleMusic } else { Flow::Continue };
+    }
+    if game.is_game_over() && !matches!(action, Action::Load | Action::Undo) { return Flow::Continue; }
 
-        let draw_block = |w: &mut W, x: isize, y: isize, color: Color| -> io::Result<()> {
-            let Color(r, g, b) = color;
-            queue!(w, cursor::MoveTo((board_left_x as isize +1+ x * 2) as u16, (board_top_y as isize + y) as u16),
-                style::SetForegroundColor(style::Color::Rgb { r, g, b }),
-                style::Print("██"))?;
-            Ok(())
-        };
+    if !game.is_paused() {
+        apply_move(game, action, key.kind);
+    }
 
-        for (i, cell) in self.board.iter().enumerate() {
-            if let Some(color) = cell {
-                draw_block(w, (i % self.width) as isize, (i / self.width) as isize, *color)?;
+    match action {
+        Action::Pause if pressed => {
+            game.toggle_pause();
+        }
+        Action::Stats if pressed => {
+            game.toggle_stats();
+        }
+        Action::Heights if pressed => {
+            game.toggle_heights();
+        }
+        Action::Reveal if pressed => {
+            game.reveal_board();
+        }
+        Action::HideNext if pressed => {
+            game.toggle_next_hidden();
+        }
+        Action::Undo if pressed => {
+            game.undo();
+        }
+        Action::Edit if pressed => {
+            game.toggle_edit_mode();
+        }
+        Action::Save if pressed && game.is_editing() => {
+            let msg = match game.export_setup() {
+                Ok(name) => format!("Board exported to {}", name),
+                Err(e) => format!("Export Failed: {}", e),
+            };
+            game.set_status_message(msg);
+        }
+        Action::Restart if pressed => {
+            // A single stray press shouldn't throw away a good run.
+            *prompt = Some(Prompt::Restart);
+            let key = bindings.keys_for(Action::Restart)[0];
+            let label = if game.options().ascii { key.ascii_label() } else { key.label() };
+            game.set_status_message(format!("{} again to restart; score is lost", label));
+        }
+        Action::Save if pressed => {
+            *prompt = Some(Prompt::Save);
+            game.set_status_message(format!("Save to slot? (1-{})", SAVE_SLOTS));
+        }
+        Action::Load if pressed => {
+            *prompt = Some(Prompt::Load);
+            game.set_status_message(format!("Load slot? (1-{})", SAVE_SLOTS));
+        }
+        _ => {}
+    }
+    Flow::Continue
+}
+
+/// Applies a moving, rotating, dropping or holding action to the active piece; other
+/// actions are ignored. `kind` says whether the key went down, repeated or came up.
+fn apply_move(game: &mut Game, action: Action, kind: KeyEventKind) {
+    let pressed = kind == KeyEventKind::Press;
+    let repeatable = matches!(kind, KeyEventKind::Press | KeyEventKind::Repeat);
+    let released = kind == KeyEventKind::Release;
+    match action {
+        // With auto shift the game repeats held moves itself, so terminal
+        // repeats are ignored; without it they are the only source of repeats.
+        // A mirrored or turned board is drawn flipped, so each key moves the piece the
+        // way it looks on screen; Left moves up when pieces fall sideways.
+        Action::MoveLeft | Action::MoveRight => {
+            let dir = if (action == Action::MoveLeft) != game.options().mirror { -1 } else { 1 };
+            let dir = game.options().gravity_dir.shift(dir);
+            if pressed {
+                game.press_shift(dir);
+            } else if released {
+                game.release_shift(dir);
+            } else if repeatable && !game.options().auto_shift {
+                game.try_shift(dir);
+            }
+        }
+        // Held rotate keys only repeat with --rotate-repeat, and only from the game's
+        // own timing; terminal repeats never turn the piece.
+        Action::Rotate | Action::RotateCcw | Action::Rotate180 => {
+            let direction = match action {
+                Action::Rotate => RotationDir::Clockwise,
+                Action::RotateCcw => RotationDir::CounterClockwise,
+                _ => RotationDir::Half,
+            };
+            if pressed {
+                game.press_rotate(direction);
+            } else if released {
+                game.release_rotate(direction);
+            }
+        }
+        // Like sideways moves, a held soft drop is timed by the game when it
+        // sees key releases and by the terminal's key repeat otherwise.
+        Action::SoftDrop => {
+            if pressed {
+                game.press_soft_drop();
+            } else if released {
+                game.release_soft_drop();
+            } else if repeatable && !game.options().auto_shift {
+                game.soft_drop();
             }
         }
+        Action::HardDrop if pressed => {
+            game.queue_input(PlayerInput::HardDrop);
+        }
+        Action::Hold if pressed => {
+            game.queue_input(PlayerInput::Hold);
+        }
+        _ => {}
+    }
+}
 
-        if !self.is_game_over {
-            let color = self.active_piece.definition().color;
-            for (x, y) in self.active_piece.blocks() {
-                if y >= 0 {
-                    draw_block(w, x, y, color)?;
+// --- VERSUS ---
+
+/// Applies a key event to a two-player match. Each player's moves go to their own
+/// game; pause, music and quit work for either. While paused, the pause menu drives
+/// both games at once, except that a match can't be saved. Quit asks "Quit? (Y/N)" on
+/// both boards, as [`handle_key`] does for one.
+fn handle_versus_key(versus: &mut Versus, bindings: &[KeyBindings; 2], key: KeyEvent) -> Flow {
+    if versus.is_confirming_quit() {
+        match quit_answer(key) {
+            Some(true) => return Flow::Quit,
+            Some(false) => versus.cancel_quit(),
+            None => {}
+        }
+        return Flow::Continue;
+    }
+    let pressed = key.kind == KeyEventKind::Press;
+    if pressed && versus.is_paused() {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => {
+                let delta = if key.code == KeyCode::Up { -1 } else { 1 };
+                versus.games.iter_mut().for_each(|game| game.move_pause_selection(delta));
+                return Flow::Continue;
+            }
+            KeyCode::Enter => {
+                match versus.games[0].pause_selection() {
+                    PauseSelection::Resume => versus.toggle_pause(),
+                    PauseSelection::Restart => return Flow::Restart,
+                    PauseSelection::Save => versus.games[0].set_status_message("Matches can't be saved".to_string()),
+                    PauseSelection::QuitToMenu => return Flow::QuitToMenu,
+                }
+                return Flow::Continue;
+            }
+            _ => {}
+        }
+    }
+    let Some(key_pressed) = to_key(key.code) else { return Flow::Continue };
+    let Some((player, action)) =
+        (0..2).find_map(|player| Some((player, bindings[player].action_for(key_pressed)?)))
+    else {
+        return Flow::Continue;
+    };
+    match action {
+        Action::Quit if pressed && !versus.games[0].options().confirm_quit => return Flow::Quit,
+        Action::Quit if pressed => versus.ask_to_quit(),
+        Action::Music if pressed => return Flow::ToggleMusic,
+        Action::Pause if pressed => versus.toggle_pause(),
+        _ if !versus.is_paused() => apply_move(&mut versus.games[player], action, key.kind),
+        _ => {}
+    }
+    Flow::Continue
+}
+
+/// Advances both games of `versus` in fixed steps by the real time since `last_step`,
+/// returning the events they recorded.
+fn catch_up_versus(versus: &mut Versus, timestep: &mut FixedTimestep, last_step: &mut Instant) -> Vec<GameEvent> {
+    let now = Instant::now();
+    let mut events = Vec::new();
+    for _ in 0..timestep.advance(now - *last_step) {
+        events.extend(versus.update(TICK));
+    }
+    *last_step = now;
+    events
+}
+
+/// Runs a two-player match like [`run`] runs a game, until one player tops out
+/// ([`Flow::Continue`]) or someone quits ([`Flow::Quit`] or [`Flow::QuitToMenu`]).
+/// Restarting from the pause menu starts a fresh match in place.
+fn run_versus<W: Write>(
+    versus: &mut Versus,
+    bindings: &[KeyBindings; 2],
+    pacing: Pacing,
+    sound: &mut SoundEngine,
+    writer: &mut W,
+) -> io::Result<Flow> {
+    let ascii = versus.games[0].options().ascii;
+    let controls = bindings.each_ref().map(|bindings| controls_for(bindings, ascii));
+    let (mut columns, mut rows) = terminal::size()?;
+    let mut frame = FrameBuffer::new(columns, rows);
+    let mut paused_for_size = false;
+    let mut timestep = FixedTimestep::new(TICK, pacing.max_steps());
+    let mut last_step = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut next_frame = Instant::now();
+    let mut events = Vec::new();
+    if let Some(warning) = sound.take_warning() {
+        versus.games[0].set_status_message(warning);
+    }
+    while versus.outcome().is_none() {
+        next_frame += pacing.wait(&versus.games[0]).min(pacing.wait(&versus.games[1]));
+        while event::poll(next_frame.saturating_duration_since(Instant::now()))? {
+            events.extend(catch_up_versus(versus, &mut timestep, &mut last_step));
+            match event::read()? {
+                Event::Key(key) => match handle_versus_key(versus, bindings, key) {
+                    Flow::Continue => {}
+                    Flow::ToggleMusic => versus.games[0].set_status_message(sound.toggle_music()),
+                    Flow::Restart => versus.restart(),
+                    flow @ (Flow::Quit | Flow::QuitToMenu) => return Ok(flow),
+                },
+                Event::Resize(new_columns, new_rows) => {
+                    (columns, rows) = (new_columns, new_rows);
+                    frame = FrameBuffer::new(columns, rows);
                 }
+                _ => {}
             }
         }
 
-        let panel_x = (self.width * 2 + 5) as u16;
-        queue!(w, cursor::MoveTo(panel_x, 2), style::SetForegroundColor(style::Color::White), style::Print("Score"))?;
-        queue!(w, cursor::MoveTo(panel_x, 3), style::SetForegroundColor(style::Color::Yellow), style::Print(format!("{:0>8}", self.score)))?;
-
-        queue!(w, cursor::MoveTo(panel_x, 5), style::SetForegroundColor(style::Color::White), style::Print("Next Piece"))?;
-        let next_piece = &PIECES[self.next_piece_id];
-        let (p_width, p_bitmap) = next_piece.rotations[0];
-        for (i, &cell) in p_bitmap.iter().enumerate() {
-            if cell == 1 {
-                let x = (i % p_width) as isize;
-                let y = (i / p_width) as isize;
-                let Color(r,g,b) = next_piece.color;
-                queue!(w, cursor::MoveTo(panel_x + (x * 2) as u16, 6 + y as u16),
-                    style::SetForegroundColor(style::Color::Rgb { r, g, b }),
-                    style::Print("██"))?;
-            }
-        }
-
-        let controls_y = 12;
-        queue!(w, cursor::MoveTo(panel_x, controls_y), style::SetForegroundColor(style::Color::White), style::Print("Controls"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 1), style::Print("←/→: Move"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 2), style::Print("  ↑: Rotate"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 3), style::Print("  ↓: Soft Drop"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 4), style::Print("Spc: Hard Drop"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 5), style::Print("  P: Pause"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 6), style::Print("  S: Save"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 7), style::Print("  L: Load"))?;
-        queue!(w, cursor::MoveTo(panel_x, controls_y + 8), style::Print("  Q: Quit"))?;
-        
-        if self.is_game_over {
-            let msg = "GAME OVER";
-            let msg_x = board_left_x + ((self.width * 2 - msg.len()) / 2) as u16;
-            let msg_y = board_top_y + (self.height / 2) as u16;
-            queue!(w, cursor::MoveTo(msg_x, msg_y), style::SetForegroundColor(style::Color::Red), style::Print(msg))?;
-        } else if self.paused {
-            let msg = "PAUSED";
-            let msg_x = board_left_x + ((self.width * 2 - msg.len()) / 2) as u16;
-            let msg_y = board_top_y + (self.height / 2) as u16;
-            queue!(w, cursor::MoveTo(msg_x, msg_y), style::SetForegroundColor(style::Color::Cyan), style::Print(msg))?;
-        }
-
-        if let Some((msg, _)) = &self.status_message {
-            let msg_x = board_left_x + ((self.width * 2 - msg.len()) / 2) as u16;
-            let msg_y = board_top_y + self.height as u16 + 1;
-            queue!(w, cursor::MoveTo(msg_x, msg_y), style::SetForegroundColor(style::Color::Green), style::Print(msg))?;
-        }
-
-        w.flush()
-    }
-
-    fn run<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
-        'running: loop {
-            while event::poll(Duration::from_millis(1))? {
-                if let Event::Key(key) = event::read()? {
-                    match key.code {
-                        KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => break 'running,
-                        _ => {}
+        let (min_columns, min_rows) = min_split_size(&[&versus.games[0], &versus.games[1]]);
+        let fits = columns >= min_columns && rows >= min_rows;
+        if !fits && !versus.is_paused() {
+            versus.toggle_pause();
+            paused_for_size = true;
+        } else if fits && paused_for_size {
+            paused_for_size = false;
+            if versus.is_paused() {
+                versus.toggle_pause();
+            }
+        }
+
+        let now = Instant::now();
+        events.extend(catch_up_versus(versus, &mut timestep, &mut last_step));
+        let level = versus.games.iter().map(Game::level).max().unwrap_or(1);
+        sound.update_music(now - last_frame, level, !versus.is_paused() && versus.outcome().is_none());
+        last_frame = now;
+        sound.play_events(&std::mem::take(&mut events));
+        let mut renderer = DiffRenderer { out: writer, frame: &mut frame, overlay: None, written: 0 };
+        draw_split(&[&versus.games[0], &versus.games[1]], &controls, &mut renderer)?;
+        next_frame = next_frame.max(now);
+    }
+    Ok(Flow::Continue)
+}
+
+/// The end screen for a match that ended in `outcome`: who won, if anyone, and each
+/// player's score, lines and garbage sent. Matches aren't recorded on the leaderboards.
+fn versus_end_screen(versus: &Versus, outcome: versus::Outcome) -> EndScreen {
+    let rows = versus
+        .games
+        .iter()
+        .enumerate()
+        .map(|(player, game)| match_row(&format!("P{}", player + 1), game, versus.sent(player)))
+        .collect();
+    let (title, title_color, cause, result, winner) = match outcome {
+        versus::Outcome::Won(winner) => {
+            let loser = 1 - winner;
+            let cause = versus.games[loser].game_over_reason().map(GameOverReason::label);
+            let title = ["PLAYER 1 WINS", "PLAYER 2 WINS"][winner];
+            (title, style::Color::Green, cause, format!("Player {} topped out", loser + 1), Some(winner))
+        }
+        versus::Outcome::Draw => ("DRAW", style::Color::Yellow, None, "Both players topped out".to_string(), None),
+    };
+    EndScreen {
+        title,
+        title_color,
+        cause,
+        result,
+        seed: versus.games[0].seed(),
+        table_title: format!("{:<6}{:>11}{:>7}{:>6}", "PLAYER", "SCORE", "LINES", "SENT"),
+        rows,
+        new_rank: winner,
+        summary: Vec::new(),
+        save_error: None,
+        restart: true,
+    }
+}
+
+/// One player's row in a match's end-screen table: score, lines and garbage sent.
+fn match_row(name: &str, game: &Game, sent: u32) -> String {
+    format!("{:<6}{:>11}{:>7}{:>6}", name, game.score(), game.lines_cleared(), sent)
+}
+
+// --- NETWORK VERSUS ---
+
+/// How long a joining player waits for the host's hello once connected.
+const NET_HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs this end of a network match, with the local game on the left and the
+/// opponent's on the right, until it is decided (returning how) or the player quits
+/// (returning `None`, after telling the opponent). There is no pausing: the other
+/// player's game keeps going regardless.
+fn run_net<W: Write>(
+    net: &mut NetMatch,
+    bindings: &KeyBindings,
+    pacing: Pacing,
+    sound: &mut SoundEngine,
+    writer: &mut W,
+) -> io::Result<Option<Outcome>> {
+    let bindings = bindings.restricted_to(&[
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Rotate,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Hold,
+        Action::Music,
+        Action::Quit,
+    ]);
+    let controls = [controls_for(&bindings, net.game.options().ascii), Controls::default()];
+    // The opponent's board changes whenever their messages arrive, so frames are
+    // drawn at the full rate even with --low-cpu.
+    let pacing = Pacing { event_driven: false, ..pacing };
+    let (mut columns, mut rows) = terminal::size()?;
+    let mut frame = FrameBuffer::new(columns, rows);
+    let mut timestep = FixedTimestep::new(TICK, pacing.max_steps());
+    let mut last_step = Instant::now();
+    let mut last_frame = Instant::now();
+    let mut next_frame = Instant::now();
+    let mut events = Vec::new();
+    let mut catch_up = |net: &mut NetMatch, events: &mut Vec<GameEvent>| {
+        let now = Instant::now();
+        for _ in 0..timestep.advance(now - last_step) {
+            events.extend(net.update(TICK));
+        }
+        last_step = now;
+    };
+    if let Some(warning) = sound.take_warning() {
+        net.game.set_status_message(warning);
+    }
+    while net.outcome().is_none() {
+        next_frame += pacing.wait(&net.game);
+        while event::poll(next_frame.saturating_duration_since(Instant::now()))? {
+            catch_up(net, &mut events);
+            match event::read()? {
+                // Leaving forfeits the match, so it is asked about like quitting a game.
+                Event::Key(key) if net.game.is_confirming_quit() => match quit_answer(key) {
+                    Some(true) => {
+                        net.leave();
+                        return Ok(None);
                     }
-                    if self.is_game_over && key.code != KeyCode::Char('l') && key.code != KeyCode::Char('L') { continue; }
-
-                    if !self.paused {
-                         match key.code {
-                            KeyCode::Left if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) => {
-                                self.try_move(-1, 0);
-                            }
-                            KeyCode::Right if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) => {
-                                self.try_move(1, 0);
-                            }
-                            KeyCode::Up if key.kind == KeyEventKind::Press => {
-                                self.try_rotate();
-                            }
-                            KeyCode::Down if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) => {
-                                if self.try_move(0, 1) {
-                                    self.last_gravity_time = Instant::now();
-                                } else {
-                                    self.lock_piece();
-                                    self.last_gravity_time = Instant::now();
-                                }
-                            }
-                            KeyCode::Char(' ') if key.kind == KeyEventKind::Press => {
-                                let mut distance = 0;
-                                while self.try_move(0, 1) { distance += 1; }
-                                self.lock_piece();
-                                self.last_gravity_time = Instant::now();
-                            }
-                            _ => {}
+                    Some(false) => net.game.cancel_quit(),
+                    None => {}
+                },
+                Event::Key(key) => match to_key(key.code).and_then(|key| bindings.action_for(key)) {
+                    Some(Action::Quit) if key.kind == KeyEventKind::Press => {
+                        if net.game.options().confirm_quit {
+                            net.game.ask_to_quit();
+                        } else {
+                            net.leave();
+                            return Ok(None);
                         }
                     }
-
-                    match key.code {
-                         KeyCode::Char('p') | KeyCode::Char('P') if key.kind == KeyEventKind::Press => {
-                            self.paused = !self.paused;
-                        }
-                        KeyCode::Char('s') | KeyCode::Char('S') if key.kind == KeyEventKind::Press => {
-                            match self.save_game() {
-                                Ok(_) => self.set_status_message("Game Saved!".to_string()),
-                                Err(e) => self.set_status_message(format!("Save Failed: {}", e)),
-                            }
-                        }
-                        KeyCode::Char('l') | KeyCode::Char('L') if key.kind == KeyEventKind::Press => {
-                            match self.load_game() {
-                                Ok(_) => self.set_status_message("Game Loaded!".to_string()),
-                                Err(e) => self.set_status_message(format!("Load Failed: {}", e)),
-                            }
-                        }
-                        _ => {}
+                    Some(Action::Music) if key.kind == KeyEventKind::Press => {
+                        net.game.set_status_message(sound.toggle_music());
                     }
+                    Some(action) => apply_move(&mut net.game, action, key.kind),
+                    None => {}
+                },
+                Event::Resize(new_columns, new_rows) => {
+                    (columns, rows) = (new_columns, new_rows);
+                    frame = FrameBuffer::new(columns, rows);
                 }
+                _ => {}
             }
+        }
+
+        let now = Instant::now();
+        catch_up(net, &mut events);
+        sound.update_music(now - last_frame, net.game.level(), net.outcome().is_none());
+        last_frame = now;
+        sound.play_events(&std::mem::take(&mut events));
+        let mut renderer = DiffRenderer { out: writer, frame: &mut frame, overlay: None, written: 0 };
+        draw_split(&[&net.game, &net.opponent], &controls, &mut renderer)?;
+        next_frame = next_frame.max(now);
+    }
+    Ok(net.outcome())
+}
+
+/// The end screen for this end of a network match.
+fn net_end_screen(net: &NetMatch, outcome: Outcome) -> EndScreen {
+    let (title, title_color, cause) = match outcome {
+        Outcome::Won => ("YOU WIN", style::Color::Green, net.opponent.game_over_reason().map(GameOverReason::label)),
+        Outcome::Lost => ("YOU LOSE", style::Color::Red, net.game.game_over_reason().map(GameOverReason::label)),
+        Outcome::OpponentLeft => ("YOU WIN", style::Color::Green, Some("OPPONENT LEFT")),
+    };
+    EndScreen {
+        title,
+        title_color,
+        cause,
+        result: format!("Final Score: {}", net.game.score()),
+        seed: net.game.seed(),
+        table_title: format!("{:<6}{:>11}{:>7}{:>6}", "PLAYER", "SCORE", "LINES", "SENT"),
+        rows: vec![match_row("YOU", &net.game, net.sent()), match_row("THEM", &net.opponent, net.received())],
+        new_rank: Some(0),
+        summary: Vec::new(),
+        save_error: None,
+        // Both ends would have to agree to a rematch.
+        restart: false,
+    }
+}
+
+// --- WATCHING ---
+
+/// The file `--autosave-interval` keeps rewriting in the data directory for `--watch`.
+const LIVE_FILE: &str = "tetris_live.json";
+/// How often `--watch` checks the file for a newer state.
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Writes the game being played to [`LIVE_FILE`] at most once per `interval`, so
+/// another instance can follow it with `--watch`.
+struct LiveSave {
+    path: PathBuf,
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl LiveSave {
+    fn new(data_dir: &Path, interval: Duration) -> Self {
+        LiveSave { path: data_dir.join(LIVE_FILE), interval, last: None }
+    }
 
-            self.update();
-            self.render(writer)?;
-            std::thread::sleep(Duration::from_millis(16));
+    /// Writes `game` out if `interval` has passed since the last write. A failure is
+    /// shown in the status line; the next write tries again.
+    fn update(&mut self, game: &mut Game) {
+        if self.last.is_some_and(|last| last.elapsed() < self.interval) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        // Written beside the file and renamed over it, so a watcher rarely sees half a state.
+        let partial = self.path.with_extension("json.partial");
+        let result = serde_json::to_string(&game.to_state())
+            .map_err(io::Error::other)
+            .and_then(|json| fs::write(&partial, json))
+            .and_then(|()| fs::rename(&partial, &self.path));
+        if let Err(e) = result {
+            game.set_status_message(format!("Live save failed: {}", e));
         }
-        Ok(())
     }
+}
+
+/// Shows the game another instance writes to `path` with `--autosave-interval` until
+/// the quit key is pressed, reading the file again as it changes. `game` only displays
+/// the states read; no key plays it. Unreadable or half-written states are skipped,
+/// leaving the last good one on screen.
+fn run_watch<W: Write>(path: &Path, mut game: Game, bindings: &KeyBindings, writer: &mut W) -> io::Result<()> {
+    let bindings = bindings.restricted_to(&[Action::Quit]);
+    let controls = controls_for(&bindings, game.options().ascii);
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().into_owned());
+    let (mut columns, mut rows) = terminal::size()?;
+    let mut frame = FrameBuffer::new(columns, rows);
+    let mut last_read = None;
+    let mut watching = false;
+    loop {
+        while event::poll(WATCH_POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key) if to_key(key.code).and_then(|key| bindings.action_for(key)) == Some(Action::Quit) => {
+                    return Ok(());
+                }
+                Event::Resize(new_columns, new_rows) => {
+                    (columns, rows) = (new_columns, new_rows);
+                    frame = FrameBuffer::new(columns, rows);
+                }
+                _ => {}
+            }
+        }
 
-    fn set_status_message(&mut self, msg: String) {
-        self.status_message = Some((msg, Instant::now()));
+        if let Ok(text) = fs::read_to_string(path) {
+            if last_read.as_ref() != Some(&text) {
+                if let Ok(state) = serde_json::from_str(&text) {
+                    watching |= game.load_state(state).is_ok();
+                }
+                last_read = Some(text);
+            }
+        }
+        let status = if watching { "Watching" } else { "Waiting for" };
+        game.set_status_message(format!("{} {}", status, name));
+        let mut renderer = DiffRenderer { out: &mut *writer, frame: &mut frame, overlay: None, written: 0 };
+        draw_game(&game, &controls, &mut renderer)?;
     }
+}
+
+// --- HIGH SCORES ---
+
+const HIGH_SCORES_FILE: &str = "highscores.json";
+/// Single-number high score file used by older versions; migrated on first load.
+const LEGACY_HIGH_SCORE_FILE: &str = "highscore.txt";
+const MAX_HIGH_SCORES: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScoreEntry {
+    initials: String,
+    score: u64,
+    date: String,
+    /// Level the game started at. Entries from before start levels were level 1.
+    #[serde(default = "default_start_level")]
+    start_level: u32,
+    /// Whether the game was played without ever showing the next queue.
+    #[serde(default)]
+    blind: bool,
+    /// Where the points came from; all zero for entries from before it was recorded.
+    #[serde(default)]
+    points: ScoreBreakdown,
+    /// How long the game lasted; 0 for entries from before it was recorded.
+    #[serde(default)]
+    time_ms: u64,
+}
+
+fn default_start_level() -> u32 {
+    1
+}
 
-    fn save_game(&self) -> io::Result<()> {
-        let state = SerializableGameState {
-            board: self.board.clone(),
-            width: self.width,
-            height: self.height,
-            active_piece: self.active_piece.clone(),
-            next_piece_id: self.next_piece_id,
-            is_game_over: self.is_game_over,
-            gravity_delay_ms: self.gravity_delay.as_millis() as u64,
-            speed_up_counter: self.speed_up_counter,
-            score: self.score,
+/// The top-10 leaderboard, kept sorted by descending score.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HighScores {
+    entries: Vec<ScoreEntry>,
+}
+
+impl HighScores {
+    /// Loads the leaderboard from "highscores.json". If it doesn't exist yet, an old
+    /// "highscore.txt" is migrated into a single entry. Missing or invalid files yield an empty table.
+    fn load(dir: &Path) -> Self {
+        if let Ok(data) = fs::read_to_string(dir.join(HIGH_SCORES_FILE)) {
+            let mut scores: HighScores = serde_json::from_str(&data).unwrap_or_default();
+            scores.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+            scores.entries.truncate(MAX_HIGH_SCORES);
+            return scores;
+        }
+
+        let mut scores = HighScores::default();
+        let legacy: u64 = fs::read_to_string(dir.join(LEGACY_HIGH_SCORE_FILE))
+            .unwrap_or_default()
+            .trim()
+            .parse()
+            .unwrap_or(0);
+        if legacy > 0 {
+            scores.entries.push(ScoreEntry {
+                initials: "---".to_string(),
+                score: legacy,
+                date: String::new(),
+                start_level: 1,
+                blind: false,
+                points: ScoreBreakdown::default(),
+                time_ms: 0,
+            });
+            // Best effort: if this fails the migration simply runs again next launch.
+            let _ = scores.save(dir);
+        }
+        scores
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        fs::write(dir.join(HIGH_SCORES_FILE), serialized)
+    }
+
+    /// Inserts the score with today's date, the game's length and, from its stats, its
+    /// start level, whether it was blind and its point breakdown, if it makes the table.
+    /// Returns its rank (0-based); the caller fills in the initials of the returned entry.
+    fn try_insert(&mut self, score: u64, stats: &Stats, time: Duration) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        let rank = self.entries.iter().position(|entry| score > entry.score).unwrap_or(self.entries.len());
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+        let entry = ScoreEntry {
+            initials: String::new(),
+            score,
+            date: today(),
+            start_level: stats.start_level,
+            blind: stats.blind,
+            points: stats.points,
+            time_ms: time.as_millis() as u64,
         };
-        let serialized = serde_json::to_string(&state)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        fs::write("tetris_save.json", serialized)
-    }
-
-    fn load_game(&mut self) -> io::Result<()> {
-        let data = fs::read_to_string("tetris_save.json")?;
-        let state: SerializableGameState = serde_json::from_str(&data)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-        
-        self.board = state.board;
-        self.width = state.width;
-        self.height = state.height;
-        self.active_piece = state.active_piece;
-        self.next_piece_id = state.next_piece_id;
-        self.is_game_over = state.is_game_over;
-        self.gravity_delay = Duration::from_millis(state.gravity_delay_ms);
-        self.speed_up_counter = state.speed_up_counter;
-        self.score = state.score;
-        self.paused = false; // Always unpause on load
-        self.last_gravity_time = Instant::now(); // Reset gravity timer
+        self.entries.insert(rank, entry);
+        self.entries.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
 
-        Ok(())
+    /// The table as lines for the end screen, marking each score with its start level
+    /// since scores from higher starts aren't comparable, and blind scores with a B.
+    fn rows(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let blind = if entry.blind { "B" } else { " " };
+                let time = Some(entry.time_ms)
+                    .filter(|&ms| ms > 0)
+                    .map_or_else(String::new, |ms| format_clock(Duration::from_millis(ms)));
+                format!(
+                    "{:>2}. {:<3} {:>8} L{:<2}{} {:>5} {:<10}",
+                    i + 1, entry.initials, entry.score, entry.start_level, blind, time, entry.date
+                )
+            })
+            .collect()
     }
 }
 
-// --- NEW HELPER FUNCTIONS ---
+const SPRINT_TIMES_FILE: &str = "sprint_times.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SprintEntry {
+    initials: String,
+    time_ms: u64,
+    date: String,
+}
+
+/// The fastest sprint times, a separate top-10 table for each line goal, fastest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SprintTimes {
+    boards: BTreeMap<u32, Vec<SprintEntry>>,
+}
+
+impl SprintTimes {
+    /// Loads the tables from "sprint_times.json"; a missing or invalid file yields empty tables.
+    fn load(dir: &Path) -> Self {
+        let mut times: SprintTimes = fs::read_to_string(dir.join(SPRINT_TIMES_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        for board in times.boards.values_mut() {
+            board.sort_by_key(|entry| entry.time_ms);
+            board.truncate(MAX_HIGH_SCORES);
+        }
+        times
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        fs::write(dir.join(SPRINT_TIMES_FILE), serialized)
+    }
+
+    /// Inserts a finished sprint to `lines` lines if it makes that goal's table,
+    /// returning its rank (0-based). The caller fills in the initials.
+    fn try_insert(&mut self, lines: u32, time_ms: u64) -> Option<usize> {
+        let board = self.boards.entry(lines).or_default();
+        let rank = board.iter().position(|entry| time_ms < entry.time_ms).unwrap_or(board.len());
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+        board.insert(rank, SprintEntry { initials: String::new(), time_ms, date: today() });
+        board.truncate(MAX_HIGH_SCORES);
+        Some(rank)
+    }
+
+    /// The table for `lines` as lines for the end screen.
+    fn rows(&self, lines: u32) -> Vec<String> {
+        self.boards.get(&lines).map_or_else(Vec::new, |board| {
+            board
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    let time = format_time(Duration::from_millis(entry.time_ms));
+                    format!("{:>2}. {:<3} {:>8} {:<10}", i + 1, entry.initials, time, entry.date)
+                })
+                .collect()
+        })
+    }
+}
+
+const ULTRA_SCORES_FILE: &str = "ultra_scores.json";
+
+/// The best ultra scores, a separate leaderboard for each time limit (in seconds).
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UltraScores {
+    boards: BTreeMap<u64, HighScores>,
+}
+
+impl UltraScores {
+    /// Loads the tables from "ultra_scores.json"; a missing or invalid file yields empty tables.
+    fn load(dir: &Path) -> Self {
+        let mut scores: UltraScores = fs::read_to_string(dir.join(ULTRA_SCORES_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        for board in scores.boards.values_mut() {
+            board.entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+            board.entries.truncate(MAX_HIGH_SCORES);
+        }
+        scores
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        fs::write(dir.join(ULTRA_SCORES_FILE), serialized)
+    }
+
+    /// The leaderboard for games of `time_limit`.
+    fn board(&mut self, time_limit: Duration) -> &mut HighScores {
+        self.boards.entry(time_limit.as_secs()).or_default()
+    }
+}
 
-/// Loads the high score from "highscore.txt". Returns 0 if the file doesn't exist or contains invalid data.
-fn load_high_score() -> u32 {
-    fs::read_to_string("highscore.txt")
-        .unwrap_or_else(|_| "0".to_string())
-        .trim()
-        .parse()
-        .unwrap_or(0)
+/// Today's UTC date formatted as YYYY-MM-DD.
+fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
 }
 
-/// Saves the given score to "highscore.txt", overwriting it.
-fn save_high_score(score: u32) -> io::Result<()> {
-    fs::write("highscore.txt", score.to_string())
+// --- LIFETIME STATS ---
+
+const LIFETIME_STATS_FILE: &str = "lifetime_stats.json";
+
+/// Totals across every finished game, shown on the start screen.
+///
+/// The file is read once at startup and rewritten after each game, so two copies of
+/// the game running at once would overwrite each other's totals. Only one instance
+/// is expected to run at a time.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+struct LifetimeStats {
+    games_played: u32,
+    lines_cleared: u64,
+    play_time_ms: u64,
+    best_score: u64,
 }
 
+impl LifetimeStats {
+    /// Loads the totals from "lifetime_stats.json"; a missing or corrupt file starts from zero.
+    fn load(dir: &Path) -> Self {
+        fs::read_to_string(dir.join(LIFETIME_STATS_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, dir: &Path) -> io::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)
+            .map_err(io::Error::other)?;
+        fs::write(dir.join(LIFETIME_STATS_FILE), serialized)
+    }
+
+    /// Adds a finished game to the totals.
+    fn record(&mut self, game: &Game) {
+        self.games_played += 1;
+        self.lines_cleared += u64::from(game.lines_cleared());
+        self.play_time_ms += game.play_time().as_millis() as u64;
+        self.best_score = self.best_score.max(game.score());
+    }
+
+    /// Lines for the start screen.
+    fn lines(&self) -> Vec<String> {
+        let secs = self.play_time_ms / 1000;
+        vec![
+            format!("Games {}   Lines {}", self.games_played, self.lines_cleared),
+            format!("Play time {}:{:02}:{:02}   Best {}", secs / 3600, secs / 60 % 60, secs % 60, self.best_score),
+        ]
+    }
+}
+
+// --- DATA DIRECTORY ---
+
+/// Subdirectory of the platform data directory that holds the game's files.
+const DATA_DIR_NAME: &str = "mad_tris";
+
+/// Where saves, scores and stats live unless `--save-dir` says otherwise:
+/// `$XDG_DATA_HOME/mad_tris`, else the platform's usual per-user data directory.
+fn default_data_dir() -> PathBuf {
+    let env_dir = |name: &str| std::env::var_os(name).map(PathBuf::from).filter(|dir| dir.is_absolute());
+    data_dir_from(env_dir("XDG_DATA_HOME"), env_dir("HOME"), env_dir("APPDATA"))
+}
+
+fn data_dir_from(xdg_data_home: Option<PathBuf>, home: Option<PathBuf>, appdata: Option<PathBuf>) -> PathBuf {
+    let base = xdg_data_home.or_else(|| {
+        if cfg!(windows) {
+            appdata
+        } else if cfg!(target_os = "macos") {
+            home.map(|home| home.join("Library").join("Application Support"))
+        } else {
+            home.map(|home| home.join(".local").join("share"))
+        }
+    });
+    // With nowhere better to go, keep the old behavior of using the working directory.
+    base.map_or_else(PathBuf::new, |base| base.join(DATA_DIR_NAME))
+}
+
+/// Creates `dir` if needed and moves over any files an older version left in the
+/// working directory. If `dir` can't be created, warns and uses the working directory.
+fn prepare_data_dir(dir: PathBuf) -> PathBuf {
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("warning: could not create {}: {}; saving to the working directory", dir.display(), e);
+        return PathBuf::new();
+    }
+    let mut names = vec![
+        HIGH_SCORES_FILE.to_string(),
+        LEGACY_HIGH_SCORE_FILE.to_string(),
+        SPRINT_TIMES_FILE.to_string(),
+        ULTRA_SCORES_FILE.to_string(),
+        LIFETIME_STATS_FILE.to_string(),
+    ];
+    names.extend((1..=SAVE_SLOTS).filter_map(|slot| save_file(slot).ok()));
+    for msg in migrate_files(Path::new("."), &dir, &names) {
+        eprintln!("warning: {}", msg);
+    }
+    dir
+}
+
+/// Moves each of `names` from `from` to `to` unless `to` already has a file by that
+/// name. Returns a message for every file that couldn't be moved.
+fn migrate_files(from: &Path, to: &Path, names: &[String]) -> Vec<String> {
+    let same_dir = match (from.canonicalize(), to.canonicalize()) {
+        (Ok(from), Ok(to)) => from == to,
+        _ => false,
+    };
+    if same_dir {
+        return Vec::new();
+    }
+    let mut errors = Vec::new();
+    for name in names {
+        let (source, target) = (from.join(name), to.join(name));
+        if !source.is_file() || target.exists() {
+            continue;
+        }
+        // Renaming fails across filesystems, so fall back to copying.
+        let moved = fs::rename(&source, &target)
+            .or_else(|_| fs::copy(&source, &target).and_then(|_| fs::remove_file(&source)));
+        if let Err(e) = moved {
+            errors.push(format!("could not move {} to {}: {}", source.display(), to.display(), e));
+        }
+    }
+    errors
+}
+
+// --- NEW HELPER FUNCTIONS ---
+
 /// Consumes and discards all pending input events from the queue.
 fn drain_event_queue() -> io::Result<()> {
     while event::poll(Duration::from_millis(0))? {
@@ -479,113 +1859,1175 @@ fn drain_event_queue() -> io::Result<()> {
     Ok(())
 }
 
-/// Displays a centered start screen and waits for any key press.
-fn show_start_screen<W: Write>(w: &mut W) -> io::Result<()> {
-    let (width, height) = terminal::size()?;
-    let title = "RUST TETRIS";
-    let msg = "Press any key to start";
+/// Time between frames of the start screen's demo; slower than play to go easy on the CPU.
+const DEMO_FRAME_TIME: Duration = Duration::from_millis(50);
+/// How long the demo bot waits between placing pieces.
+const DEMO_MOVE_TIME: Duration = Duration::from_millis(400);
 
-    queue!(w, terminal::Clear(terminal::ClearType::All))?;
-    queue!(w, cursor::MoveTo((width - title.len() as u16) / 2, height / 2 - 2))?;
-    queue!(w, style::SetForegroundColor(style::Color::Yellow), style::Print(title))?;
+/// A game the bot plays by itself on the start screen, attract-mode style.
+struct Demo {
+    game: Game,
+    bot: HeuristicBot,
+    since_move: Duration,
+}
+
+impl Demo {
+    /// Starts a demo on the player's board with their look, but endless and with
+    /// none of the options that would get in the bot's way.
+    fn new(columns: usize, lines: usize, options: &GameOptions) -> Self {
+        let options = GameOptions {
+            seed: None,
+            record_events: false,
+            garbage_rate: 0,
+            invisible: false,
+            mode: GameMode::Marathon { lines: 0 },
+            ..options.clone()
+        };
+        Demo { game: Game::new(columns, lines, options), bot: HeuristicBot::default(), since_move: Duration::ZERO }
+    }
+
+    /// Advances the demo by one simulation step, placing a piece whenever one is due.
+    /// A demo that tops out starts over.
+    fn step(&mut self) {
+        self.game.update(TICK);
+        self.since_move += TICK;
+        if self.game.is_game_over() {
+            *self = Demo::new(self.game.width(), self.game.height(), self.game.options());
+        } else if self.since_move >= DEMO_MOVE_TIME && self.game.clearing_rows().is_none() {
+            self.since_move = Duration::ZERO;
+            if let Some(placement) = self.bot.choose(&self.game) {
+                play_move(&mut self.game, placement);
+            }
+        }
+    }
+}
+
+/// Displays a centered start screen over a dimmed demo game until any key is pressed.
+/// If `resumable`, it offers to resume the autosaved game instead and returns whether
+/// the player took it up with Y.
+fn show_start_screen<W: Write>(
+    w: &mut W,
+    lifetime: &LifetimeStats,
+    columns: usize,
+    lines: usize,
+    options: &GameOptions,
+    resumable: bool,
+) -> io::Result<bool> {
+    let mut demo = Demo::new(columns, lines, options);
+    let mut timestep = FixedTimestep::new(TICK, MAX_STEPS_PER_FRAME);
+    let mut last_step = Instant::now();
+    loop {
+        // Wait for a key press (ignoring releases of keys pressed earlier) until the
+        // next demo frame is due. Resizes are picked up by the redraw below.
+        if event::poll(DEMO_FRAME_TIME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    return Ok(resumable && matches!(key.code, KeyCode::Char('y' | 'Y')));
+                }
+            }
+        }
+        let now = Instant::now();
+        for _ in 0..timestep.advance(now - last_step) {
+            demo.step();
+        }
+        last_step = now;
+
+        let (width, height) = terminal::size()?;
+        let title = "RUST TETRIS";
+        let msg = if resumable {
+            "Resume previous game? (Y/N)".to_string()
+        } else {
+            format!("Press any key to start at level {}", options.start_level)
+        };
 
-    queue!(w, cursor::MoveTo((width - msg.len() as u16) / 2, height / 2))?;
-    queue!(w, style::SetForegroundColor(style::Color::White), style::Print(msg))?;
-    w.flush()?;
+        draw_game(&demo.game, &Controls::default(), &mut Backdrop(CrosstermRenderer { out: w }))?;
+        queue!(w, cursor::MoveTo(width.saturating_sub(title.len() as u16) / 2, (height / 2).saturating_sub(2)))?;
+        queue!(w, style::SetForegroundColor(style::Color::Yellow), style::Print(title))?;
+
+        queue!(w, cursor::MoveTo(width.saturating_sub(msg.len() as u16) / 2, height / 2))?;
+        queue!(w, style::SetForegroundColor(style::Color::White), style::Print(msg))?;
+
+        if lifetime.games_played > 0 {
+            for (i, line) in lifetime.lines().iter().enumerate() {
+                queue!(w, cursor::MoveTo(width.saturating_sub(line.len() as u16) / 2, height / 2 + 2 + i as u16))?;
+                queue!(w, style::SetForegroundColor(style::Color::Grey), style::Print(line))?;
+            }
+        }
+        w.flush()?;
+    }
+}
 
-    // Block until any key is pressed
-    event::read()?;
+/// How long each step of the countdown before a game stays up.
+const COUNTDOWN_STEP: Duration = Duration::from_millis(700);
+
+/// Counts 3-2-1-GO down over the dimmed board so pieces don't start falling the moment
+/// the start screen closes. The game isn't updated meanwhile, so its clocks only start
+/// once play does. Any key press skips the rest; none of them reach the game.
+fn show_countdown<W: Write>(w: &mut W, game: &Game) -> io::Result<()> {
+    for step in ["3", "2", "1", "GO!"] {
+        let (columns, rows) = terminal::size()?;
+        draw_game(game, &Controls::default(), &mut Backdrop(CrosstermRenderer { out: w }))?;
+        if let Some((x, y)) = board_center(game, columns, rows) {
+            queue!(w, cursor::MoveTo(x.saturating_sub(step.len() as u16 / 2), y))?;
+            queue!(w, style::SetForegroundColor(style::Color::Yellow), style::Print(step))?;
+        }
+        w.flush()?;
+        let shown = Instant::now();
+        while let Some(left) = COUNTDOWN_STEP.checked_sub(shown.elapsed()) {
+            if event::poll(left)? {
+                if let Event::Key(KeyEvent { kind: KeyEventKind::Press, .. }) = event::read()? {
+                    return Ok(());
+                }
+            }
+        }
+    }
     Ok(())
 }
 
-/// Displays the end screen with final score, high score, and options.
-fn show_end_screen<W: Write>(w: &mut W, score: u32, high_score: u32) -> io::Result<()> {
-    let (width, height) = terminal::size()?;
-    let title = "GAME OVER";
-    let score_text = format!("Final Score: {}", score);
-    let high_score_text = format!("High Score: {}", high_score);
-    let msg = "R: Restart, Q: Quit";
+/// Initials entered when the player skips the prompt with Esc.
+const SKIPPED_INITIALS: &str = "---";
 
-    queue!(w, terminal::Clear(terminal::ClearType::All))?;
+/// The three letters of a high score entry being edited, with one slot highlighted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InitialsEntry {
+    letters: [char; 3],
+    slot: usize,
+}
 
-    queue!(w, cursor::MoveTo((width - title.len() as u16) / 2, height / 2 - 3))?;
-    queue!(w, style::SetForegroundColor(style::Color::Red), style::Print(title))?;
+impl Default for InitialsEntry {
+    fn default() -> Self {
+        InitialsEntry { letters: ['A'; 3], slot: 0 }
+    }
+}
 
-    queue!(w, cursor::MoveTo((width - score_text.len() as u16) / 2, height / 2 - 1))?;
-    queue!(w, style::SetForegroundColor(style::Color::White), style::Print(score_text))?;
+impl InitialsEntry {
+    /// Applies a key press, returning the initials once Enter confirms them or Esc skips.
+    /// Up/Down cycle the highlighted letter through A-Z, Left/Right pick the slot,
+    /// typing a letter fills the slot and moves on, and Backspace resets the highlighted
+    /// letter (or, if it is already reset, the one before it).
+    fn press(&mut self, code: KeyCode) -> Option<String> {
+        let shift = |letter: char, by: u8| (b'A' + (letter as u8 - b'A' + by) % 26) as char;
+        match code {
+            KeyCode::Up => self.letters[self.slot] = shift(self.letters[self.slot], 1),
+            KeyCode::Down => self.letters[self.slot] = shift(self.letters[self.slot], 25),
+            KeyCode::Left => self.slot = self.slot.saturating_sub(1),
+            KeyCode::Right => self.slot = (self.slot + 1).min(2),
+            KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+                self.letters[self.slot] = c.to_ascii_uppercase();
+                self.slot = (self.slot + 1).min(2);
+            }
+            KeyCode::Backspace => {
+                if self.letters[self.slot] == 'A' {
+                    self.slot = self.slot.saturating_sub(1);
+                }
+                self.letters[self.slot] = 'A';
+            }
+            KeyCode::Enter => return Some(self.letters.iter().collect()),
+            KeyCode::Esc => return Some(SKIPPED_INITIALS.to_string()),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Asks for the player's three initials on a new leaderboard entry, under `title`
+/// and the game's `result`, until they are confirmed with Enter or skipped with Esc.
+/// See [`InitialsEntry::press`] for the keys. Input left over from the game is
+/// dropped first so it can't fill in letters.
+fn prompt_initials<W: Write>(w: &mut W, title: &str, result: &str) -> io::Result<String> {
+    drain_event_queue()?;
+    let mut entry = InitialsEntry::default();
+    let hint = "Up/Down letter  Left/Right slot  Enter OK  Esc skip";
+    loop {
+        let (width, height) = terminal::size()?;
+        let centered = |text: &str| width.saturating_sub(text.chars().count() as u16) / 2;
+        // "Initials:" then each letter in its own three-column slot.
+        let entry_x = centered("Initials: _  _  _");
+
+        queue!(w, terminal::Clear(terminal::ClearType::All))?;
+        queue!(w, cursor::MoveTo(centered(title), (height / 2).saturating_sub(2)))?;
+        queue!(w, style::SetForegroundColor(style::Color::Yellow), style::Print(title))?;
+        queue!(w, cursor::MoveTo(centered(result), height / 2))?;
+        queue!(w, style::SetForegroundColor(style::Color::White), style::Print(result))?;
+        queue!(w, cursor::MoveTo(entry_x, height / 2 + 2), style::Print("Initials:"))?;
+        for (i, &letter) in entry.letters.iter().enumerate() {
+            let (text, color) = if i == entry.slot {
+                (format!("[{}]", letter), style::Color::Yellow)
+            } else {
+                (format!(" {} ", letter), style::Color::White)
+            };
+            queue!(w, cursor::MoveTo(entry_x + 9 + i as u16 * 3, height / 2 + 2))?;
+            queue!(w, style::SetForegroundColor(color), style::Print(text))?;
+        }
+        queue!(w, cursor::MoveTo(centered(hint), height / 2 + 4))?;
+        queue!(w, style::SetForegroundColor(style::Color::DarkGrey), style::Print(hint))?;
+        w.flush()?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(initials) = entry.press(key.code) {
+                return Ok(initials);
+            }
+        }
+    }
+}
 
+/// What the end screen shows about a finished game.
+struct EndScreen {
+    title: &'static str,
+    title_color: style::Color,
+    /// How the stack killed the game, e.g. "LOCK OUT"; `None` for finished goals.
+    cause: Option<&'static str>,
+    /// The game's result, e.g. its final score.
+    result: String,
+    /// Shown so the same piece sequence can be replayed with `--seed`.
+    seed: u64,
+    table_title: String,
+    rows: Vec<String>,
+    /// Row of the entry this game just added, highlighted.
+    new_rank: Option<usize>,
+    /// Label and value pairs describing how the game went.
+    summary: Vec<(&'static str, String)>,
+    /// Why the game's scores or stats couldn't be written, if they couldn't.
+    save_error: Option<String>,
+    /// Whether R plays again; otherwise only quitting is offered.
+    restart: bool,
+}
 
-    queue!(w, cursor::MoveTo((width - high_score_text.len() as u16) / 2, height / 2))?;
-    queue!(w, style::SetForegroundColor(style::Color::Yellow), style::Print(high_score_text))?;
+/// Label and value pairs for the end screen's game summary.
+fn game_summary(game: &Game) -> Vec<(&'static str, String)> {
+    let stats = game.stats();
+    vec![
+        ("Lines", game.lines_cleared().to_string()),
+        ("Level", game.level().to_string()),
+        ("Max combo", stats.max_combo.to_string()),
+        ("Tetrises", stats.clears[3].to_string()),
+        ("T-spins", stats.t_spins.to_string()),
+        ("Perfect", stats.perfect_clears.to_string()),
+        ("Pieces", stats.total_pieces().to_string()),
+        ("PPS", format!("{:.2}", game.pieces_per_second())),
+        ("Faults", stats.finesse_faults.to_string()),
+        ("Clear pts", stats.points.clears.to_string()),
+        ("T-spin pts", stats.points.t_spins.to_string()),
+        ("Combo pts", stats.points.combos.to_string()),
+        ("Cascade pts", stats.points.cascades.to_string()),
+        ("Perfect pts", stats.points.perfect_clears.to_string()),
+        ("Soft drop", stats.points.soft_drop.to_string()),
+        ("Hard drop", stats.points.hard_drop.to_string()),
+    ]
+}
 
-    queue!(w, cursor::MoveTo((width - msg.len() as u16) / 2, height / 2 + 2))?;
-    queue!(w, style::SetForegroundColor(style::Color::White), style::Print(msg))?;
+/// Width of one "label  value" cell in the summary.
+const SUMMARY_CELL_WIDTH: usize = 16;
+const SUMMARY_GAP: &str = "   ";
+
+/// Lays the summary out two cells per row when `width` allows, one per row otherwise.
+fn summary_rows(summary: &[(&str, String)], width: u16) -> Vec<String> {
+    let cell = |(label, value): &(&str, String)| {
+        format!("{:<w$}{}", label, value, w = SUMMARY_CELL_WIDTH.saturating_sub(value.len()))
+    };
+    let per_row = if width as usize >= SUMMARY_CELL_WIDTH * 2 + SUMMARY_GAP.len() { 2 } else { 1 };
+    summary
+        .chunks(per_row)
+        .map(|pair| pair.iter().map(cell).collect::<Vec<_>>().join(SUMMARY_GAP))
+        .collect()
+}
+
+/// Displays the end screen with the game's result and summary, a leaderboard, and
+/// options. On short terminals the spacing goes first, then the bottom of the
+/// leaderboard; lines wider than the terminal are cut off.
+fn show_end_screen<W: Write>(w: &mut W, screen: &EndScreen) -> io::Result<()> {
+    let (width, height) = terminal::size()?;
+    let white = style::Color::White;
+
+    // `None` is a blank spacer line.
+    let mut lines: Vec<Option<(String, style::Color)>> = vec![
+        Some((screen.title.to_string(), screen.title_color)),
+        screen.cause.map(|cause| (cause.to_string(), screen.title_color)),
+        Some((screen.result.clone(), white)),
+        Some((format!("Seed: {}", screen.seed), style::Color::DarkGrey)),
+        None,
+    ];
+    if let Some(error) = &screen.save_error {
+        lines.insert(4, Some((error.clone(), style::Color::Red)));
+    }
+    lines.extend(summary_rows(&screen.summary, width).into_iter().map(|row| Some((row, white))));
+    lines.push(None);
+    lines.push(Some((screen.table_title.clone(), style::Color::Yellow)));
+    let mut table_start = lines.len();
+    for (i, row) in screen.rows.iter().enumerate() {
+        let color = if Some(i) == screen.new_rank { style::Color::Yellow } else { white };
+        lines.push(Some((row.clone(), color)));
+    }
+    lines.push(None);
+    let options = if screen.restart { "R: Restart, Q: Quit" } else { "Q: Quit" };
+    lines.push(Some((options.to_string(), white)));
+
+    let height = height as usize;
+    if lines.len() > height {
+        table_start -= lines[..table_start].iter().filter(|line| line.is_none()).count();
+        lines.retain(Option::is_some);
+    }
+    if lines.len() > height {
+        let dropped = (lines.len() - height).min(screen.rows.len());
+        let table_end = table_start + screen.rows.len();
+        lines.drain(table_end - dropped..table_end);
+    }
+    lines.truncate(height);
+
+    queue!(w, terminal::Clear(terminal::ClearType::All))?;
+    let top = (height - lines.len()) / 2;
+    for (i, line) in lines.iter().enumerate() {
+        if let Some((text, color)) = line {
+            let text: String = text.chars().take(width as usize).collect();
+            let x = width.saturating_sub(text.chars().count() as u16) / 2;
+            queue!(w, cursor::MoveTo(x, (top + i) as u16))?;
+            queue!(w, style::SetForegroundColor(*color), style::Print(text))?;
+        }
+    }
 
     w.flush()
 }
 
+/// Shows the end screen and waits for R (true, to play again, if the screen offers it)
+/// or Q/Esc (false), redrawing it when the terminal is resized.
+fn wait_for_restart<W: Write>(w: &mut W, screen: &EndScreen) -> io::Result<bool> {
+    show_end_screen(w, screen)?;
+    loop {
+        match event::read()? {
+            // Only react to key presses to avoid double inputs.
+            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Char('r') | KeyCode::Char('R') if screen.restart => return Ok(true),
+                KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => return Ok(false),
+                _ => {}
+            },
+            Event::Resize(..) => show_end_screen(w, screen)?,
+            _ => {}
+        }
+    }
+}
+
+// --- BENCHMARK ---
+
+/// Pieces after which a benchmark game is stopped, in case the bot never tops out.
+const MAX_BENCH_PIECES: u32 = 10_000;
+
+/// Plays `games` games with the heuristic bot as fast as possible and prints totals.
+/// With `--seed`, game `i` uses seed + i so runs are reproducible.
+fn run_bench(games: u32, columns: usize, lines: usize, options: &GameOptions) {
+    let mut pieces = 0u64;
+    let mut total_score = 0u64;
+    let mut total_lines = 0u64;
+    let started = Instant::now();
+    for i in 0..games {
+        let options = GameOptions {
+            seed: options.seed.map(|seed| seed.wrapping_add(u64::from(i))),
+            // No time passes between bot moves, so clears have to happen at once.
+            line_clear_delay: Duration::ZERO,
+            ..options.clone()
+        };
+        let mut game = Game::new(columns, lines, options);
+        pieces += u64::from(play_game(&mut game, &mut HeuristicBot::default(), MAX_BENCH_PIECES));
+        total_score += game.score();
+        total_lines += u64::from(game.lines_cleared());
+    }
+    let secs = started.elapsed().as_secs_f64();
+    println!("games:          {}", games);
+    println!("pieces placed:  {}", pieces);
+    println!("time:           {:.3}s", secs);
+    println!("pieces/second:  {:.0}", pieces as f64 / secs.max(f64::EPSILON));
+    println!("average score:  {:.1}", total_score as f64 / f64::from(games));
+    println!("average lines:  {:.1}", total_lines as f64 / f64::from(games));
+}
+
 // --- MAIN FUNCTION ---
 
 fn main() -> io::Result<()> {
-    let args = Args::parse();
-    let mut stdout = io::stdout();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let (config, warnings) = Config::load();
+    for warning in warnings {
+        eprintln!("warning: {}: {}", CONFIG_FILE, warning);
+    }
+    config.apply(&mut args, &matches);
+    let (columns, lines) = match validate_args(&args) {
+        Ok(dimensions) => dimensions,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            std::process::exit(2);
+        }
+    };
+    let pieces = match &args.pieces {
+        Some(path) => load_pieces(path).map_err(|msg| format!("{}: {}", path.display(), msg)),
+        None => Ok(standard_pieces()),
+    };
+    let pieces = match pieces.and_then(|pieces| check_pieces_fit(&pieces, columns, lines).map(|()| pieces)) {
+        Ok(pieces) => pieces,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            std::process::exit(2);
+        }
+    };
+    let setup = args.setup.as_ref().map(|path| match load_setup(path, &pieces, columns, lines) {
+        Ok(setup) => Arc::new(setup),
+        Err(msg) => {
+            eprintln!("error: {}: {}", path.display(), msg);
+            std::process::exit(2);
+        }
+    });
+    let bindings = config.controls.unwrap_or_else(|| {
+        KeyBindings::load().unwrap_or_else(|msg| {
+            eprintln!("warning: {}; using default controls", msg);
+            KeyBindings::default()
+        })
+    });
+    let data_dir = prepare_data_dir(args.save_dir.clone().unwrap_or_else(default_data_dir));
+    if let Some(games) = args.bench {
+        run_bench(games, columns, lines, &game_options(&args, &data_dir, pieces, setup, config.scoring));
+        return Ok(());
+    }
+    if args.json_events {
+        // No key releases arrive on stdin, so every move command is a single step.
+        let mut game = Game::new(columns, lines, game_options(&args, &data_dir, pieces, setup, config.scoring));
+        return tetris_tui::headless::run(&mut game, io::stdin().lock(), io::stdout().lock());
+    }
+    let versus_bindings = KeyBindings::versus();
+    let net = args.host.is_some() || args.connect.is_some();
+    if args.versus || net {
+        let options = game_options(&args, &data_dir, pieces.clone(), setup.clone(), config.scoring);
+        // Checked up front: there is no point starting a match that can't be drawn.
+        let versus = Versus::new(columns, lines, options);
+        let (need_columns, need_rows) = min_split_size(&[&versus.games[0], &versus.games[1]]);
+        let (have_columns, have_rows) = terminal::size()?;
+        if have_columns < need_columns || have_rows < need_rows {
+            eprintln!(
+                "error: two {}x{} boards need a terminal of at least {}x{}; this one is {}x{}",
+                columns, lines, need_columns, need_rows, have_columns, have_rows
+            );
+            std::process::exit(2);
+        }
+    }
+    // Connected before the terminal is taken over, so the wait can be seen and interrupted.
+    let connection = match (args.host, &args.connect) {
+        (Some(port), _) => {
+            println!("Waiting for the other player to connect on port {}...", port);
+            Some(Connection::host(port))
+        }
+        (None, Some(addr)) => Some(Connection::connect(addr.as_str())),
+        (None, None) => None,
+    };
+    let connection = connection.transpose().unwrap_or_else(|e| {
+        eprintln!("error: could not connect: {}", e);
+        std::process::exit(1);
+    });
+    // With --export-cast everything drawn is copied into the recording as well.
+    let cast: Box<dyn Write> = match &args.export_cast {
+        Some(path) => {
+            let (width, height) = terminal::size()?;
+            let file = io::BufWriter::new(fs::File::create(path)?);
+            Box::new(CastWriter::new(file, width, height)?)
+        }
+        None => Box::new(io::sink()),
+    };
+    let mut stdout = TeeWriter::new(io::stdout(), cast);
+
+    // A panic would otherwise leave the terminal in raw mode with mouse capture on and
+    // its message lost in the alternate screen, so put the terminal back first. The
+    // guard covers early returns; it runs too late for the message on a panic.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal(&mut io::stdout());
+        default_hook(info);
+    }));
 
     // Setup terminal
     terminal::enable_raw_mode()?;
+    RAW_MODE.store(true, Ordering::SeqCst);
+    let _guard = TerminalGuard(io::stdout());
     execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+    ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+    if args.mouse {
+        execute!(stdout, event::EnableMouseCapture)?;
+        MOUSE_CAPTURE.store(true, Ordering::SeqCst);
+    }
 
+    // DAS needs to see key releases. Terminals that can't report them fall back to
+    // their own key repeat: every Press/Repeat moves the piece one cell.
+    let key_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if key_releases {
+        execute!(stdout, event::PushKeyboardEnhancementFlags(event::KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        KEYBOARD_FLAGS.store(true, Ordering::SeqCst);
+    }
+    // Sound effects are driven by game events, so only record them when they'll be heard.
+    let mut sound = SoundEngine::new(args.mute, args.music.as_deref());
+    let options = GameOptions {
+        auto_shift: key_releases,
+        record_events: sound.is_available(),
+        ..game_options(&args, &data_dir, pieces, setup, config.scoring)
+    };
+
+    // Reported once the terminal is back to normal, like errors.
+    let mut warnings = Vec::new();
     // Use a closure to manage the main loop and errors, ensuring cleanup happens.
     let result = (|| {
-        let mut high_score = load_high_score();
+        let mut high_scores = HighScores::load(&data_dir);
+        let mut sprint_times = SprintTimes::load(&data_dir);
+        let mut ultra_scores = UltraScores::load(&data_dir);
+        let mut lifetime = LifetimeStats::load(&data_dir);
+
+        if let Some(connection) = connection {
+            let mut net = match args.host {
+                Some(_) => NetMatch::host(connection, columns, lines, options.clone())?,
+                None => NetMatch::join(connection, options.clone(), NET_HELLO_TIMEOUT)?,
+            };
+            // The host picked the board size, so check again that both boards fit.
+            let (need_columns, need_rows) = min_split_size(&[&net.game, &net.opponent]);
+            let (have_columns, have_rows) = terminal::size()?;
+            let (width, height) = (net.game.width(), net.game.height());
+            let problem = check_pieces_fit(net.game.pieces(), width, height).err().or_else(|| {
+                (have_columns < need_columns || have_rows < need_rows).then(|| {
+                    format!(
+                        "two {}x{} boards need a terminal of at least {}x{}; this one is {}x{}",
+                        width, height, need_columns, need_rows, have_columns, have_rows
+                    )
+                })
+            });
+            if let Some(problem) = problem {
+                net.leave();
+                return Err(io::Error::other(problem));
+            }
+            if let Some(outcome) = run_net(&mut net, &bindings, Pacing::from_args(&args), &mut sound, &mut stdout)? {
+                wait_for_restart(&mut stdout, &net_end_screen(&net, outcome))?;
+            }
+            return Ok(());
+        }
+
+        if let Some(path) = &args.watch {
+            return run_watch(path, Game::new(columns, lines, options.clone()), &bindings, &mut stdout);
+        }
+        let mut live = args.autosave_interval.map(|ms| LiveSave::new(&data_dir, Duration::from_millis(ms)));
 
         'main_loop: loop {
-            show_start_screen(&mut stdout)?;
+            let resumable = !args.versus && data_dir.join(AUTOSAVE_FILE).exists();
+            let resume = show_start_screen(&mut stdout, &lifetime, columns, lines, &options, resumable)?;
             drain_event_queue()?;
 
-            let mut game = Game::new(args.columns, args.lines);
-            game.run(&mut stdout)?;
+            if args.versus {
+                let mut versus = Versus::new(columns, lines, options.clone());
+                match run_versus(&mut versus, &versus_bindings, Pacing::from_args(&args), &mut sound, &mut stdout)? {
+                    Flow::QuitToMenu => {
+                        drain_event_queue()?;
+                        continue 'main_loop;
+                    }
+                    Flow::Quit => break 'main_loop,
+                    _ => {}
+                }
+                let Some(outcome) = versus.outcome() else { break 'main_loop };
+                if !wait_for_restart(&mut stdout, &versus_end_screen(&versus, outcome))? {
+                    break 'main_loop;
+                }
+                drain_event_queue()?;
+                continue 'main_loop;
+            }
+
+            let mut game = Game::new(columns, lines, options.clone());
+            if resume {
+                let msg = match game.resume_autosave() {
+                    Ok(true) => "Resumed previous game".to_string(),
+                    Ok(false) => "Resumed previous game (checksum mismatch)".to_string(),
+                    Err(e) => format!("Resume Failed: {}", e),
+                };
+                game.set_status_message(msg);
+            }
+            if !args.no_countdown {
+                show_countdown(&mut stdout, &game)?;
+                drain_event_queue()?;
+            }
+            let frame_stats = args.debug_overlay.then(FrameStats::default);
+            let pacing = Pacing::from_args(&args);
+            let flow = run(&mut game, &bindings, pacing, frame_stats, live.as_mut(), &mut sound, &mut stdout)?;
+            // A game left unfinished is kept to be resumed, and a finished one means
+            // there is nothing left to resume. Rows still flashing are cleared first,
+            // since a save can't hold them.
+            game.finish_line_clear();
+            if !game.is_game_over() {
+                if let Err(e) = game.autosave() {
+                    warnings.push(format!("could not autosave the game: {}", e));
+                }
+            } else if let Err(e) = fs::remove_file(data_dir.join(AUTOSAVE_FILE)) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    warnings.push(format!("could not delete the autosave: {}", e));
+                }
+            }
+            if flow == Flow::QuitToMenu {
+                drain_event_queue()?;
+                continue 'main_loop;
+            }
 
-            // If game.run() exited but the game wasn't over, the user must have
+            // If run() exited but the game wasn't over, the user must have
             // pressed 'Q' to quit mid-game.
-            if !game.is_game_over {
+            if !game.is_game_over() {
                 break 'main_loop;
             }
 
-            // Inside the main function's loop...
-            if game.score > high_score {
-                high_score = game.score;
-                // This will now crash and show an error if saving fails.
-                save_high_score(high_score)
-                    .expect("ERROR: Could not save the high score file!");
-}
-
-            show_end_screen(&mut stdout, game.score, high_score)?;
-
-            // Wait for user input on the end screen (R for restart, Q for quit).
-            loop {
-                if let Event::Key(key) = event::read()? {
-                    // Only react to key presses to avoid double inputs.
-                    if key.kind == KeyEventKind::Press {
-                        match key.code {
-                            KeyCode::Char('r') | KeyCode::Char('R') => {
-                                drain_event_queue()?;
-                                continue 'main_loop;
-                            }
-                            KeyCode::Char('q') | KeyCode::Char('Q') | KeyCode::Esc => {
-                                break 'main_loop;
-                            }
-                            _ => {}
-                        }
+            lifetime.record(&game);
+            // Undo makes practice scores meaningless, so they stay off the leaderboards,
+            // as do zen games (which only end here if a finished game was loaded).
+            let ranked = !game.options().practice && game.options().mode != GameMode::Zen;
+            // Reported on the end screen along with any leaderboard failure below.
+            let mut save_error = lifetime.save(&data_dir)
+                .err()
+                .map(|e| format!("Could not save lifetime stats: {}", e));
+            let cause = game.game_over_reason()
+                .filter(|&reason| reason != GameOverReason::GoalReached)
+                .map(GameOverReason::label);
+
+            let end_screen = if let GameMode::Sprint { lines: goal } = game.options().mode {
+                let time = format!("Time: {}", format_time(game.play_time()));
+                // Only finished sprints get a time; topping out just shows the progress.
+                let new_rank = if game.goal_reached() && ranked {
+                    sprint_times.try_insert(goal, game.play_time().as_millis() as u64)
+                } else {
+                    None
+                };
+                if let Some(rank) = new_rank {
+                    let initials = prompt_initials(&mut stdout, "NEW BEST TIME!", &time)?;
+                    sprint_times.boards.get_mut(&goal).expect("entry was just inserted")[rank].initials = initials;
+                    if let Err(e) = sprint_times.save(&data_dir) {
+                        save_error = Some(format!("Could not save sprint times: {}", e));
                     }
                 }
+                EndScreen {
+                    title: if game.goal_reached() { "SPRINT COMPLETE" } else { "GAME OVER" },
+                    title_color: if game.goal_reached() { style::Color::Green } else { style::Color::Red },
+                    cause,
+                    result: if game.goal_reached() { time } else { format!("Lines: {}/{}", game.lines_cleared(), goal) },
+                    seed: game.seed(),
+                    table_title: format!("BEST {}-LINE TIMES", goal),
+                    rows: sprint_times.rows(goal),
+                    new_rank,
+                    summary: game_summary(&game),
+                    save_error: save_error.clone(),
+                    restart: true,
+                }
+            } else if let GameMode::Ultra { time_limit } = game.options().mode {
+                let board = ultra_scores.board(time_limit);
+                let new_rank =
+                    if ranked { board.try_insert(game.score(), game.stats(), game.play_time()) } else { None };
+                if let Some(rank) = new_rank {
+                    let score = format!("Score: {}", game.score());
+                    board.entries[rank].initials = prompt_initials(&mut stdout, "NEW ULTRA HIGH SCORE!", &score)?;
+                    if let Err(e) = ultra_scores.save(&data_dir) {
+                        save_error = Some(format!("Could not save ultra scores: {}", e));
+                    }
+                }
+                EndScreen {
+                    title: if game.goal_reached() { "TIME'S UP" } else { "GAME OVER" },
+                    title_color: if game.goal_reached() { style::Color::Green } else { style::Color::Red },
+                    cause,
+                    result: format!("Final Score: {}", game.score()),
+                    seed: game.seed(),
+                    table_title: format!("BEST {} ULTRA SCORES", format_time(time_limit).trim_end_matches(".00")),
+                    rows: ultra_scores.board(time_limit).rows(),
+                    new_rank,
+                    summary: game_summary(&game),
+                    save_error: save_error.clone(),
+                    restart: true,
+                }
+            } else {
+                let new_rank =
+                    if ranked { high_scores.try_insert(game.score(), game.stats(), game.play_time()) } else { None };
+                if let Some(rank) = new_rank {
+                    let score = format!("Score: {}", game.score());
+                    high_scores.entries[rank].initials = prompt_initials(&mut stdout, "NEW HIGH SCORE!", &score)?;
+                    if let Err(e) = high_scores.save(&data_dir) {
+                        save_error = Some(format!("Could not save high scores: {}", e));
+                    }
+                }
+                EndScreen {
+                    title: if game.goal_reached() { "YOU WIN!" } else { "GAME OVER" },
+                    title_color: if game.goal_reached() { style::Color::Green } else { style::Color::Red },
+                    cause,
+                    result: format!("Final Score: {}  Time: {}", game.score(), format_time(game.play_time())),
+                    seed: game.seed(),
+                    table_title: "HIGH SCORES".to_string(),
+                    rows: high_scores.rows(),
+                    new_rank,
+                    summary: game_summary(&game),
+                    save_error: save_error.clone(),
+                    restart: true,
+                }
+            };
+
+            if !wait_for_restart(&mut stdout, &end_screen)? {
+                break 'main_loop;
             }
+            drain_event_queue()?;
         }
         Ok(())
     })(); // Immediately invoke the closure
 
-    // Cleanup terminal
-    execute!(stdout, cursor::Show, terminal::LeaveAlternateScreen)?;
-    terminal::disable_raw_mode()?;
+    let cleanup = restore_terminal(&mut stdout);
+
+    // Reported only now that the terminal is back to normal.
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if let Err(e) = result.and(cleanup) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// What `main` has changed about the terminal and not yet put back. Each is set once
+// its change is made, so restoring never undoes something that didn't happen.
+static RAW_MODE: AtomicBool = AtomicBool::new(false);
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+static MOUSE_CAPTURE: AtomicBool = AtomicBool::new(false);
+static KEYBOARD_FLAGS: AtomicBool = AtomicBool::new(false);
+
+/// Undoes the terminal setup in `main`. Every step runs even if an earlier one fails,
+/// so the shell is left as usable as possible; the first error is returned. Steps
+/// already undone are skipped, so it is safe to call more than once.
+fn restore_terminal<W: Write>(w: &mut W) -> io::Result<()> {
+    let undo = |flag: &AtomicBool| flag.swap(false, Ordering::SeqCst);
+    let mouse_capture = if undo(&MOUSE_CAPTURE) { execute!(w, event::DisableMouseCapture) } else { Ok(()) };
+    let pop_flags = if undo(&KEYBOARD_FLAGS) { execute!(w, event::PopKeyboardEnhancementFlags) } else { Ok(()) };
+    let leave_screen =
+        if undo(&ALTERNATE_SCREEN) { execute!(w, cursor::Show, terminal::LeaveAlternateScreen) } else { Ok(()) };
+    let raw_mode = if undo(&RAW_MODE) { terminal::disable_raw_mode() } else { Ok(()) };
+    mouse_capture.and(pop_flags).and(leave_screen).and(raw_mode)
+}
+
+/// Restores the terminal when dropped, so no way out of `main` (an early `?` return
+/// or a panic unwinding) leaves the shell in raw mode. Created right after raw mode
+/// is turned on.
+struct TerminalGuard<W: Write>(W);
+
+impl<W: Write> Drop for TerminalGuard<W> {
+    fn drop(&mut self) {
+        let _ = restore_terminal(&mut self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tetris_tui::render::StringRenderer;
+
+    fn args_for(columns: usize, lines: usize) -> Args {
+        Args::parse_from([
+            "tetris",
+            "--columns",
+            &columns.to_string(),
+            "--lines",
+            &lines.to_string(),
+        ])
+    }
+
+    #[test]
+    fn rejects_boards_smaller_than_four() {
+        assert!(validate_args(&args_for(3, 20)).is_err());
+        assert!(validate_args(&args_for(10, 3)).is_err());
+        assert!(validate_args(&args_for(0, 0)).is_err());
+    }
+
+    #[test]
+    fn accepts_minimum_board() {
+        assert_eq!(validate_args(&args_for(4, 4)), Ok((4, 4)));
+    }
+
+    #[test]
+    fn clamps_oversized_boards() {
+        assert_eq!(
+            validate_args(&args_for(usize::MAX, 1_000_000)),
+            Ok((MAX_COLUMNS, MAX_LINES))
+        );
+    }
+
+    #[test]
+    fn config_file_fills_in_flags_not_given_and_skips_bad_values() {
+        let text = "columns = 12\nlines = 2\ntheme = \"pastel\"\ndas_ms = 100\nmode = \"rocket\"\ncolour = 1\n\n\
+                    [controls]\nhard_drop = \"x\"\n\n\
+                    [scoring]\nlines = [40, 100, 300, 1200]\nlevel_multiplier = true\n";
+        let (config, warnings) = Config::from_toml(text);
+        assert_eq!(warnings.len(), 3, "{:?}", warnings);
+        assert_eq!(config.scoring, ScoringConfig { lines: [40, 100, 300, 1200], level_multiplier: true });
+        assert_eq!(Config::from_toml("[scoring]\nlines = [1, 2]\n").1.len(), 1);
+        assert!(warnings.iter().any(|w| w.starts_with("`lines`")));
+        assert!(config.mode.is_none());
+        let bindings = config.controls.as_ref().unwrap();
+        assert_eq!(bindings.action_for(Key::char('x')), Some(Action::HardDrop));
+
+        let matches = Args::command().get_matches_from(["tetris", "--das-ms", "50"]);
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        config.apply(&mut args, &matches);
+        assert_eq!((args.columns, args.lines, args.theme), (12, 20, Theme::Pastel));
+        assert_eq!(args.das_ms, 50);
+        assert_eq!(args.mode, ModeArg::Marathon);
+    }
+
+    #[test]
+    fn live_save_writes_states_a_watcher_can_load() {
+        let dir = std::env::temp_dir().join(format!("mad_tris_live_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let options = GameOptions { seed: Some(3), ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options.clone());
+        let start = game.state_hash();
+        let mut live = LiveSave::new(&dir, Duration::from_secs(3600));
+        live.update(&mut game);
+        let text = fs::read_to_string(dir.join(LIVE_FILE)).unwrap();
+
+        // Too soon for another write.
+        game.hard_drop();
+        live.update(&mut game);
+        assert_eq!(fs::read_to_string(dir.join(LIVE_FILE)).unwrap(), text);
+
+        let mut watched = Game::new(10, 20, GameOptions::default());
+        watched.load_state(serde_json::from_str(&text).unwrap()).unwrap();
+        assert_eq!(watched.state_hash(), start);
+        // A write caught halfway doesn't parse, so the watcher keeps what it has.
+        assert!(serde_json::from_str::<tetris_tui::game::SerializableGameState>(&text[..text.len() / 2]).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn initials_entry_cycles_types_and_skips() {
+        let mut entry = InitialsEntry::default();
+        for code in [KeyCode::Down, KeyCode::Right, KeyCode::Up, KeyCode::Up, KeyCode::Right, KeyCode::Char('q')] {
+            assert_eq!(entry.press(code), None);
+        }
+        assert_eq!(entry.letters, ['Z', 'C', 'Q']);
+        entry.press(KeyCode::Backspace);
+        entry.press(KeyCode::Backspace);
+        assert_eq!((entry.letters, entry.slot), (['Z', 'A', 'A'], 1));
+        entry.press(KeyCode::Char('k'));
+        assert_eq!(entry.press(KeyCode::Enter), Some("ZKA".to_string()));
+        assert_eq!(InitialsEntry::default().press(KeyCode::Esc), Some(SKIPPED_INITIALS.to_string()));
+    }
+
+    #[test]
+    fn high_score_insert_keeps_top_ten_sorted() {
+        let mut scores = HighScores::default();
+        for score in (1..=10).map(|n| n * 100) {
+            assert!(scores.try_insert(score, &Stats { start_level: 1, ..Stats::default() }, Duration::ZERO).is_some());
+        }
+        assert_eq!(scores.try_insert(50, &Stats::default(), Duration::ZERO), None);
+        let points = ScoreBreakdown { clears: 500, hard_drop: 50, ..ScoreBreakdown::default() };
+        let stats = Stats { start_level: 5, blind: true, points, ..Stats::default() };
+        assert_eq!(scores.try_insert(550, &stats, Duration::from_secs(754)), Some(5));
+        assert_eq!(scores.entries[5].start_level, 5);
+        assert_eq!(scores.entries[5].points, points);
+        assert!(scores.rows()[5].contains("L5 B 12:34"), "{:?}", scores.rows()[5]);
+        assert!(!scores.rows()[4].contains(':'), "{:?}", scores.rows()[4]);
+        assert_eq!(scores.entries.len(), MAX_HIGH_SCORES);
+        assert_eq!(scores.entries[0].score, 1000);
+        assert_eq!(scores.entries.last().unwrap().score, 200);
+    }
+
+    #[test]
+    fn terminal_guard_restores_on_panic_and_only_once() {
+        let mut out = Vec::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = TerminalGuard(&mut out);
+            ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+            MOUSE_CAPTURE.store(true, Ordering::SeqCst);
+            panic!("deliberate");
+        }));
+        assert!(result.is_err());
+        let written = String::from_utf8(out).unwrap();
+        assert!(written.contains("\x1b[?1049l"), "alternate screen left: {:?}", written);
+        assert!(written.contains("\x1b[?1000l"), "mouse capture off: {:?}", written);
+
+        let mut again = Vec::new();
+        restore_terminal(&mut again).unwrap();
+        assert!(again.is_empty());
+    }
+
+    #[test]
+    fn frame_stats_average_the_last_frames() {
+        let mut stats = FrameStats::default();
+        let mut start = Instant::now();
+        // Slow frames first, then enough fast ones to push them all out of the window.
+        for i in 0..100 {
+            start += Duration::from_millis(if i < 50 { 100 } else { 20 });
+            stats.record(start, Duration::from_millis(2), 7);
+        }
+        assert_eq!(stats.frames.len(), FRAME_STATS_WINDOW);
+        assert_eq!(stats.overlay(), "50 fps 2.00ms 7 writes");
+    }
+
+    #[test]
+    fn fixed_timestep_carries_remainder_and_caps_steps() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(4), MAX_STEPS_PER_FRAME);
+        assert_eq!(timestep.advance(Duration::from_millis(10)), 2);
+        // 2ms were left over from the last call.
+        assert_eq!(timestep.advance(Duration::from_millis(2)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 0);
+        assert_eq!(timestep.advance(Duration::from_secs(5)), MAX_STEPS_PER_FRAME);
+        // The rest of the stall was dropped.
+        assert_eq!(timestep.advance(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn sprint_times_are_kept_per_goal_fastest_first() {
+        let mut times = SprintTimes::default();
+        assert_eq!(times.try_insert(40, 90_000), Some(0));
+        assert_eq!(times.try_insert(40, 60_000), Some(0));
+        assert_eq!(times.try_insert(20, 120_000), Some(0));
+        assert_eq!(times.try_insert(40, 75_000), Some(1));
+        let fastest: Vec<u64> = times.boards[&40].iter().map(|entry| entry.time_ms).collect();
+        assert_eq!(fastest, [60_000, 75_000, 90_000]);
+        assert!(times.rows(40)[0].contains("1:00.00"));
+    }
+
+    #[test]
+    fn summary_uses_two_columns_only_when_they_fit() {
+        let summary = [("Lines", "42".to_string()), ("Level", "5".to_string()), ("PPS", "1.50".to_string())];
+        assert_eq!(summary_rows(&summary, 80), ["Lines         42   Level          5", "PPS         1.50"]);
+        assert_eq!(summary_rows(&summary, 20).len(), 3);
+    }
+
+    #[test]
+    fn lifetime_stats_add_up_finished_games() {
+        let game = Game::new(10, 20, GameOptions::default());
+        let mut lifetime = LifetimeStats { games_played: 2, lines_cleared: 30, play_time_ms: 3_600_000, best_score: 9000 };
+        lifetime.record(&game);
+        assert_eq!(lifetime.games_played, 3);
+        assert_eq!(lifetime.best_score, 9000);
+        assert_eq!(lifetime.lines()[1], "Play time 1:00:00   Best 9000");
+        // Fields missing from the file start at zero.
+        let partial: LifetimeStats = serde_json::from_str(r#"{"games_played": 4}"#).unwrap();
+        assert_eq!(partial, LifetimeStats { games_played: 4, ..LifetimeStats::default() });
+    }
+
+    #[test]
+    fn data_dir_prefers_xdg_then_the_platform_default() {
+        let home = Some(PathBuf::from("/home/ada"));
+        assert_eq!(data_dir_from(Some(PathBuf::from("/data")), home.clone(), None), Path::new("/data/mad_tris"));
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            assert_eq!(data_dir_from(None, home, None), Path::new("/home/ada/.local/share/mad_tris"));
+        }
+        assert_eq!(data_dir_from(None, None, None), PathBuf::new());
+    }
 
-    result
-}
\ No newline at end of file
+    #[test]
+    fn migration_moves_only_files_the_data_dir_lacks() {
+        let root = std::env::temp_dir().join(format!("mad_tris_migrate_{}", std::process::id()));
+        let (old, new) = (root.join("old"), root.join("new"));
+        fs::create_dir_all(&old).unwrap();
+        fs::create_dir_all(&new).unwrap();
+        fs::write(old.join("a.json"), "old a").unwrap();
+        fs::write(old.join("b.json"), "old b").unwrap();
+        fs::write(new.join("b.json"), "new b").unwrap();
+
+        let names = ["a.json".to_string(), "b.json".to_string(), "c.json".to_string()];
+        assert!(migrate_files(&old, &new, &names).is_empty());
+        assert_eq!(fs::read_to_string(new.join("a.json")).unwrap(), "old a");
+        assert!(!old.join("a.json").exists());
+        assert_eq!(fs::read_to_string(new.join("b.json")).unwrap(), "new b");
+        assert!(old.join("b.json").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn press(game: &mut Game, code: KeyCode) {
+        handle_key(game, &KeyBindings::default(), &mut None, KeyEvent::new(code, event::KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn versus_keys_move_only_their_players_piece() {
+        let mut versus = Versus::new(10, 20, GameOptions::default());
+        let bindings = KeyBindings::versus();
+        let press = |versus: &mut Versus, code| {
+            handle_versus_key(versus, &bindings, KeyEvent::new(code, event::KeyModifiers::NONE))
+        };
+        let x = |versus: &Versus| versus.games.each_ref().map(|game| game.active_piece().x);
+        let start = x(&versus);
+        press(&mut versus, KeyCode::Left);
+        press(&mut versus, KeyCode::Char('d'));
+        assert_eq!(x(&versus), [start[0] - 1, start[1] + 1]);
+
+        press(&mut versus, KeyCode::Char('p'));
+        assert!(versus.games.iter().all(Game::is_paused));
+        press(&mut versus, KeyCode::Char('a'));
+        assert_eq!(x(&versus), [start[0] - 1, start[1] + 1]);
+        assert_eq!(press(&mut versus, KeyCode::Enter), Flow::Continue);
+        assert!(!versus.games.iter().any(Game::is_paused));
+        assert_eq!(press(&mut versus, KeyCode::Esc), Flow::Continue);
+        assert_eq!(press(&mut versus, KeyCode::Char('y')), Flow::Quit);
+    }
+
+    #[test]
+    fn mirrored_left_key_moves_the_piece_left_on_screen() {
+        let mut game = Game::new(10, 20, GameOptions { mirror: true, ..GameOptions::default() });
+        // The first block character inside the board frame, laid out as in the render tests.
+        let screen_left = |game: &Game| {
+            let mut screen = StringRenderer::new(43, 25);
+            draw_game(game, &Controls::default(), &mut screen).unwrap();
+            (2..=21).filter_map(|y| screen.row(y).chars().skip(3).take(20).position(|c| c == '█')).min()
+        };
+        let (x, left) = (game.active_piece().x, screen_left(&game).unwrap());
+        press(&mut game, KeyCode::Left);
+        // The game itself moved the piece right, which the flipped board shows as left.
+        assert_eq!(game.active_piece().x, x + 1);
+        assert_eq!(screen_left(&game), Some(left - 2));
+    }
+
+    #[test]
+    fn soft_and_hard_drops_award_points_per_cell() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        for _ in 0..3 {
+            press(&mut game, KeyCode::Down);
+        }
+        assert_eq!(game.score(), 3);
+
+        let distance = (game.ghost_piece().y - game.active_piece().y) as u64;
+        press(&mut game, KeyCode::Char(' '));
+        assert_eq!(game.score(), 3 + distance * 2);
+    }
+
+    #[test]
+    fn slot_prompt_is_answered_or_cancelled_by_next_key() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let bindings = KeyBindings::default();
+        let mut prompt = None;
+        let key = |code| KeyEvent::new(code, event::KeyModifiers::NONE);
+
+        handle_key(&mut game, &bindings, &mut prompt, key(KeyCode::Char('l')));
+        assert_eq!(prompt, Some(Prompt::Load));
+        // Keys answering the prompt don't reach the game.
+        let x = game.active_piece().x;
+        handle_key(&mut game, &bindings, &mut prompt, key(KeyCode::Left));
+        assert_eq!(prompt, None);
+        assert_eq!(game.active_piece().x, x);
+        assert_eq!(game.status_message(), Some("Load cancelled"));
+    }
+
+    #[test]
+    fn quick_restart_needs_a_second_press() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let bindings = KeyBindings::default();
+        let mut prompt = None;
+        let mut press = |game: &mut Game, code| {
+            handle_key(game, &bindings, &mut prompt, KeyEvent::new(code, event::KeyModifiers::NONE))
+        };
+
+        assert_eq!(press(&mut game, KeyCode::Char('r')), Flow::Continue);
+        assert_eq!(game.status_message(), Some("R again to restart; score is lost"));
+        assert_eq!(press(&mut game, KeyCode::Char('x')), Flow::Continue);
+        assert_eq!(game.status_message(), Some("Restart cancelled"));
+        assert_eq!(press(&mut game, KeyCode::Char('r')), Flow::Continue);
+        assert_eq!(press(&mut game, KeyCode::Char('R')), Flow::Restart);
+    }
+
+    #[test]
+    fn quitting_mid_game_asks_first_and_holds_the_game_still() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(3), ..GameOptions::default() });
+        let bindings = KeyBindings::default();
+        let mut prompt = None;
+        let mut press = |game: &mut Game, code| {
+            handle_key(game, &bindings, &mut prompt, KeyEvent::new(code, event::KeyModifiers::NONE))
+        };
+
+        assert_eq!(press(&mut game, KeyCode::Char('q')), Flow::Continue);
+        assert!(game.is_confirming_quit() && game.is_paused());
+        let blocks = |game: &Game| game.active_piece().blocks(game.pieces()).collect::<Vec<_>>();
+        let piece = blocks(&game);
+        for _ in 0..100 {
+            game.update(TICK);
+        }
+        assert_eq!(press(&mut game, KeyCode::Left), Flow::Continue);
+        assert_eq!(blocks(&game), piece);
+        assert_eq!(press(&mut game, KeyCode::Esc), Flow::Continue);
+        assert!(!game.is_confirming_quit() && !game.is_paused());
+        // The wait didn't count: the piece only falls once a full gravity step has passed since resuming.
+        game.update(TICK);
+        assert_eq!(blocks(&game), piece);
+
+        // Asking from the pause menu goes back to it.
+        game.toggle_pause();
+        press(&mut game, KeyCode::Char('q'));
+        assert_eq!(press(&mut game, KeyCode::Char('n')), Flow::Continue);
+        assert!(game.is_paused());
+        press(&mut game, KeyCode::Char('q'));
+        assert_eq!(press(&mut game, KeyCode::Char('y')), Flow::Quit);
+
+        let mut game = Game::new(10, 20, GameOptions { confirm_quit: false, ..GameOptions::default() });
+        assert_eq!(press(&mut game, KeyCode::Char('q')), Flow::Quit);
+    }
+
+    #[test]
+    fn quitting_a_versus_match_asks_on_both_boards() {
+        let bindings = KeyBindings::versus();
+        let key = |code, kind| KeyEvent::new_with_kind(code, event::KeyModifiers::NONE, kind);
+        let press = |versus: &mut Versus, code| handle_versus_key(versus, &bindings, key(code, KeyEventKind::Press));
+
+        let mut versus = Versus::new(10, 20, GameOptions { seed: Some(3), ..GameOptions::default() });
+        let release = key(KeyCode::Esc, KeyEventKind::Release);
+        assert_eq!(handle_versus_key(&mut versus, &bindings, release), Flow::Continue);
+        assert!(!versus.is_confirming_quit());
+        assert_eq!(press(&mut versus, KeyCode::Esc), Flow::Continue);
+        assert!(versus.is_confirming_quit() && versus.games.iter().all(Game::is_paused));
+        assert_eq!(press(&mut versus, KeyCode::Char('n')), Flow::Continue);
+        assert!(!versus.is_confirming_quit() && !versus.is_paused());
+        press(&mut versus, KeyCode::Esc);
+        assert_eq!(press(&mut versus, KeyCode::Char('Y')), Flow::Quit);
+
+        let mut versus = Versus::new(10, 20, GameOptions { confirm_quit: false, ..GameOptions::default() });
+        assert_eq!(press(&mut versus, KeyCode::Esc), Flow::Quit);
+    }
+
+    #[test]
+    fn demo_places_pieces_on_its_own_and_ignores_player_modes() {
+        let options = GameOptions { invisible: true, mode: GameMode::Sprint { lines: 1 }, ..GameOptions::default() };
+        let mut demo = Demo::new(10, 20, &options);
+        assert_eq!(demo.game.line_goal(), None);
+        assert!(demo.game.reveals_left().is_none());
+        for _ in 0..(DEMO_MOVE_TIME * 5).as_millis() / TICK.as_millis() {
+            demo.step();
+        }
+        assert!(demo.game.stats().total_pieces() >= 5);
+    }
+
+    #[test]
+    fn clicks_move_rotate_and_drop_the_piece() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(3), ..GameOptions::default() });
+        let click = |button, column| MouseEvent {
+            kind: MouseEventKind::Down(button),
+            column,
+            row: 5,
+            modifiers: event::KeyModifiers::NONE,
+        };
+        let columns = |game: &Game| game.active_piece().blocks(game.pieces()).map(|(x, _)| x).collect::<Vec<_>>();
+
+        // Column 0 is drawn at screen x 3 and 4 on a 43x25 screen.
+        handle_mouse(&mut game, 43, 25, click(MouseButton::Left, 3));
+        assert_eq!(columns(&game).into_iter().min(), Some(0));
+
+        let rotation = game.active_piece().rotation;
+        handle_mouse(&mut game, 43, 25, click(MouseButton::Left, 3));
+        assert_ne!(game.active_piece().rotation, rotation);
+
+        let pieces = game.stats().total_pieces();
+        handle_mouse(&mut game, 43, 25, click(MouseButton::Right, 30));
+        assert_eq!(game.stats().total_pieces(), pieces + 1);
+    }
+
+    #[test]
+    fn ascii_is_chosen_for_locales_without_utf8() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+        };
+        assert!(!locale_lacks_unicode(env(&[("LANG", "en_US.UTF-8")])));
+        assert!(!locale_lacks_unicode(env(&[("LC_CTYPE", "de_DE.utf8"), ("LANG", "C")])));
+        assert!(locale_lacks_unicode(env(&[("LANG", "C")])));
+        // LC_ALL wins over everything else, and empty values are skipped.
+        assert!(locale_lacks_unicode(env(&[("LC_ALL", "POSIX"), ("LANG", "en_US.UTF-8")])));
+        assert!(!locale_lacks_unicode(env(&[("LC_ALL", ""), ("LANG", "en_US.UTF-8")])));
+        assert_eq!(locale_lacks_unicode(env(&[])), cfg!(windows));
+        assert!(!locale_lacks_unicode(env(&[("WT_SESSION", "1")])));
+    }
+}
@@ -0,0 +1,132 @@
+//! Computer players that drive a [`Game`] through the same actions a person would.
+//!
+//! A [`Bot`] only picks where the active piece should go; [`play_move`] then rotates,
+//! shifts and hard-drops it there. [`HeuristicBot`] is a simple one-piece lookahead
+//! that scores every landing spot by stack height, holes and bumpiness.
+
+use crate::game::{column_heights_in, holes_in, ActivePiece, Game, RotationDir, BUFFER_ROWS};
+
+/// Where to put the active piece: which rotation, and the column of its left edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub rotation: usize,
+    pub x: isize,
+}
+
+/// Something that can choose placements.
+pub trait Bot {
+    /// The placement for the active piece, or `None` if there is nowhere to put it.
+    fn choose(&mut self, game: &Game) -> Option<Placement>;
+}
+
+/// Picks the landing spot with the best weighted sum of aggregate height, completed
+/// lines, holes and bumpiness after the piece locks.
+#[derive(Debug, Clone, Copy)]
+pub struct HeuristicBot {
+    pub height_weight: f64,
+    pub lines_weight: f64,
+    pub holes_weight: f64,
+    pub bumpiness_weight: f64,
+}
+
+impl Default for HeuristicBot {
+    fn default() -> Self {
+        HeuristicBot { height_weight: -0.51, lines_weight: 0.76, holes_weight: -0.36, bumpiness_weight: -0.18 }
+    }
+}
+
+impl Bot for HeuristicBot {
+    fn choose(&mut self, game: &Game) -> Option<Placement> {
+        let piece = game.active_piece();
+        let rotations = piece.definition(game.pieces()).rotations.len();
+        let mut best: Option<(f64, Placement)> = None;
+        for rotation in 0..rotations {
+            let width = piece.definition(game.pieces()).size(rotation).0 as isize;
+            for x in 0..=(game.width() as isize - width) {
+                let mut candidate = ActivePiece { id: piece.id, rotation, x, y: -(BUFFER_ROWS as isize) };
+                if game.check_collision(&candidate) {
+                    continue;
+                }
+                while !game.check_collision(&ActivePiece { y: candidate.y + 1, ..candidate.clone() }) {
+                    candidate.y += 1;
+                }
+                let score = self.evaluate(game, &candidate);
+                if best.is_none_or(|(best_score, _)| score > best_score) {
+                    best = Some((score, Placement { rotation, x }));
+                }
+            }
+        }
+        best.map(|(_, placement)| placement)
+    }
+}
+
+impl HeuristicBot {
+    /// Scores the board as it would be with `landed` locked in place; higher is better.
+    fn evaluate(&self, game: &Game, landed: &ActivePiece) -> f64 {
+        let (width, height) = (game.width(), game.height());
+        let mut filled: Vec<bool> = game.board().iter().map(Option::is_some).collect();
+        for (x, y) in landed.blocks(game.pieces()) {
+            if y >= 0 {
+                filled[y as usize * width + x as usize] = true;
+            }
+        }
+        let full_rows: Vec<usize> = (0..height).filter(|&y| (0..width).all(|x| filled[y * width + x])).collect();
+        let lines = full_rows.len();
+        // Judge the stack as it will be once the completed rows are gone.
+        let rest: Vec<bool> = (0..height)
+            .filter(|y| !full_rows.contains(y))
+            .flat_map(|y| filled[y * width..(y + 1) * width].iter().copied())
+            .collect();
+
+        let heights = column_heights_in(&rest, width);
+        let holes = holes_in(&rest, width).len();
+        let aggregate: usize = heights.iter().sum();
+        let bumpiness: usize = heights.windows(2).map(|pair| pair[0].abs_diff(pair[1])).sum();
+
+        self.height_weight * aggregate as f64
+            + self.lines_weight * lines as f64
+            + self.holes_weight * holes as f64
+            + self.bumpiness_weight * bumpiness as f64
+    }
+}
+
+/// Rotates and shifts the active piece toward `placement`, then hard-drops it. If
+/// something is in the way the piece drops wherever it got to.
+pub fn play_move(game: &mut Game, placement: Placement) {
+    for _ in 0..placement.rotation {
+        game.try_rotate(RotationDir::Clockwise);
+    }
+    let dir = (placement.x - game.active_piece().x).signum();
+    while game.active_piece().x != placement.x && game.try_shift(dir) {}
+    game.hard_drop();
+}
+
+/// Lets `bot` play `game` until it ends or `max_pieces` pieces have been placed,
+/// returning how many were placed. The game should have no line-clear delay, since
+/// no time passes between moves.
+pub fn play_game<B: Bot>(game: &mut Game, bot: &mut B, max_pieces: u32) -> u32 {
+    let mut placed = 0;
+    while placed < max_pieces && !game.is_game_over() {
+        let Some(placement) = bot.choose(game) else { break };
+        play_move(game, placement);
+        placed += 1;
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameOptions;
+    use std::time::Duration;
+
+    #[test]
+    fn heuristic_bot_keeps_clearing_lines() {
+        let options = GameOptions { seed: Some(11), line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        let placed = play_game(&mut game, &mut HeuristicBot::default(), 300);
+        assert_eq!(placed, 300);
+        assert!(!game.is_game_over());
+        assert!(game.lines_cleared() >= 100);
+    }
+}
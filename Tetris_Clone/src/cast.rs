@@ -0,0 +1,110 @@
+//! Recording terminal output as an asciicast v2 file, the format `asciinema play` reads.
+//!
+//! A [`CastWriter`] turns whatever is written to it into timed output events, one per
+//! flush, so each drawn frame becomes one event. [`TeeWriter`] sends the same bytes to
+//! the real terminal and to a recorder.
+
+use std::io::{self, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+/// Writes everything to `inner` and a copy of it to `copy`.
+pub struct TeeWriter<W: Write, C: Write> {
+    inner: W,
+    copy: C,
+}
+
+impl<W: Write, C: Write> TeeWriter<W, C> {
+    pub fn new(inner: W, copy: C) -> Self {
+        TeeWriter { inner, copy }
+    }
+}
+
+impl<W: Write, C: Write> Write for TeeWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.copy.write_all(&buf[..written])?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.copy.flush()
+    }
+}
+
+/// Buffers terminal output and writes it to `out` as asciicast v2: a header line, then
+/// one `[seconds, "o", text]` line for each flush that had something new.
+pub struct CastWriter<W: Write> {
+    out: W,
+    start: Instant,
+    /// Output since the last event. A multi-byte character split across flushes waits
+    /// here until the rest of it arrives.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> CastWriter<W> {
+    /// Starts a recording of a `width` by `height` terminal, writing its header to `out`.
+    pub fn new(mut out: W, width: u16, height: u16) -> io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+        let header = json!({ "version": 2, "width": width, "height": height, "timestamp": timestamp });
+        writeln!(out, "{}", header)?;
+        Ok(CastWriter { out, start: Instant::now(), pending: Vec::new() })
+    }
+}
+
+impl<W: Write> Write for CastWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let complete = match std::str::from_utf8(&self.pending) {
+            Ok(text) => text.len(),
+            // Bytes that can never be valid are replaced rather than held forever.
+            Err(e) if e.error_len().is_some() => self.pending.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if complete > 0 {
+            let text = String::from_utf8_lossy(&self.pending[..complete]);
+            let event = json!([self.start.elapsed().as_secs_f64(), "o", text]);
+            writeln!(self.out, "{}", event)?;
+            self.pending.drain(..complete);
+        }
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tee_records_each_flushed_frame_as_an_output_event() {
+        let mut screen = Vec::new();
+        let mut cast = Vec::new();
+        {
+            let mut tee = TeeWriter::new(&mut screen, CastWriter::new(&mut cast, 80, 24).unwrap());
+            write!(tee, "\x1b[2J╔").unwrap();
+            tee.flush().unwrap();
+            tee.flush().unwrap();
+            // Half of a three-byte character waits for the rest.
+            tee.write_all(&"═".as_bytes()[..1]).unwrap();
+            tee.flush().unwrap();
+            tee.write_all(&"═".as_bytes()[1..]).unwrap();
+            tee.flush().unwrap();
+        }
+        assert_eq!(String::from_utf8(screen).unwrap(), "\x1b[2J╔═");
+
+        let lines: Vec<serde_json::Value> =
+            String::from_utf8(cast).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!([&lines[0]["version"], &lines[0]["width"], &lines[0]["height"]], [2, 80, 24]);
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "\x1b[2J╔");
+        assert_eq!(lines[2][2], "═");
+        assert!(lines[2][0].as_f64().unwrap() >= lines[1][0].as_f64().unwrap());
+    }
+}
@@ -0,0 +1,4616 @@
+//! Core game state and rules: pieces, the board, movement, line clears and saving.
+
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::render::{RenderStyle, Theme};
+
+// --- OPTIONS & TUNING ---
+
+/// Gameplay options fixed for the lifetime of a game.
+#[derive(Debug, Clone)]
+pub struct GameOptions {
+    /// Whether the frontend should draw the ghost piece.
+    pub show_ghost: bool,
+    /// The frontend warns when locked blocks reach this many rows from the top.
+    /// Zero turns the warning off.
+    pub danger_rows: usize,
+    /// Whether the danger warning flashes rather than staying lit.
+    pub danger_flash: bool,
+    /// How long a grounded piece may rest before it locks.
+    pub lock_delay: Duration,
+    /// Delayed auto shift: how long a sideways key must be held before it starts repeating.
+    pub das: Duration,
+    /// Auto repeat rate: time between repeated sideways moves once DAS has charged.
+    /// Zero moves the piece all the way to the wall at once.
+    pub arr: Duration,
+    /// DAS carry: a sideways key still held when a new piece appears keeps its charge,
+    /// so a charged one moves the new piece straight away. Without it the new piece
+    /// waits out the full DAS again before repeating.
+    pub das_carry: bool,
+    /// Whether the game drives held sideways movement itself (DAS/ARR). This needs the
+    /// frontend to report key releases through [`Game::release_shift`]; frontends that
+    /// can't should turn it off and forward their own key repeats to [`Game::press_shift`].
+    pub auto_shift: bool,
+    /// How many times faster gravity runs while soft drop is held. Like auto shift this
+    /// needs key releases, reported through [`Game::release_soft_drop`].
+    pub soft_drop_factor: u32,
+    /// Rotate repeat: a held rotate key turns the piece again every this long, but only
+    /// while the piece is in the air, so holding it can't stall the lock delay. Needs
+    /// key releases, reported through [`Game::release_rotate`]. `None` turns it off, so
+    /// every press rotates exactly once.
+    pub rotate_repeat: Option<Duration>,
+    /// Initial rotation and hold (IRS/IHS): a rotation or hold pressed while it can't be
+    /// used (rows are clearing, or hold was already used on this piece) is applied to the
+    /// next piece the moment it spawns, if the press came at most this long before.
+    /// Zero turns it off.
+    pub initial_action_window: Duration,
+    /// How long full rows flash before they are removed. Zero clears them instantly.
+    pub line_clear_delay: Duration,
+    /// Seed for the piece sequence. `None` picks a random one; games started with the
+    /// same seed get the same pieces.
+    pub seed: Option<u64>,
+    /// How many upcoming pieces are previewed (at least one).
+    pub preview_count: usize,
+    /// Whether the game starts with the next queue hidden; see [`Game::toggle_next_hidden`].
+    pub hide_next: bool,
+    /// Colors and glyphs the pieces are drawn with.
+    pub theme: Theme,
+    /// Whether blocks are drawn solid or as piece outlines.
+    pub render_style: RenderStyle,
+    /// Big mode: every cell is drawn twice as wide and twice as tall. The board keeps its size.
+    pub big: bool,
+    /// High-contrast mode: only white on black, heavy borders, no background pattern and
+    /// the score boxed in.
+    pub high_contrast: bool,
+    /// Draw with plain ASCII instead of box-drawing and block characters.
+    pub ascii: bool,
+    /// Draw the board and pieces flipped left to right, with the Left and Right keys
+    /// swapped to match. Only the presentation is mirrored: the game itself plays out
+    /// exactly as it would unmirrored, so seeds, saves and replays stay comparable.
+    pub mirror: bool,
+    /// Which way pieces fall on screen. Like `mirror`, this only turns the presentation.
+    pub gravity_dir: GravityDir,
+    /// Always draw the compact layout, without the side panel. Otherwise it is only used
+    /// on screens too small for the full one.
+    pub compact: bool,
+    /// Whether quitting mid-game asks "Quit? (Y/N)" first; see [`Game::ask_to_quit`].
+    pub confirm_quit: bool,
+    /// Whether to collect [`GameEvent`]s for [`Game::take_events`]. Off by default so
+    /// games that never drain them don't pile them up.
+    pub record_events: bool,
+    /// Garbage lines pushed up from the bottom per minute, in bursts of
+    /// [`GARBAGE_BURST`] queued like any other garbage. Zero turns garbage off.
+    pub garbage_rate: u32,
+    /// How long queued garbage waits before the next lock pushes it in, giving time to
+    /// clear lines first. See [`Game::queue_garbage`].
+    pub garbage_delay: Duration,
+    /// How the game is won, if at all.
+    pub mode: GameMode,
+    /// Level the game starts at (at least 1). Clearing lines only raises the level
+    /// once the line count calls for a higher one.
+    pub start_level: u32,
+    /// How fast gravity gets as the level rises.
+    pub gravity_curve: GravityCurve,
+    /// Points for line clears.
+    pub scoring: ScoringConfig,
+    /// Gravity at the start level instead of the curve's, at least [`MIN_GRAVITY`].
+    /// With `speedup` the curve is scaled to start there; see [`GameOptions::speedup`].
+    pub gravity: Option<Duration>,
+    /// Whether gravity follows the level. Levels always rise with lines; this only
+    /// decides whether the speed goes with them:
+    ///
+    /// | `speedup` | `gravity` | speed                                                   |
+    /// |-----------|-----------|---------------------------------------------------------|
+    /// | on        | none      | the curve at the current level                          |
+    /// | on        | set       | the curve, scaled so the start level runs at `gravity`  |
+    /// | off       | none      | the curve at the start level, for the whole game        |
+    /// | off       | set       | `gravity`, for the whole game                           |
+    pub speedup: bool,
+    /// How the blocks above cleared rows fall.
+    pub gravity_mode: GravityMode,
+    /// How the pieces to deal are picked.
+    pub randomizer: RandomizerKind,
+    /// Whether locked cells disappear `fade_delay` after they lock. Only drawing is
+    /// affected; see [`Game::cell_visible`].
+    pub invisible: bool,
+    pub fade_delay: Duration,
+    /// How many times an invisible game may [`Game::reveal_board`].
+    pub reveals: u32,
+    /// How many times hold may be used in the whole game. Zero means no limit.
+    pub max_holds: u32,
+    /// A board layout and piece order to start from instead of an empty board; every
+    /// restart begins from it again.
+    pub setup: Option<Arc<Setup>>,
+    /// Practice mode: the last [`UNDO_DEPTH`] placements can be taken back with
+    /// [`Game::undo`]. Frontends shouldn't put practice games on leaderboards.
+    pub practice: bool,
+    /// Directory holding the save slots; empty means the working directory.
+    pub save_dir: PathBuf,
+    /// The pieces the game deals, indexed by piece id; see [`standard_pieces`] and
+    /// [`load_pieces`].
+    pub pieces: Arc<[Piece]>,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            show_ghost: true,
+            danger_rows: 4,
+            danger_flash: true,
+            lock_delay: Duration::from_millis(500),
+            das: Duration::from_millis(170),
+            arr: Duration::from_millis(30),
+            das_carry: false,
+            auto_shift: true,
+            soft_drop_factor: 20,
+            rotate_repeat: None,
+            initial_action_window: Duration::from_millis(100),
+            line_clear_delay: Duration::from_millis(200),
+            seed: None,
+            preview_count: 5,
+            hide_next: false,
+            theme: Theme::default(),
+            render_style: RenderStyle::default(),
+            big: false,
+            high_contrast: false,
+            ascii: false,
+            mirror: false,
+            gravity_dir: GravityDir::default(),
+            compact: false,
+            confirm_quit: true,
+            record_events: false,
+            garbage_rate: 0,
+            garbage_delay: Duration::from_secs(1),
+            mode: GameMode::default(),
+            start_level: 1,
+            gravity_curve: GravityCurve::default(),
+            scoring: ScoringConfig::default(),
+            gravity: None,
+            speedup: true,
+            gravity_mode: GravityMode::default(),
+            randomizer: RandomizerKind::default(),
+            invisible: false,
+            fade_delay: Duration::from_secs(1),
+            reveals: 3,
+            max_holds: 0,
+            setup: None,
+            practice: false,
+            save_dir: PathBuf::new(),
+            pieces: standard_pieces(),
+        }
+    }
+}
+
+/// Rules for how a game ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Play with rising speed until `lines` lines are cleared, which wins the game.
+    /// With `lines` 0 it is endless and only ends when the stack tops out.
+    Marathon { lines: u32 },
+    /// Clear `lines` lines as fast as possible.
+    Sprint { lines: u32 },
+    /// Score as many points as possible before `time_limit` of play runs out.
+    Ultra { time_limit: Duration },
+    /// Stack at a steady speed for as long as you like: topping out empties the board
+    /// instead of ending the game, and the level never rises.
+    Zen,
+}
+
+/// Default line goal for marathon games.
+pub const MARATHON_LINES: u32 = 150;
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Marathon { lines: MARATHON_LINES }
+    }
+}
+
+/// Number of times moving or rotating a grounded piece may restart its lock delay.
+const MAX_LOCK_RESETS: u32 = 15;
+
+/// How long a status message stays up.
+const STATUS_TIME: Duration = Duration::from_secs(2);
+
+/// How many placements a practice game remembers for [`Game::undo`].
+pub const UNDO_DEPTH: usize = 5;
+
+/// How long [`Game::reveal_board`] shows the hidden stack for.
+pub const REVEAL_TIME: Duration = Duration::from_millis(1500);
+
+/// How long the cells of a freshly locked piece flash white.
+pub const LOCK_FLASH_TIME: Duration = Duration::from_millis(80);
+
+/// Lines that must be cleared to advance one level.
+const LINES_PER_LEVEL: u32 = 10;
+
+/// How the time between gravity steps shrinks as the level rises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GravityCurve {
+    /// One second at level 1, 75ms faster per level down to [`LINEAR_MIN_DELAY`].
+    Linear,
+    /// The frame counts of the NES game, whose level 0 is level 1 here.
+    ClassicNes,
+    /// The modern guideline formula, which gets steeply faster in the teens.
+    #[default]
+    Guideline,
+}
+
+impl GravityCurve {
+    pub const ALL: [GravityCurve; 3] = [GravityCurve::Linear, GravityCurve::ClassicNes, GravityCurve::Guideline];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityCurve::Linear => "linear",
+            GravityCurve::ClassicNes => "classic-nes",
+            GravityCurve::Guideline => "guideline",
+        }
+    }
+}
+
+impl FromStr for GravityCurve {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        GravityCurve::ALL
+            .into_iter()
+            .find(|curve| curve.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = GravityCurve::ALL.iter().map(|curve| curve.name()).collect();
+                format!("unknown gravity curve `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// What happens to the blocks above a line clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GravityMode {
+    /// The rows above move down by the number of rows cleared, leaving any overhangs
+    /// floating.
+    #[default]
+    Naive,
+    /// Each cluster of connected blocks falls as far as it can on its own, and any rows
+    /// that completes clear too, in a chain; see [`settle_clusters`].
+    Sticky,
+}
+
+impl GravityMode {
+    pub const ALL: [GravityMode; 2] = [GravityMode::Naive, GravityMode::Sticky];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityMode::Naive => "naive",
+            GravityMode::Sticky => "sticky",
+        }
+    }
+}
+
+impl FromStr for GravityMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        GravityMode::ALL
+            .into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = GravityMode::ALL.iter().map(|mode| mode.name()).collect();
+                format!("unknown gravity mode `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// Which way pieces fall on screen, as a novelty. The game always plays out as if they
+/// fell down; the board is only drawn turned so they fall this way. The rows it
+/// clears are then whole columns on screen for sideways gravity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GravityDir {
+    #[default]
+    Down,
+    Up,
+    Left,
+    Right,
+}
+
+impl GravityDir {
+    pub const ALL: [GravityDir; 4] = [GravityDir::Down, GravityDir::Up, GravityDir::Left, GravityDir::Right];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            GravityDir::Down => "down",
+            GravityDir::Up => "up",
+            GravityDir::Left => "left",
+            GravityDir::Right => "right",
+        }
+    }
+
+    /// Whether pieces fall sideways, turning the board's rows into columns on screen.
+    pub fn is_sideways(self) -> bool {
+        matches!(self, GravityDir::Left | GravityDir::Right)
+    }
+
+    /// Size in cells, as (columns, rows), of a `width` by `height` board once turned.
+    pub fn screen_size(self, width: usize, height: usize) -> (usize, usize) {
+        if self.is_sideways() { (height, width) } else { (width, height) }
+    }
+
+    /// The screen cell, counted from the top left of the turned board, that shows board
+    /// cell (`x`, `y`) of a `width` by `height` board. The board is rotated rather than
+    /// flipped, so pieces keep their handedness.
+    pub fn to_screen(self, (x, y): (isize, isize), width: usize, height: usize) -> (isize, isize) {
+        let (right, bottom) = (width as isize - 1, height as isize - 1);
+        match self {
+            GravityDir::Down => (x, y),
+            GravityDir::Up => (right - x, bottom - y),
+            GravityDir::Left => (bottom - y, x),
+            GravityDir::Right => (y, right - x),
+        }
+    }
+
+    /// The board cell shown at screen cell (`x`, `y`); the reverse of [`GravityDir::to_screen`].
+    pub fn from_screen(self, (x, y): (isize, isize), width: usize, height: usize) -> (isize, isize) {
+        let (right, bottom) = (width as isize - 1, height as isize - 1);
+        match self {
+            GravityDir::Down => (x, y),
+            GravityDir::Up => (right - x, bottom - y),
+            GravityDir::Left => (y, bottom - x),
+            GravityDir::Right => (right - y, x),
+        }
+    }
+
+    /// The sideways step on the board that moves a piece `dir` cells along the screen:
+    /// right for a positive `dir`, or down when the piece falls sideways.
+    pub fn shift(self, dir: isize) -> isize {
+        match self {
+            GravityDir::Down | GravityDir::Left => dir,
+            GravityDir::Up | GravityDir::Right => -dir,
+        }
+    }
+}
+
+impl FromStr for GravityDir {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        GravityDir::ALL
+            .into_iter()
+            .find(|dir| dir.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = GravityDir::ALL.iter().map(|dir| dir.name()).collect();
+                format!("unknown gravity direction `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// Slowest the linear curve gets faster to.
+const LINEAR_MIN_DELAY: Duration = Duration::from_millis(150);
+const LINEAR_STEP: Duration = Duration::from_millis(75);
+/// Frames per row on the NES from its level 0 up; every level past the table drops a row per frame.
+const NES_FRAMES: [u32; 29] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+const NES_FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Fastest the guideline curve is allowed to reach.
+const MIN_GRAVITY_DELAY: Duration = Duration::from_millis(50);
+
+/// Fastest gravity a game can set for itself with [`GameOptions::gravity`]; anything
+/// quicker is unplayable.
+pub const MIN_GRAVITY: Duration = Duration::from_millis(20);
+
+/// Time between gravity steps at `level` under `options`' curve, fixed gravity and
+/// speed-up setting.
+fn gravity_for_options(options: &GameOptions, level: u32) -> Duration {
+    let start_level = options.start_level.max(1);
+    let level = if options.speedup { level } else { start_level };
+    let curve = gravity_for_level(level, options.gravity_curve);
+    match options.gravity {
+        Some(start) => {
+            let start = start.max(MIN_GRAVITY);
+            curve.mul_f64(start.as_secs_f64() / gravity_for_level(start_level, options.gravity_curve).as_secs_f64())
+                .max(MIN_GRAVITY)
+        }
+        None => curve,
+    }
+}
+
+/// Time between gravity steps at the given level (starting from 1) on `curve`.
+/// Every curve starts at or near one second per row.
+fn gravity_for_level(level: u32, curve: GravityCurve) -> Duration {
+    let steps = level.saturating_sub(1);
+    match curve {
+        GravityCurve::Linear => {
+            Duration::from_secs(1).saturating_sub(LINEAR_STEP * steps).max(LINEAR_MIN_DELAY)
+        }
+        GravityCurve::ClassicNes => NES_FRAME * NES_FRAMES.get(steps as usize).copied().unwrap_or(1),
+        GravityCurve::Guideline => {
+            let seconds = (0.8 - steps as f64 * 0.007).max(0.0).powi(steps.min(1000) as i32);
+            Duration::from_secs_f64(seconds).max(MIN_GRAVITY_DELAY)
+        }
+    }
+}
+
+/// Garbage lines added at a time; they all share one hole column so they can be dug out together.
+pub const GARBAGE_BURST: u32 = 2;
+const GARBAGE_COLOR: Color = Color(128, 128, 128);
+/// Mixed into the seed for the garbage hole generator, keeping it apart from the piece sequence.
+const GARBAGE_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Number of save slots; slots are numbered from 1.
+pub const SAVE_SLOTS: u8 = 9;
+
+/// Name of the file holding the game saved in `slot`, or an error if the slot doesn't exist.
+pub fn save_file(slot: u8) -> io::Result<String> {
+    if !(1..=SAVE_SLOTS).contains(&slot) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("no save slot {}", slot)));
+    }
+    Ok(format!("tetris_save_{}.json", slot))
+}
+
+/// Name of the file a game quit partway through waits in to be resumed.
+pub const AUTOSAVE_FILE: &str = "tetris_autosave.json";
+
+// --- COLOR & PIECE DEFINITIONS ---
+
+/// An RGB color.
+// Added Serialize and Deserialize for saving/loading the game state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+/// A piece shape: each rotation is a row width plus a row-major bitmap (1 = filled).
+/// Piece files use the same layout, e.g. `{"name": "O", "rotations": [[2, [1,1,1,1]]],
+/// "color": [252, 244, 3]}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Piece {
+    /// Short name for the stats panel.
+    #[serde(default)]
+    pub name: String,
+    pub rotations: Vec<(usize, Vec<u8>)>,
+    pub color: Color,
+    /// Where the spawn bitmap's left edge sits in the four columns at the middle of the
+    /// board, rounded left on odd widths. Spawning every piece relative to the same four
+    /// columns keeps them lined up the same way whatever the board width.
+    #[serde(default)]
+    pub spawn_column: isize,
+}
+
+/// The rotation every piece spawns in, and so the one previews show.
+pub const SPAWN_ROTATION: usize = 0;
+
+impl Piece {
+    /// Width and height of the piece in rotation `rotation`.
+    pub fn size(&self, rotation: usize) -> (usize, usize) {
+        let (width, bitmap) = &self.rotations[rotation];
+        (*width, bitmap.len() / width)
+    }
+}
+
+/// Rotations of the 7 classic Tetris pieces (tetrominos), indexed by piece id, with
+/// their names, colors and spawn columns. Rotations follow the SRS states 0, R, 2, L;
+/// each bitmap is trimmed to the piece, see [`SRS_BOX_OFFSETS`] for where it sits in
+/// the rotation box.
+#[allow(clippy::type_complexity)]
+const STANDARD_PIECES: [(&str, &[(usize, &[u8])], Color, isize); 7] = [
+    ("I", &[(4, &[1,1,1,1]), (1, &[1,1,1,1]), (4, &[1,1,1,1]), (1, &[1,1,1,1])], Color(3, 252, 248), 0),
+    ("O", &[(2, &[1,1,1,1])], Color(252, 244, 3), 1),
+    ("T", &[(3, &[0,1,0,1,1,1]), (2, &[1,0,1,1,1,0]), (3, &[1,1,1,0,1,0]), (2, &[0,1,1,1,0,1])], Color(161, 3, 252), 0),
+    ("L", &[(3, &[0,0,1,1,1,1]), (2, &[1,0,1,0,1,1]), (3, &[1,1,1,1,0,0]), (2, &[1,1,0,1,0,1])], Color(252, 161, 3), 0),
+    ("J", &[(3, &[1,0,0,1,1,1]), (2, &[1,1,1,0,1,0]), (3, &[1,1,1,0,0,1]), (2, &[0,1,0,1,1,1])], Color(3, 48, 252), 0),
+    ("S", &[(3, &[0,1,1,1,1,0]), (2, &[1,0,1,1,0,1]), (3, &[0,1,1,1,1,0]), (2, &[1,0,1,1,0,1])], Color(3, 252, 28), 0),
+    ("Z", &[(3, &[1,1,0,0,1,1]), (2, &[0,1,1,1,1,0]), (3, &[1,1,0,0,1,1]), (2, &[0,1,1,1,1,0])], Color(252, 3, 3), 0),
+];
+
+static STANDARD_PIECE_SET: LazyLock<Arc<[Piece]>> = LazyLock::new(|| {
+    STANDARD_PIECES
+        .iter()
+        .map(|&(name, rotations, color, spawn_column)| Piece {
+            name: name.to_string(),
+            rotations: rotations.iter().map(|&(width, bitmap)| (width, bitmap.to_vec())).collect(),
+            color,
+            spawn_column,
+        })
+        .collect()
+});
+
+/// The seven tetrominos, the only set that uses SRS wall kicks and scores T-spins.
+pub fn standard_pieces() -> Arc<[Piece]> {
+    STANDARD_PIECE_SET.clone()
+}
+
+/// Largest width or height a piece may have in any rotation.
+pub const MAX_PIECE_SIZE: usize = 5;
+
+/// Smallest board that still fits every piece in every rotation.
+pub const MIN_BOARD_SIZE: usize = 4;
+/// Largest board dimensions; bigger values are clamped so the layout stays within `u16` terminal coordinates.
+pub const MAX_COLUMNS: usize = 100;
+pub const MAX_LINES: usize = 100;
+
+/// Hidden rows above the visible board, with negative `y`. Pieces spawn in them and
+/// may turn and stack up into them; nothing fits above them. Tall enough for any piece.
+pub const BUFFER_ROWS: usize = MAX_PIECE_SIZE;
+
+/// Checks that `pieces` is a usable piece set: at least one piece, and every rotation
+/// a rectangle of 0s and 1s no bigger than [`MAX_PIECE_SIZE`] with at least one cell filled.
+pub fn validate_pieces(pieces: &[Piece]) -> Result<(), String> {
+    if pieces.is_empty() {
+        return Err("no pieces defined".to_string());
+    }
+    for (id, piece) in pieces.iter().enumerate() {
+        if piece.rotations.is_empty() {
+            return Err(format!("piece {} has no rotations", id));
+        }
+        for (rotation, (width, bitmap)) in piece.rotations.iter().enumerate() {
+            let problem = if *width == 0 || bitmap.len() % width != 0 {
+                Some(format!("{} cells don't make rows {} wide", bitmap.len(), width))
+            } else if *width > MAX_PIECE_SIZE || bitmap.len() / width > MAX_PIECE_SIZE {
+                Some(format!("it is bigger than {}x{}", MAX_PIECE_SIZE, MAX_PIECE_SIZE))
+            } else if bitmap.iter().any(|&cell| cell > 1) {
+                Some("cells must be 0 or 1".to_string())
+            } else if !bitmap.contains(&1) {
+                Some("no cells are filled".to_string())
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(format!("piece {} rotation {}: {}", id, rotation, problem));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a piece set from a JSON file holding a list of [`Piece`]s, and validates it.
+pub fn load_pieces(path: &Path) -> Result<Arc<[Piece]>, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let pieces: Vec<Piece> = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+    validate_pieces(&pieces)?;
+    Ok(pieces.into())
+}
+
+// --- PRACTICE SETUPS ---
+
+/// A board layout and piece order to start a game from, for drilling a situation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setup {
+    /// Rows at the bottom of the board, top to bottom, one cell per column.
+    pub rows: Vec<Vec<Option<Color>>>,
+    /// Piece ids dealt first, in order: the active piece, then the start of the next
+    /// queue. The seeded sequence carries on after them.
+    pub pieces: Vec<usize>,
+}
+
+/// A setup file as written by hand.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SetupFile {
+    board: String,
+    #[serde(default)]
+    pieces: Vec<String>,
+}
+
+impl Setup {
+    /// Parses a setup from TOML for a `width` by `height` board played with `pieces`:
+    ///
+    /// ```toml
+    /// board = """
+    /// ......TT..
+    /// ##.##TTT##
+    /// """
+    /// pieces = ["T", "I", "O"]
+    /// ```
+    ///
+    /// Each line of `board` is a row, bottom-aligned on the board: `.` is empty, `#` is
+    /// garbage and a piece's one-letter name (in any case) is a block of its color. Rows
+    /// must be exactly as wide as the board, no full rows are allowed, and there can't
+    /// be more rows than the board has. `pieces` names the pieces to deal first.
+    pub fn parse(text: &str, pieces: &[Piece], width: usize, height: usize) -> Result<Setup, String> {
+        let file: SetupFile = toml::from_str(text).map_err(|e| e.to_string())?;
+        let lines: Vec<&str> = file.board.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        if lines.len() > height {
+            return Err(format!("board has {} rows, but the board is only {} high", lines.len(), height));
+        }
+        let cell = |c: char| match c {
+            '.' => Ok(None),
+            '#' => Ok(Some(GARBAGE_COLOR)),
+            c => pieces
+                .iter()
+                .find(|piece| piece.name.len() == 1 && piece.name.eq_ignore_ascii_case(&c.to_string()))
+                .map(|piece| Some(piece.color))
+                .ok_or_else(|| format!("unknown cell `{}`", c)),
+        };
+        let mut rows = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            let row: Vec<_> =
+                line.chars().map(cell).collect::<Result<_, _>>().map_err(|e| format!("row {}: {}", y + 1, e))?;
+            if row.len() != width {
+                return Err(format!("row {} has {} cells, but the board is {} wide", y + 1, row.len(), width));
+            }
+            if row.iter().all(Option::is_some) {
+                return Err(format!("row {} is full", y + 1));
+            }
+            rows.push(row);
+        }
+        let pieces = file
+            .pieces
+            .iter()
+            .map(|name| {
+                let id = pieces.iter().position(|piece| piece.name.eq_ignore_ascii_case(name));
+                id.ok_or_else(|| format!("unknown piece `{}`", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Setup { rows, pieces })
+    }
+
+    /// The game's visible board, from its highest filled row down, with the active
+    /// piece and the next queue as the pieces to deal.
+    pub fn from_game(game: &Game) -> Setup {
+        let width = game.width();
+        let top = game.board().iter().position(Option::is_some).map_or(game.height(), |index| index / width);
+        let rows = game.board()[top * width..].chunks(width).map(<[_]>::to_vec).collect();
+        let pieces = [game.active_piece().id].into_iter().chain(game.next_queue().iter().copied()).collect();
+        Setup { rows, pieces }
+    }
+
+    /// Writes the setup in the format [`Setup::parse`] reads. Cells of a color no
+    /// one-letter piece has are written as garbage.
+    pub fn to_toml(&self, pieces: &[Piece]) -> String {
+        let cell = |cell: &Option<Color>| match cell {
+            None => '.',
+            Some(color) => pieces
+                .iter()
+                .find(|piece| piece.name.len() == 1 && piece.color == *color)
+                .and_then(|piece| piece.name.chars().next())
+                .map_or('#', |c| c.to_ascii_uppercase()),
+        };
+        let board: String =
+            self.rows.iter().map(|row| row.iter().map(cell).chain(['\n']).collect::<String>()).collect();
+        let names: Vec<String> =
+            self.pieces.iter().map(|&id| toml::Value::String(pieces[id].name.clone()).to_string()).collect();
+        format!("board = \"\"\"\n{}\"\"\"\npieces = [{}]\n", board, names.join(", "))
+    }
+}
+
+/// Reads a setup file; see [`Setup::parse`] for the format.
+pub fn load_setup(path: &Path, pieces: &[Piece], width: usize, height: usize) -> Result<Setup, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    Setup::parse(&text, pieces, width, height)
+}
+
+/// Piece id of the I-block, which has its own rotation box and kick table.
+const I_PIECE: usize = 0;
+
+/// Where each rotation's trimmed bitmap sits inside the SRS rotation box (4x4 for the
+/// I-block, 3x3 for J, L, S, T and Z), indexed by rotation. Rotating keeps the box still.
+const SRS_BOX_OFFSETS: [(isize, isize); 4] = [(0, 0), (1, 0), (0, 1), (0, 0)];
+const SRS_I_BOX_OFFSETS: [(isize, isize); 4] = [(0, 1), (2, 0), (0, 2), (1, 0)];
+
+/// SRS wall kicks for clockwise turns out of each rotation (0->R, R->2, 2->L, L->0),
+/// tried in order. Offsets are (right, up) as in the SRS tables; a counter-clockwise
+/// turn uses the negated kicks of the clockwise turn it undoes.
+const SRS_KICKS: [[(isize, isize); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+const SRS_I_KICKS: [[(isize, isize); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+
+/// Kicks for half turns, which SRS leaves out: stay put, then prefer moving up, then
+/// sideways. Same (right, up) convention as [`SRS_KICKS`].
+const HALF_TURN_KICKS: [(isize, isize); 6] = [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)];
+
+/// Which way to turn the active piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationDir {
+    Clockwise,
+    CounterClockwise,
+    /// Two steps at once.
+    Half,
+}
+
+/// Candidate position changes, in board coordinates (y down), for turning standard
+/// piece `id` from rotation `from` to rotation `to`. The first candidate is the plain
+/// rotation about the SRS box; the rest are its wall kicks.
+fn srs_rotation_candidates(id: usize, from: usize, to: usize) -> Vec<(isize, isize)> {
+    if STANDARD_PIECES[id].1.len() < 4 {
+        // The O-block looks the same in every rotation and never kicks.
+        return vec![(0, 0)];
+    }
+    let (offsets, kicks) = if id == I_PIECE { (&SRS_I_BOX_OFFSETS, &SRS_I_KICKS) } else { (&SRS_BOX_OFFSETS, &SRS_KICKS) };
+    let (shift_x, shift_y) = (offsets[to].0 - offsets[from].0, offsets[to].1 - offsets[from].1);
+    let (table, sign): (&[(isize, isize)], isize) = match (to + 4 - from) % 4 {
+        1 => (&kicks[from], 1),
+        3 => (&kicks[to], -1),
+        _ => (&HALF_TURN_KICKS, 1),
+    };
+    table.iter().map(|&(kick_x, kick_y)| (shift_x + sign * kick_x, shift_y - sign * kick_y)).collect()
+}
+
+/// Piece id of the T-block, the only piece that can score T-spins.
+const T_PIECE: usize = 2;
+/// Offset from a T-piece's position to its center cell, per rotation.
+const T_CENTER: [(isize, isize); 4] = [(1, 1), (0, 1), (1, 0), (1, 1)];
+
+/// Something that happened in the game, for frontends and tools that follow along.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// A new active piece appeared, from the next queue or the hold slot.
+    Spawned { piece: usize },
+    /// The active piece moved to a new position.
+    Moved { x: isize, y: isize },
+    /// The active piece turned to a new rotation.
+    Rotated { rotation: usize, x: isize, y: isize },
+    /// The active piece was written into the board, by a hard drop or otherwise.
+    Locked { piece: usize, hard_drop: bool },
+    /// Full rows were removed.
+    LinesCleared { count: u32, t_spin: bool },
+    /// Garbage rows were pushed up from the bottom.
+    GarbageAdded { count: u32, hole: usize },
+    /// A zen game topped out and the board was emptied.
+    BoardCleared,
+    GameOver,
+}
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOverReason {
+    /// A new piece appeared overlapping the stack.
+    BlockOut,
+    /// A piece locked entirely in the [`BUFFER_ROWS`] above the board.
+    LockOut,
+    /// Garbage pushed the stack over the top.
+    TopOut,
+    /// The mode's goal was reached, or its time ran out.
+    GoalReached,
+}
+
+impl GameOverReason {
+    /// Short name for the game-over screen.
+    pub fn label(self) -> &'static str {
+        match self {
+            GameOverReason::BlockOut => "BLOCK OUT",
+            GameOverReason::LockOut => "LOCK OUT",
+            GameOverReason::TopOut => "TOP OUT",
+            GameOverReason::GoalReached => "GOAL REACHED",
+        }
+    }
+}
+
+/// A tap of one of the piece controls, for [`Game::queue_input`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerInput {
+    MoveLeft,
+    MoveRight,
+    Rotate(RotationDir),
+    SoftDrop,
+    HardDrop,
+    Hold,
+}
+
+/// Most inputs [`Game::queue_input`] holds on to; presses beyond it are dropped.
+pub const INPUT_QUEUE_LEN: usize = 16;
+
+/// A press saved for the next piece by initial rotation/hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitialAction {
+    Rotate(RotationDir),
+    Hold,
+}
+
+/// A sideways key that is being held down for auto shift.
+#[derive(Debug, Clone, Copy)]
+struct HeldShift {
+    dir: isize,
+    next_move_at: Duration,
+}
+
+/// A rotate key that is being held down for rotate repeat.
+#[derive(Debug, Clone, Copy)]
+struct HeldRotation {
+    direction: RotationDir,
+    next_turn_at: Duration,
+}
+
+/// Entries of the pause menu, top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseSelection {
+    Resume,
+    Restart,
+    Save,
+    QuitToMenu,
+}
+
+impl PauseSelection {
+    pub const ALL: [PauseSelection; 4] =
+        [PauseSelection::Resume, PauseSelection::Restart, PauseSelection::Save, PauseSelection::QuitToMenu];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseSelection::Resume => "Resume",
+            PauseSelection::Restart => "Restart",
+            PauseSelection::Save => "Save",
+            PauseSelection::QuitToMenu => "Quit to Menu",
+        }
+    }
+}
+
+// --- ACTIVE PIECE ---
+
+/// The falling piece: which shape, which rotation, and the board position of its top-left corner.
+// Added Serialize and Deserialize for saving/loading the game state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivePiece {
+    pub id: usize,
+    pub rotation: usize,
+    pub x: isize,
+    pub y: isize,
+}
+
+impl ActivePiece {
+    /// Creates piece `id` of `pieces` in its spawn rotation in the buffer rows just above
+    /// a board `board_width` wide: the I-block fills the middle four columns, the O-block
+    /// sits in the middle two of them and the other pieces in the left three. Custom
+    /// pieces too wide for their spawn column are kept on the board.
+    pub fn new(id: usize, pieces: &[Piece], board_width: usize) -> Self {
+        let spawn_left = (board_width as isize - 4).div_euclid(2);
+        let (width, height) = pieces[id].size(SPAWN_ROTATION);
+        let x = (spawn_left + pieces[id].spawn_column).min(board_width as isize - width as isize).max(0);
+        ActivePiece { id, rotation: SPAWN_ROTATION, x, y: -(height as isize) }
+    }
+
+    pub fn definition<'a>(&self, pieces: &'a [Piece]) -> &'a Piece { &pieces[self.id] }
+
+    /// Board coordinates of every filled cell, with the shapes from `pieces`. Cells
+    /// above the board have negative `y`.
+    pub fn blocks<'a>(&'a self, pieces: &'a [Piece]) -> impl Iterator<Item = (isize, isize)> + 'a {
+        let (width, bitmap) = &self.definition(pieces).rotations[self.rotation];
+        let width = *width;
+        bitmap
+            .iter()
+            .enumerate()
+            .filter(|&(_, &cell)| cell == 1)
+            .map(move |(i, _)| (self.x + (i % width) as isize, self.y + (i / width) as isize))
+    }
+}
+
+/// Running totals for the stats panel and the end screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Pieces dealt from the next queue, by piece id.
+    pub pieces: Vec<u32>,
+    /// Line clears by size: singles, doubles, triples and tetrises.
+    pub clears: [u32; 4],
+    /// T-spins scored, with or without lines.
+    pub t_spins: u32,
+    /// Highest combo count reached.
+    pub max_combo: u32,
+    /// Level the game started at; 0 in saves from before start levels existed.
+    pub start_level: u32,
+    /// Whether the next queue stayed hidden for the whole game.
+    pub blind: bool,
+    /// Where the score came from; all zero in saves from before it was tracked.
+    pub points: ScoreBreakdown,
+    /// Pieces placed with more moves and turns than their spot needed.
+    pub finesse_faults: u32,
+    /// Line clears that left the whole board empty.
+    pub perfect_clears: u32,
+}
+
+impl Stats {
+    pub fn total_pieces(&self) -> u32 { self.pieces.iter().sum() }
+}
+
+/// A game's points by how they were earned. The parts add up to [`Game::score`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreBreakdown {
+    /// Line clears other than T-spins, including their back-to-back bonus.
+    pub clears: u64,
+    /// T-spins with or without lines, including their back-to-back bonus.
+    pub t_spins: u64,
+    /// Bonuses for clearing lines with consecutive pieces.
+    pub combos: u64,
+    /// Rows cleared by blocks falling in a sticky gravity chain.
+    pub cascades: u64,
+    /// Bonuses for clears that empty the board.
+    pub perfect_clears: u64,
+    pub soft_drop: u64,
+    pub hard_drop: u64,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> u64 {
+        self.clears + self.t_spins + self.combos + self.cascades + self.perfect_clears + self.soft_drop + self.hard_drop
+    }
+}
+
+// --- RANDOMIZERS ---
+
+/// Picks the pieces a game deals, drawing on the game's seeded generator.
+pub trait Randomizer {
+    /// The id of the next piece to deal.
+    fn next(&mut self, rng: &mut StdRng) -> usize;
+}
+
+/// Which [`Randomizer`] a game deals from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RandomizerKind {
+    /// [`PureRandomizer`].
+    Pure,
+    /// [`BagRandomizer`].
+    #[default]
+    Bag7,
+    /// [`HistoryRandomizer`].
+    History,
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 3] = [RandomizerKind::Pure, RandomizerKind::Bag7, RandomizerKind::History];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::Pure => "pure",
+            RandomizerKind::Bag7 => "bag7",
+            RandomizerKind::History => "history",
+        }
+    }
+}
+
+impl FromStr for RandomizerKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        RandomizerKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = RandomizerKind::ALL.iter().map(|kind| kind.name()).collect();
+                format!("unknown randomizer `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// Every piece equally likely every time, whatever came before, so long droughts and
+/// runs of one piece can happen.
+#[derive(Debug, Clone)]
+pub struct PureRandomizer {
+    piece_count: usize,
+    /// Numbers drawn from the generator so far, to replay it on load.
+    rolls: u64,
+}
+
+impl PureRandomizer {
+    pub fn new(piece_count: usize) -> Self {
+        PureRandomizer { piece_count, rolls: 0 }
+    }
+}
+
+impl Randomizer for PureRandomizer {
+    fn next(&mut self, rng: &mut StdRng) -> usize {
+        self.rolls += 1;
+        rng.gen_range(0..self.piece_count)
+    }
+}
+
+/// Deals every piece once in a shuffled order before any comes again: the 7-bag with
+/// the standard pieces.
+#[derive(Debug, Clone)]
+pub struct BagRandomizer {
+    piece_count: usize,
+    bag: Vec<usize>,
+    /// How many times the bag has been shuffled, to replay the generator on load.
+    refills: u64,
+}
+
+impl BagRandomizer {
+    pub fn new(piece_count: usize) -> Self {
+        BagRandomizer { piece_count, bag: Vec::new(), refills: 0 }
+    }
+}
+
+impl Randomizer for BagRandomizer {
+    fn next(&mut self, rng: &mut StdRng) -> usize {
+        draw_from_bag(&mut self.bag, self.piece_count, rng, &mut self.refills)
+    }
+}
+
+/// Pieces the history randomizer remembers.
+const HISTORY_LENGTH: usize = 4;
+/// Rolls the history randomizer makes for a piece it doesn't remember before settling
+/// for the last one.
+const HISTORY_ROLLS: usize = 6;
+
+/// TGM-style: rolls again when the piece is one of the last [`HISTORY_LENGTH`] dealt,
+/// up to [`HISTORY_ROLLS`] rolls in all, so repeats are rare but possible. The history
+/// starts out as S, Z, S, Z and the first piece is never S, Z or O, so a game doesn't
+/// open with an overhang. Pieces are known by name, so other piece sets with those
+/// letters get the same treatment.
+#[derive(Debug, Clone)]
+pub struct HistoryRandomizer {
+    piece_count: usize,
+    /// The last pieces dealt, oldest first.
+    history: VecDeque<usize>,
+    /// Pieces the first one may not be.
+    not_first: Vec<usize>,
+    dealt: bool,
+    /// Numbers drawn from the generator so far, to replay it on load.
+    rolls: u64,
+}
+
+impl HistoryRandomizer {
+    pub fn new(pieces: &[Piece]) -> Self {
+        let id = |name: &str| pieces.iter().position(|piece| piece.name == name);
+        let history = [id("S"), id("Z")].into_iter().flatten().cycle().take(HISTORY_LENGTH).collect();
+        let not_first = ["S", "Z", "O"].into_iter().filter_map(id).collect();
+        HistoryRandomizer { piece_count: pieces.len(), history, not_first, dealt: false, rolls: 0 }
+    }
+}
+
+impl Randomizer for HistoryRandomizer {
+    fn next(&mut self, rng: &mut StdRng) -> usize {
+        let mut piece = 0;
+        if !self.dealt && self.not_first.len() < self.piece_count {
+            // The first piece ignores the history and rolls until it is allowed.
+            loop {
+                piece = rng.gen_range(0..self.piece_count);
+                self.rolls += 1;
+                if !self.not_first.contains(&piece) {
+                    break;
+                }
+            }
+        } else {
+            for _ in 0..HISTORY_ROLLS {
+                piece = rng.gen_range(0..self.piece_count);
+                self.rolls += 1;
+                if !self.history.contains(&piece) {
+                    break;
+                }
+            }
+        }
+        self.dealt = true;
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(piece);
+        piece
+    }
+}
+
+/// The randomizer a game deals from, as an enum so that it can be copied to look ahead
+/// and written into saves.
+#[derive(Debug, Clone)]
+enum PieceRandomizer {
+    Pure(PureRandomizer),
+    Bag(BagRandomizer),
+    History(HistoryRandomizer),
+}
+
+impl PieceRandomizer {
+    fn new(kind: RandomizerKind, pieces: &[Piece]) -> Self {
+        match kind {
+            RandomizerKind::Pure => PieceRandomizer::Pure(PureRandomizer::new(pieces.len())),
+            RandomizerKind::Bag7 => PieceRandomizer::Bag(BagRandomizer::new(pieces.len())),
+            RandomizerKind::History => PieceRandomizer::History(HistoryRandomizer::new(pieces)),
+        }
+    }
+
+    /// The state as saved: the bag and its refill count, or how far another randomizer
+    /// had got.
+    fn to_saved(&self) -> (Vec<usize>, u64, Option<SavedRandomizer>) {
+        match self {
+            PieceRandomizer::Bag(bag) => (bag.bag.clone(), bag.refills, None),
+            PieceRandomizer::Pure(pure) => (Vec::new(), 0, Some(SavedRandomizer::Pure { rolls: pure.rolls })),
+            PieceRandomizer::History(history) => {
+                let saved = SavedRandomizer::History {
+                    rolls: history.rolls,
+                    history: history.history.iter().copied().collect(),
+                    dealt: history.dealt,
+                };
+                (Vec::new(), 0, Some(saved))
+            }
+        }
+    }
+
+    /// Rebuilds a randomizer from [`PieceRandomizer::to_saved`], moving `rng`, freshly
+    /// seeded, past the numbers it had drawn so the rest of the sequence matches.
+    fn from_saved(
+        pieces: &[Piece],
+        (bag, refills, saved): (Vec<usize>, u64, Option<SavedRandomizer>),
+        rng: &mut StdRng,
+    ) -> Self {
+        let piece_count = pieces.len();
+        let mut replay = |rolls| {
+            for _ in 0..rolls {
+                rng.gen_range(0..piece_count);
+            }
+        };
+        match saved {
+            None => {
+                let mut scratch: Vec<usize> = (0..piece_count).collect();
+                for _ in 0..refills {
+                    scratch.shuffle(rng);
+                }
+                PieceRandomizer::Bag(BagRandomizer { piece_count, bag, refills })
+            }
+            Some(SavedRandomizer::Pure { rolls }) => {
+                replay(rolls);
+                PieceRandomizer::Pure(PureRandomizer { piece_count, rolls })
+            }
+            Some(SavedRandomizer::History { rolls, history, dealt }) => {
+                replay(rolls);
+                let randomizer = HistoryRandomizer::new(pieces);
+                PieceRandomizer::History(HistoryRandomizer { history: history.into(), dealt, rolls, ..randomizer })
+            }
+        }
+    }
+}
+
+impl Randomizer for PieceRandomizer {
+    fn next(&mut self, rng: &mut StdRng) -> usize {
+        match self {
+            PieceRandomizer::Pure(randomizer) => randomizer.next(rng),
+            PieceRandomizer::Bag(randomizer) => randomizer.next(rng),
+            PieceRandomizer::History(randomizer) => randomizer.next(rng),
+        }
+    }
+}
+
+/// How far a pure or history randomizer had got, as saved. The bag's state has its
+/// own fields in [`SerializableGameState`], from before randomizers could be chosen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum SavedRandomizer {
+    Pure { rolls: u64 },
+    History { rolls: u64, history: Vec<usize>, dealt: bool },
+}
+
+// --- SAVEGAME STATE ---
+// A separate struct for serialization that holds all data needed to restore a game.
+// Also used as the state snapshot in the JSON event stream.
+#[derive(Serialize, Deserialize)]
+pub struct SerializableGameState {
+    /// Row-major, buffer rows first. Saves from before the buffer existed have only the
+    /// visible rows.
+    board: Vec<Option<Color>>,
+    width: usize,
+    height: usize,
+    active_piece: ActivePiece,
+    next_piece_id: usize,
+    is_game_over: bool,
+    /// Missing in saves from before reasons were recorded.
+    #[serde(default)]
+    game_over_reason: Option<GameOverReason>,
+    gravity_delay_ms: u64,
+    #[serde(default)]
+    level: u32,
+    #[serde(default)]
+    total_lines_cleared: u32,
+    score: u64,
+    #[serde(default)]
+    held_piece_id: Option<usize>,
+    #[serde(default)]
+    hold_used_this_turn: bool,
+    /// `None` when holds are unlimited, as in saves from before the limit existed.
+    #[serde(default)]
+    holds_remaining: Option<u32>,
+    #[serde(default)]
+    bag: Vec<usize>,
+    #[serde(default = "no_combo")]
+    combo: i32,
+    /// Missing in saves from before seeded games; those continue with a fresh seed.
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default)]
+    bag_refills: u64,
+    /// `None` for the bag, which uses `bag` and `bag_refills`.
+    #[serde(default)]
+    randomizer: Option<SavedRandomizer>,
+    /// Saves from before the preview queue only have `next_piece_id`.
+    #[serde(default)]
+    next_queue: VecDeque<usize>,
+    #[serde(default)]
+    back_to_back: bool,
+    #[serde(default)]
+    garbage_rate: u32,
+    #[serde(default)]
+    garbage_bursts: u64,
+    /// Queued garbage as (lines, milliseconds until it may go in).
+    #[serde(default)]
+    pending_garbage: Vec<(u32, u64)>,
+    #[serde(default)]
+    play_time_ms: u64,
+    #[serde(default)]
+    stats: Stats,
+    /// [`Game::state_hash`] of the game this was taken from. Missing in older saves.
+    #[serde(default)]
+    hash: Option<u64>,
+}
+
+fn no_combo() -> i32 { -1 }
+
+/// 64-bit FNV-1a, which is quick and the same on every platform.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self { Fnv(0xcbf2_9ce4_8422_2325) }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) { self.write(&n.to_le_bytes()) }
+}
+
+/// Removes every full row from `board`, a row-major grid `width` cells wide, moving the
+/// rows above them down and adding empty rows at the top. Returns the new board, the
+/// same size as the old one, and the number of rows removed.
+pub fn clear_full_rows(board: &[Option<Color>], width: usize) -> (Vec<Option<Color>>, u32) {
+    assert!(width > 0, "board width must be positive");
+    let kept: Vec<&[Option<Color>]> = board
+        .chunks_exact(width)
+        .filter(|row| !row.iter().all(Option::is_some))
+        .collect();
+    let cleared = board.len() / width - kept.len();
+    let mut new_board = vec![None; cleared * width];
+    for row in kept {
+        new_board.extend_from_slice(row);
+    }
+    (new_board, cleared as u32)
+}
+
+/// Lets every cluster of orthogonally connected blocks in `board`, a row-major grid
+/// `width` cells wide, fall until it rests on the floor or on another block, as sticky
+/// gravity does after a clear. Clusters that land on each other fall on together.
+/// Returns where each cell ends up, indexed by where it started (empty cells map to
+/// themselves), or `None` if nothing can fall.
+pub fn settle_clusters(board: &[Option<Color>], width: usize) -> Option<Vec<usize>> {
+    assert!(width > 0, "board width must be positive");
+    let height = board.len() / width;
+    let mut filled: Vec<bool> = board.iter().map(Option::is_some).collect();
+    // Which starting cell each filled cell came from.
+    let mut origin: Vec<usize> = (0..board.len()).collect();
+    let mut moved = false;
+    loop {
+        let mut clusters = connected_clusters(&filled, width);
+        // Lowest first, so the clusters above can land on them once they have fallen.
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.iter().max().copied()));
+        let mut fell = false;
+        for cluster in clusters {
+            for &i in &cluster {
+                filled[i] = false;
+            }
+            let blocked = |drop: usize| {
+                cluster.iter().any(|&i| {
+                    let y = i / width + drop;
+                    y >= height || filled[y * width + i % width]
+                })
+            };
+            let drop = (1..).find(|&drop| blocked(drop)).unwrap_or(1) - 1;
+            let origins: Vec<usize> = cluster.iter().map(|&i| origin[i]).collect();
+            for (&i, from) in cluster.iter().zip(origins) {
+                filled[i + drop * width] = true;
+                origin[i + drop * width] = from;
+            }
+            fell |= drop > 0;
+        }
+        if !fell {
+            break;
+        }
+        moved = true;
+    }
+    moved.then(|| {
+        let mut destination: Vec<usize> = (0..board.len()).collect();
+        for (i, _) in filled.iter().enumerate().filter(|(_, &filled)| filled) {
+            destination[origin[i]] = i;
+        }
+        destination
+    })
+}
+
+/// How high each column of `filled`, a row-major grid `width` cells wide, is stacked:
+/// the rows from the floor up to and including its highest filled cell.
+pub fn column_heights_in(filled: &[bool], width: usize) -> Vec<usize> {
+    assert!(width > 0, "board width must be positive");
+    let height = filled.len() / width;
+    (0..width)
+        .map(|x| (0..height).find(|&y| filled[y * width + x]).map_or(0, |top| height - top))
+        .collect()
+}
+
+/// The holes in `filled`, a row-major grid `width` cells wide: empty cells with a filled
+/// cell somewhere above them in the same column, as (x, y) from the top left.
+pub fn holes_in(filled: &[bool], width: usize) -> Vec<(usize, usize)> {
+    assert!(width > 0, "board width must be positive");
+    let height = filled.len() / width;
+    let mut holes = Vec::new();
+    for (x, column_height) in column_heights_in(filled, width).into_iter().enumerate() {
+        holes.extend((height - column_height..height).filter(|&y| !filled[y * width + x]).map(|y| (x, y)));
+    }
+    holes.sort_by_key(|&(x, y)| (y, x));
+    holes
+}
+
+/// The groups of orthogonally connected cells among the `filled` ones.
+fn connected_clusters(filled: &[bool], width: usize) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; filled.len()];
+    let mut clusters = Vec::new();
+    for start in 0..filled.len() {
+        if !filled[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut cluster = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            cluster.push(i);
+            let (x, y) = (i % width, i / width);
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < width).then_some(i + 1),
+                (y > 0).then(|| i - width),
+                (i + width < filled.len()).then_some(i + width),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if filled[next] && !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        clusters.push(cluster);
+    }
+    clusters
+}
+
+/// Points for line clears without a T-spin, as set by the `[scoring]` table of the
+/// config file:
+///
+/// ```toml
+/// [scoring]
+/// lines = [40, 100, 300, 1200]
+/// level_multiplier = true
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoringConfig {
+    /// Base points for clearing one, two, three and four or more rows at once.
+    pub lines: [u32; 4],
+    /// Whether the base points are multiplied by the level, as in NES scoring.
+    pub level_multiplier: bool,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig { lines: [100, 300, 500, 800], level_multiplier: false }
+    }
+}
+
+impl ScoringConfig {
+    /// Points for clearing `count` rows at once at `level`, before any bonus.
+    pub fn line_clear_points(&self, count: u32, level: u32) -> u32 {
+        let Some(index) = count.checked_sub(1) else {
+            return 0;
+        };
+        let points = self.lines[index.min(3) as usize];
+        if self.level_multiplier { points.saturating_mul(level) } else { points }
+    }
+}
+
+/// Bonus for a clear of `count` lines that leaves the board empty, on top of the
+/// clear's own points.
+fn perfect_clear_points(count: u32) -> u32 {
+    match count {
+        0 => 0,
+        1 => 800,
+        2 => 1200,
+        3 => 1800,
+        _ => 2000,
+    }
+}
+
+/// Upper bound on the bag refills and garbage bursts a save may record. Loading replays
+/// that many random draws, so a corrupt count mustn't be able to stall it.
+const MAX_SAVED_DRAWS: u64 = 1 << 24;
+
+impl SerializableGameState {
+    /// The board size the state was saved from, as (width, height).
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    /// Checks that the state describes a game with `pieces` that can be restored without
+    /// indexing out of bounds, explaining the first problem found.
+    fn validate(&self, pieces: &[Piece]) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("board is {}x{}", self.width, self.height));
+        }
+        // Checked, since a crafted size could overflow before the board length catches it.
+        let cells = self.height.checked_add(BUFFER_ROWS).and_then(|rows| rows.checked_mul(self.width));
+        let Some(cells) = cells else {
+            return Err(format!("board is {}x{}", self.width, self.height));
+        };
+        if self.board.len() != cells && Some(self.board.len()) != self.width.checked_mul(self.height) {
+            return Err(format!("board has {} cells, expected {}", self.board.len(), cells));
+        }
+        let history = match &self.randomizer {
+            Some(SavedRandomizer::History { history, .. }) => history.as_slice(),
+            _ => &[],
+        };
+        if history.len() > HISTORY_LENGTH {
+            return Err(format!("randomizer history has {} pieces, at most {}", history.len(), HISTORY_LENGTH));
+        }
+        let piece_ids = self.next_queue.iter().chain(&self.bag).chain(&self.held_piece_id)
+            .chain([&self.next_piece_id, &self.active_piece.id]).chain(history);
+        if let Some(id) = piece_ids.copied().find(|&id| id >= pieces.len()) {
+            return Err(format!("unknown piece {}", id));
+        }
+        if self.active_piece.rotation >= self.active_piece.definition(pieces).rotations.len() {
+            return Err(format!("piece {} has no rotation {}", self.active_piece.id, self.active_piece.rotation));
+        }
+        let rolls = match self.randomizer {
+            Some(SavedRandomizer::Pure { rolls } | SavedRandomizer::History { rolls, .. }) => rolls,
+            None => 0,
+        };
+        if self.bag_refills > MAX_SAVED_DRAWS || self.garbage_bursts > MAX_SAVED_DRAWS || rolls > MAX_SAVED_DRAWS {
+            return Err("too many random draws".to_string());
+        }
+        Ok(())
+    }
+}
+
+// --- GAME STATE & LOGIC ---
+
+/// A single game of Tetris. Frontends feed it player actions, call [`Game::update`]
+/// regularly to apply gravity, and read its state back through the accessors to draw it.
+pub struct Game {
+    board: Vec<Option<Color>>,
+    width: usize,
+    height: usize,
+    active_piece: ActivePiece,
+    /// Upcoming pieces, next one first; at least `options.preview_count` long (longer
+    /// only when a save made with a bigger preview is loaded).
+    next_queue: VecDeque<usize>,
+    rng: StdRng,
+    seed: u64,
+    /// Picks the pieces from `rng`, and keeps count so a loaded game can replay `rng`
+    /// to the same point.
+    randomizer: PieceRandomizer,
+    /// Picks garbage hole columns; seeded from `seed` but separate from `rng`.
+    garbage_rng: StdRng,
+    /// How many garbage holes have been drawn, to replay `garbage_rng` on load.
+    garbage_bursts: u64,
+    last_garbage_time: Duration,
+    /// Garbage waiting to go in, oldest first, as (lines, when it may go in).
+    pending_garbage: VecDeque<(u32, Duration)>,
+    is_game_over: bool,
+    game_over_reason: Option<GameOverReason>,
+    paused: bool,
+    /// When the current pause began.
+    paused_at: Duration,
+    /// Highlighted pause menu entry; only meaningful while paused.
+    pause_selection: PauseSelection,
+    /// Set while "Quit? (Y/N)" is up, to whether answering no should unpause the game.
+    quit_prompt: Option<bool>,
+    gravity_delay: Duration,
+    /// Game time: how far `update` has advanced the game. All timestamps below use it.
+    clock: Duration,
+    last_gravity_time: Duration,
+    level: u32,
+    total_lines_cleared: u32,
+    score: u64,
+    status_message: Option<(String, Duration)>,
+    held_piece_id: Option<usize>,
+    hold_used_this_turn: bool,
+    /// Holds left in a game with a hold limit.
+    holds_remaining: Option<u32>,
+    options: GameOptions,
+    lock_timer: Option<Duration>,
+    lock_resets: u32,
+    /// Consecutive line-clearing placements minus one; -1 when no combo is running.
+    combo: i32,
+    /// Whether the last line clear was a tetris or T-spin, so the next one earns the 1.5x bonus.
+    back_to_back: bool,
+    /// Whether the last successful movement of the active piece was a rotation (for T-spins).
+    last_action_was_rotation: bool,
+    held_shift: Option<HeldShift>,
+    held_rotation: Option<HeldRotation>,
+    /// The active piece as it appeared, and the moves and turns the player has asked of
+    /// it since, for judging finesse when it locks.
+    piece_start: ActivePiece,
+    piece_inputs: u32,
+    /// Full rows waiting out the line-clear animation, and when it started. The
+    /// next piece only spawns once they are removed.
+    clearing: Option<(Vec<usize>, Duration)>,
+    /// Whether the piece that completed the clearing rows was a T-spin.
+    clearing_t_spin: bool,
+    /// Events since the last `take_events`, when `options.record_events` is on.
+    events: Vec<GameEvent>,
+    /// Unpaused play time, counted from the player's first action.
+    play_time: Duration,
+    timer_running: bool,
+    /// Whether the game ended by reaching the mode's goal rather than topping out.
+    goal_reached: bool,
+    stats: Stats,
+    /// Whether the panel shows the stats block instead of the controls.
+    show_stats: bool,
+    /// Whether the board shows column heights and highlights holes.
+    show_heights: bool,
+    next_hidden: bool,
+    /// Whether soft drop is held down, speeding up gravity.
+    soft_drop_held: bool,
+    /// When each board cell was filled, for fading cells out in invisible mode.
+    placed_at: Vec<Duration>,
+    /// The board cells the last piece locked into, and when it locked.
+    lock_flash: Option<(Vec<usize>, Duration)>,
+    reveals_left: u32,
+    /// When the last reveal started.
+    revealed_at: Option<Duration>,
+    /// Whether the pieces are the standard set, which rotates by SRS and can T-spin.
+    srs: bool,
+    /// The last rotation or hold that couldn't be used yet, and when it was pressed.
+    initial_action: Option<(InitialAction, Duration)>,
+    /// Inputs waiting for the game to be ready for them, oldest first.
+    input_queue: VecDeque<PlayerInput>,
+    /// In practice games, the game as it was when each of the last few placed pieces
+    /// spawned, oldest first.
+    undo_history: VecDeque<SerializableGameState>,
+    /// The game as it was when the active piece spawned, moved onto `undo_history` once
+    /// the piece locks.
+    spawn_state: Option<SerializableGameState>,
+    /// Practice edit mode: gravity is off and hard drop locks the piece where it is.
+    editing: bool,
+}
+
+impl Game {
+    /// Starts a new game on an empty board of `width` columns by `height` rows.
+    pub fn new(width: usize, height: usize, options: GameOptions) -> Self {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut randomizer = PieceRandomizer::new(options.randomizer, &options.pieces);
+        let piece_count = options.pieces.len();
+        let first_piece_id = randomizer.next(&mut rng);
+        let next_queue = (0..options.preview_count.max(1)).map(|_| randomizer.next(&mut rng)).collect();
+        let start_level = options.start_level.max(1);
+        let mut stats =
+            Stats { start_level, pieces: vec![0; piece_count], blind: options.hide_next, ..Stats::default() };
+        stats.pieces[first_piece_id] += 1;
+        let mut game = Game {
+            board: vec![None; width * (height + BUFFER_ROWS)],
+            width,
+            height,
+            active_piece: ActivePiece::new(first_piece_id, &options.pieces, width),
+            srs: *options.pieces == *standard_pieces(),
+            initial_action: None,
+            input_queue: VecDeque::new(),
+            rng,
+            seed,
+            randomizer,
+            garbage_rng: StdRng::seed_from_u64(seed ^ GARBAGE_SEED_SALT),
+            garbage_bursts: 0,
+            last_garbage_time: Duration::ZERO,
+            pending_garbage: VecDeque::new(),
+            is_game_over: false,
+            game_over_reason: None,
+            paused: false,
+            paused_at: Duration::ZERO,
+            quit_prompt: None,
+            pause_selection: PauseSelection::Resume,
+            gravity_delay: gravity_for_options(&options, start_level),
+            clock: Duration::ZERO,
+            last_gravity_time: Duration::ZERO,
+            level: start_level,
+            total_lines_cleared: 0,
+            score: 0,
+            next_queue,
+            status_message: None,
+            held_piece_id: None,
+            hold_used_this_turn: false,
+            holds_remaining: (options.max_holds > 0).then_some(options.max_holds),
+            lock_timer: None,
+            lock_resets: 0,
+            combo: no_combo(),
+            back_to_back: false,
+            last_action_was_rotation: false,
+            held_shift: None,
+            held_rotation: None,
+            piece_start: ActivePiece::new(first_piece_id, &options.pieces, width),
+            piece_inputs: 0,
+            clearing: None,
+            clearing_t_spin: false,
+            events: Vec::new(),
+            play_time: Duration::ZERO,
+            timer_running: false,
+            goal_reached: false,
+            stats,
+            show_stats: false,
+            show_heights: false,
+            next_hidden: options.hide_next,
+            soft_drop_held: false,
+            placed_at: vec![Duration::ZERO; width * (height + BUFFER_ROWS)],
+            lock_flash: None,
+            reveals_left: options.reveals,
+            revealed_at: None,
+            undo_history: VecDeque::new(),
+            spawn_state: None,
+            editing: false,
+            options,
+        };
+        if let Some(setup) = game.options.setup.clone() {
+            game.apply_setup(&setup);
+        }
+        game.drop_into_view();
+        game.start_finesse();
+        game.remember_spawn();
+        game
+    }
+
+    /// Fills the bottom of the board with the setup's rows and deals its pieces ahead
+    /// of the ones already drawn, which keep their order after them.
+    fn apply_setup(&mut self, setup: &Setup) {
+        let top = self.stored_rows() - setup.rows.len();
+        for (y, row) in setup.rows.iter().enumerate() {
+            let start = (top + y) * self.width;
+            self.board[start..start + self.width].copy_from_slice(row);
+        }
+        let Some((&first, rest)) = setup.pieces.split_first() else { return };
+        let drawn = self.active_piece.id;
+        self.stats.pieces[drawn] -= 1;
+        self.stats.pieces[first] += 1;
+        let queued = std::mem::take(&mut self.next_queue);
+        self.next_queue = rest.iter().copied().chain([drawn]).chain(queued).collect();
+        self.active_piece = ActivePiece::new(first, &self.options.pieces, self.width);
+    }
+
+    // --- ACCESSORS ---
+
+    /// The locked cells of the visible board in row-major order, `width * height` long.
+    pub fn board(&self) -> &[Option<Color>] { &self.board[BUFFER_ROWS * self.width..] }
+    /// The piece set, indexed by piece id.
+    pub fn pieces(&self) -> &[Piece] { &self.options.pieces }
+    pub fn width(&self) -> usize { self.width }
+    pub fn height(&self) -> usize { self.height }
+    /// Rows from the floor up to the highest locked block; 0 for an empty board. More
+    /// than `height` once the stack reaches into the buffer.
+    pub fn max_stack_height(&self) -> usize {
+        self.board.iter().position(Option::is_some).map_or(0, |i| self.height + BUFFER_ROWS - i / self.width)
+    }
+    pub fn active_piece(&self) -> &ActivePiece { &self.active_piece }
+    /// Upcoming piece ids, the next one to spawn first. May be longer than the preview count.
+    pub fn next_queue(&self) -> &VecDeque<usize> { &self.next_queue }
+    /// The next `n` piece ids to spawn, the next one first. Reads past the end of the
+    /// queue by drawing from copies of the bag and its generator, so the game itself is
+    /// left as it was and will deal exactly these pieces.
+    pub fn upcoming(&self, n: usize) -> Vec<usize> {
+        let mut upcoming: Vec<usize> = self.next_queue.iter().copied().take(n).collect();
+        let (mut randomizer, mut rng) = (self.randomizer.clone(), self.rng.clone());
+        while upcoming.len() < n {
+            upcoming.push(randomizer.next(&mut rng));
+        }
+        upcoming
+    }
+    pub fn held_piece_id(&self) -> Option<usize> { self.held_piece_id }
+    pub fn score(&self) -> u64 { self.score }
+    pub fn level(&self) -> u32 { self.level }
+    pub fn lines_cleared(&self) -> u32 { self.total_lines_cleared }
+    /// Current combo count; 0 or below means no combo bonus is active.
+    pub fn combo(&self) -> i32 { self.combo }
+    pub fn back_to_back(&self) -> bool { self.back_to_back }
+    pub fn stats(&self) -> &Stats { &self.stats }
+    /// Pieces dealt per second of play time; 0 before the clock starts.
+    pub fn pieces_per_second(&self) -> f64 {
+        let secs = self.play_time.as_secs_f64();
+        if secs > 0.0 { self.stats.total_pieces() as f64 / secs } else { 0.0 }
+    }
+    pub fn stats_visible(&self) -> bool { self.show_stats }
+    /// Whether the column height and hole overlay is on.
+    pub fn heights_visible(&self) -> bool { self.show_heights }
+    /// How high each column is stacked, counting from the floor; may be more than
+    /// [`Game::height`] once the stack reaches into the buffer rows.
+    pub fn column_heights(&self) -> Vec<usize> {
+        column_heights_in(&self.filled_cells(), self.width)
+    }
+    /// The visible empty cells with a block somewhere above them, as (x, y), top row first.
+    pub fn holes(&self) -> Vec<(usize, usize)> {
+        holes_in(&self.filled_cells(), self.width)
+            .into_iter()
+            .filter_map(|(x, y)| Some((x, y.checked_sub(BUFFER_ROWS)?)))
+            .collect()
+    }
+    /// Whether the next queue is hidden. The queue still advances as usual.
+    pub fn next_hidden(&self) -> bool { self.next_hidden }
+    /// Time played so far, not counting pauses or the wait before the first move.
+    pub fn play_time(&self) -> Duration { self.play_time }
+    /// Whether the game is over because the mode's goal (e.g. the sprint line count) was
+    /// reached; for marathon this means the game was won.
+    pub fn goal_reached(&self) -> bool { self.goal_reached }
+    /// Why the game ended; `None` while it is still going (or for older finished saves).
+    pub fn game_over_reason(&self) -> Option<GameOverReason> { self.game_over_reason }
+    /// Lines the mode ends the game at, if it has a line goal.
+    pub fn line_goal(&self) -> Option<u32> {
+        match self.options.mode {
+            GameMode::Sprint { lines } => Some(lines),
+            GameMode::Marathon { lines } if lines > 0 => Some(lines),
+            _ => None,
+        }
+    }
+    /// Play time left before an ultra game ends; `None` in modes without a time limit.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        match self.options.mode {
+            GameMode::Ultra { time_limit } => Some(time_limit.saturating_sub(self.play_time)),
+            _ => None,
+        }
+    }
+    pub fn is_game_over(&self) -> bool { self.is_game_over }
+    pub fn is_paused(&self) -> bool { self.paused }
+    pub fn pause_selection(&self) -> PauseSelection { self.pause_selection }
+    pub fn is_confirming_quit(&self) -> bool { self.quit_prompt.is_some() }
+    pub fn options(&self) -> &GameOptions { &self.options }
+    /// The seed of this game's piece sequence; pass it as [`GameOptions::seed`] to replay it.
+    pub fn seed(&self) -> u64 { self.seed }
+
+    /// Visible rows currently flashing before they are cleared, and how long they have
+    /// been flashing.
+    pub fn clearing_rows(&self) -> Option<(Vec<usize>, Duration)> {
+        self.clearing.as_ref().map(|(rows, started)| {
+            (rows.iter().filter_map(|y| y.checked_sub(BUFFER_ROWS)).collect(), self.since(*started))
+        })
+    }
+
+    /// The [`Game::board`] cells of the piece that just locked, while they still flash white.
+    pub fn lock_flash(&self) -> Vec<usize> {
+        match &self.lock_flash {
+            Some((cells, locked)) if self.since(*locked) < LOCK_FLASH_TIME => {
+                cells.iter().filter_map(|i| i.checked_sub(BUFFER_ROWS * self.width)).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The current status message, if one was set in the last couple of seconds.
+    pub fn status_message(&self) -> Option<&str> {
+        self.status_message.as_ref().map(|(msg, _)| msg.as_str())
+    }
+
+    pub fn set_status_message(&mut self, msg: String) {
+        self.status_message = Some((msg, self.clock));
+    }
+
+    /// Game time until [`Game::update`] next changes something by itself: a gravity
+    /// step, lock, line clear, auto-shift repeat, garbage burst, fading cell, the end of
+    /// an ultra game or a status message timing out. `None` if nothing is scheduled.
+    /// Frontends that draw only when something changes can sleep this long; the running
+    /// play clock isn't counted.
+    pub fn time_until_next_change(&self) -> Option<Duration> {
+        let mut due = Vec::new();
+        if let Some((_, shown)) = self.status_message {
+            due.push(shown + STATUS_TIME);
+        }
+        if !self.is_game_over && !self.paused {
+            if let Some((_, started)) = &self.clearing {
+                due.push(*started + self.options.line_clear_delay);
+            } else {
+                let gravity_delay = if self.soft_drop_held {
+                    self.gravity_delay / self.options.soft_drop_factor.max(1)
+                } else {
+                    self.gravity_delay
+                };
+                due.push(self.last_gravity_time + gravity_delay);
+                due.extend(self.lock_timer.map(|started| started + self.options.lock_delay));
+                due.extend(self.held_shift.map(|held| held.next_move_at));
+                due.extend(self.held_rotation.map(|held| held.next_turn_at));
+                due.extend(self.garbage_interval().map(|interval| self.last_garbage_time + interval));
+                // Garbage becoming ready changes how the meter looks.
+                due.extend(self.pending_garbage.iter().map(|&(_, ready_at)| ready_at).filter(|&at| at > self.clock));
+            }
+            let flash_end = self.lock_flash.as_ref().map(|(_, locked)| *locked + LOCK_FLASH_TIME);
+            due.extend(flash_end.filter(|&at| at > self.clock));
+            if let (GameMode::Ultra { time_limit }, true) = (self.options.mode, self.timer_running) {
+                due.push(self.clock + time_limit.saturating_sub(self.play_time));
+            }
+            if self.options.invisible {
+                let fades = self.board.iter().zip(&self.placed_at).filter(|(cell, _)| cell.is_some());
+                let hides = fades
+                    .map(|(_, &placed)| placed + self.options.fade_delay)
+                    .chain(self.revealed_at.map(|started| started + REVEAL_TIME));
+                due.extend(hides.filter(|&at| at > self.clock));
+            }
+        }
+        due.into_iter().min().map(|at| at.saturating_sub(self.clock))
+    }
+
+    /// Returns and forgets the events recorded since the last call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn record(&mut self, event: GameEvent) {
+        if self.options.record_events {
+            self.events.push(event);
+        }
+    }
+
+    /// Opens the pause menu with "Resume" highlighted, or closes it. Time spent paused
+    /// never advances the game: on resume every timer picks up with the time it had
+    /// left when the pause began.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if self.paused {
+            self.paused_at = self.clock;
+            self.pause_selection = PauseSelection::Resume;
+            // Key releases aren't acted on while paused, so stop any auto shift now.
+            self.held_shift = None;
+            self.held_rotation = None;
+            self.soft_drop_held = false;
+            self.input_queue.clear();
+        } else {
+            self.skip_paused_time(self.since(self.paused_at));
+        }
+    }
+
+    /// Puts up "Quit? (Y/N)", pausing the game until [`Game::cancel_quit`] takes it down
+    /// again; answering yes is up to the caller. A game already paused stays paused.
+    pub fn ask_to_quit(&mut self) {
+        if self.quit_prompt.is_none() {
+            self.quit_prompt = Some(!self.paused);
+            if !self.paused {
+                self.toggle_pause();
+            }
+        }
+    }
+
+    /// Takes down the quit question and resumes the game if asking paused it.
+    pub fn cancel_quit(&mut self) {
+        if self.quit_prompt.take() == Some(true) && self.paused {
+            self.toggle_pause();
+        }
+    }
+
+    /// Moves every game timer's starting point forward by `paused`, so the game
+    /// continues as if the clock had stopped for that long.
+    fn skip_paused_time(&mut self, paused: Duration) {
+        self.last_gravity_time += paused;
+        self.last_garbage_time += paused;
+        for (_, ready_at) in &mut self.pending_garbage {
+            *ready_at += paused;
+        }
+        if let Some(started) = &mut self.lock_timer {
+            *started += paused;
+        }
+        if let Some((_, started)) = &mut self.clearing {
+            *started += paused;
+        }
+        if let Some(started) = &mut self.revealed_at {
+            *started += paused;
+        }
+        for placed in &mut self.placed_at {
+            *placed += paused;
+        }
+        if let Some((_, locked)) = &mut self.lock_flash {
+            *locked += paused;
+        }
+    }
+
+    /// Switches the panel between the controls list and the stats block.
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+    }
+
+    /// Turns the column height and hole overlay on or off.
+    pub fn toggle_heights(&mut self) {
+        self.show_heights = !self.show_heights;
+    }
+
+    /// Hides or shows the next queue. Showing it even once means the game no longer
+    /// counts as [`Stats::blind`].
+    pub fn toggle_next_hidden(&mut self) {
+        self.next_hidden = !self.next_hidden;
+        if !self.next_hidden {
+            self.stats.blind = false;
+        }
+        let message = if self.next_hidden { "Next queue hidden" } else { "Next queue shown" };
+        self.set_status_message(message.to_string());
+    }
+
+    /// Whether the locked cell at `index` in [`Game::board`] should be drawn. In
+    /// invisible mode cells vanish `fade_delay` after they lock, but the whole stack
+    /// shows during a reveal and once the game is over.
+    pub fn cell_visible(&self, index: usize) -> bool {
+        !self.options.invisible
+            || self.is_game_over
+            || self.revealed_at.is_some_and(|started| self.since(started) < REVEAL_TIME)
+            || self.since(self.placed_at[BUFFER_ROWS * self.width + index]) < self.options.fade_delay
+    }
+
+    /// Holds left this game; `None` when holds are unlimited.
+    pub fn holds_remaining(&self) -> Option<u32> { self.holds_remaining }
+
+    /// Whether hold was already used on the active piece, so it can't be used again
+    /// until the piece locks.
+    pub fn hold_locked(&self) -> bool { self.hold_used_this_turn }
+
+    /// Reveals left in an invisible game; `None` when the stack is always visible.
+    pub fn reveals_left(&self) -> Option<u32> {
+        self.options.invisible.then_some(self.reveals_left)
+    }
+
+    /// Takes back the last placed piece in a practice game: the board, score, queue
+    /// and everything else go back to how they were when it spawned. Works after a
+    /// top-out too.
+    pub fn undo(&mut self) {
+        if !self.options.practice {
+            self.set_status_message("Undo is only available in practice mode".to_string());
+            return;
+        }
+        if self.paused || self.clearing.is_some() {
+            return;
+        }
+        let Some(state) = self.undo_history.pop_back() else {
+            self.set_status_message("Nothing to undo".to_string());
+            return;
+        };
+        self.restore(state);
+        self.remember_spawn();
+    }
+
+    /// Turns practice edit mode on or off. While editing, gravity and garbage stop, soft
+    /// drop moves the piece down without scoring, and hard drop locks it where it is, so
+    /// a board can be built by hand and saved with [`Game::export_setup`].
+    pub fn toggle_edit_mode(&mut self) {
+        if !self.options.practice {
+            self.set_status_message("Edit mode is only available in practice mode".to_string());
+            return;
+        }
+        if self.paused || self.is_game_over {
+            return;
+        }
+        self.editing = !self.editing;
+        self.lock_timer = None;
+        self.last_gravity_time = self.clock;
+        self.last_garbage_time = self.clock;
+        let message = if self.editing { "Edit mode: hard drop locks in place" } else { "Edit mode off" };
+        self.set_status_message(message.to_string());
+    }
+
+    pub fn is_editing(&self) -> bool { self.editing }
+
+    /// Writes the board and upcoming pieces (see [`Setup::from_game`]) to
+    /// "tetris_setup_<n>.toml" in `options.save_dir`, with the first `n` not yet taken,
+    /// and returns the file name.
+    pub fn export_setup(&self) -> io::Result<String> {
+        let name = (1..)
+            .map(|n| format!("tetris_setup_{}.toml", n))
+            .find(|name| !self.options.save_dir.join(name).exists())
+            .expect("some setup file name is free");
+        fs::write(self.options.save_dir.join(&name), Setup::from_game(self).to_toml(&self.options.pieces))?;
+        Ok(name)
+    }
+
+    /// Snapshots the game for undoing the piece that just spawned.
+    fn remember_spawn(&mut self) {
+        if self.options.practice {
+            self.spawn_state = Some(self.to_state());
+        }
+    }
+
+    /// Uses up one reveal to show the hidden stack for [`REVEAL_TIME`].
+    pub fn reveal_board(&mut self) {
+        if !self.options.invisible || self.is_game_over || self.paused {
+            return;
+        }
+        if self.reveals_left == 0 {
+            self.set_status_message("No reveals left".to_string());
+            return;
+        }
+        self.reveals_left -= 1;
+        self.revealed_at = Some(self.clock);
+    }
+
+    /// Moves the pause menu highlight by `delta` entries, wrapping around.
+    pub fn move_pause_selection(&mut self, delta: isize) {
+        let all = PauseSelection::ALL;
+        let index = all.iter().position(|&s| s == self.pause_selection).unwrap_or(0) as isize;
+        self.pause_selection = all[(index + delta).rem_euclid(all.len() as isize) as usize];
+    }
+
+    /// Game time elapsed since `timestamp`.
+    fn since(&self, timestamp: Duration) -> Duration {
+        self.clock.saturating_sub(timestamp)
+    }
+
+    // --- RULES ---
+
+    /// Whether `piece` overlaps a wall, the floor, or a locked cell.
+    pub fn check_collision(&self, piece: &ActivePiece) -> bool {
+        piece.blocks(&self.options.pieces).any(|(x, y)| self.is_blocked(x, y))
+    }
+
+    /// Whether a cell is outside the walls, floor or buffer, or already occupied.
+    fn is_blocked(&self, x: isize, y: isize) -> bool {
+        x < 0
+            || x >= self.width as isize
+            || y >= self.height as isize
+            || y < -(BUFFER_ROWS as isize)
+            || self.board[self.cell_index(x, y)].is_some()
+    }
+
+    /// Where the cell at board coordinates (`x`, `y`) is kept in `board`, buffer rows included.
+    fn cell_index(&self, x: isize, y: isize) -> usize {
+        (y + BUFFER_ROWS as isize) as usize * self.width + x as usize
+    }
+
+    /// Which cells of `board`, buffer rows included, hold a block.
+    fn filled_cells(&self) -> Vec<bool> {
+        self.board.iter().map(Option::is_some).collect()
+    }
+
+    /// Rows in `board`, buffer rows included.
+    fn stored_rows(&self) -> usize {
+        self.height + BUFFER_ROWS
+    }
+
+    /// Pieces appear in the buffer and drop into view a row at once, if nothing is in the
+    /// way. One that spawned overlapping the stack stays put to block out.
+    fn drop_into_view(&mut self) {
+        let lowered = ActivePiece { y: self.active_piece.y + 1, ..self.active_piece.clone() };
+        if !self.check_collision(&self.active_piece) && !self.check_collision(&lowered) {
+            self.active_piece = lowered;
+        }
+    }
+
+    fn spawn_new_piece(&mut self) {
+        let next_id = self.next_queue.pop_front().expect("next queue is never empty");
+        self.active_piece = ActivePiece::new(next_id, &self.options.pieces, self.width);
+        self.stats.pieces[next_id] += 1;
+        self.fill_next_queue();
+        self.hold_used_this_turn = false;
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.last_action_was_rotation = false;
+        self.record(GameEvent::Spawned { piece: next_id });
+        self.drop_into_view();
+        self.start_finesse();
+        self.remember_spawn();
+
+        if self.check_collision(&self.active_piece) {
+            self.top_out(GameOverReason::BlockOut);
+            if self.is_game_over {
+                return;
+            }
+        }
+        self.apply_initial_action();
+        self.carry_shift_charge();
+        // A rotate key still held waits a full interval before turning the new piece.
+        if let (Some(held), Some(interval)) = (&mut self.held_rotation, self.options.rotate_repeat) {
+            held.next_turn_at = self.clock + interval;
+        }
+    }
+
+    /// Starts counting inputs for the active piece from where it is now.
+    fn start_finesse(&mut self) {
+        self.piece_start = self.active_piece.clone();
+        self.piece_inputs = 0;
+    }
+
+    /// Hands a held sideways key over to a newly spawned piece: with DAS carry a charged
+    /// key moves it at once, otherwise DAS starts charging again from now.
+    fn carry_shift_charge(&mut self) {
+        let Some(held) = &mut self.held_shift else { return };
+        if self.options.das_carry {
+            self.apply_auto_shift();
+        } else {
+            held.next_move_at = self.clock + self.options.das;
+        }
+    }
+
+    /// The stack reached the top: the game is over, or in zen mode the board is
+    /// emptied and play goes on.
+    fn top_out(&mut self, reason: GameOverReason) {
+        if self.options.mode == GameMode::Zen {
+            self.board.fill(None);
+            self.placed_at.fill(self.clock);
+            self.record(GameEvent::BoardCleared);
+            return;
+        }
+        self.end_game(reason);
+    }
+
+    fn end_game(&mut self, reason: GameOverReason) {
+        self.is_game_over = true;
+        self.game_over_reason = Some(reason);
+        self.goal_reached = reason == GameOverReason::GoalReached;
+        self.record(GameEvent::GameOver);
+    }
+
+    /// Applies `input` in turn after any inputs still waiting. Inputs wait while rows
+    /// are clearing or the game is paused, then apply in order, so presses made during
+    /// a line-clear animation go to the next piece instead of being lost.
+    pub fn queue_input(&mut self, input: PlayerInput) {
+        if self.is_game_over || self.input_queue.len() >= INPUT_QUEUE_LEN {
+            return;
+        }
+        self.input_queue.push_back(input);
+        self.apply_queued_inputs();
+    }
+
+    /// Applies waiting inputs for as long as the game can take them.
+    fn apply_queued_inputs(&mut self) {
+        while !self.paused && self.clearing.is_none() && !self.is_game_over {
+            let Some(input) = self.input_queue.pop_front() else { return };
+            match input {
+                PlayerInput::MoveLeft => {
+                    self.piece_inputs += 1;
+                    self.try_shift(-1);
+                }
+                PlayerInput::MoveRight => {
+                    self.piece_inputs += 1;
+                    self.try_shift(1);
+                }
+                PlayerInput::Rotate(direction) => {
+                    self.piece_inputs += 1;
+                    self.try_rotate(direction);
+                }
+                PlayerInput::SoftDrop => self.soft_drop(),
+                PlayerInput::HardDrop => self.hard_drop(),
+                PlayerInput::Hold => self.hold_piece(),
+            }
+        }
+        if self.is_game_over {
+            self.input_queue.clear();
+        }
+    }
+
+    /// Applies a rotation or hold buffered within `initial_action_window` before the
+    /// active piece spawned. Older presses are dropped.
+    fn apply_initial_action(&mut self) {
+        let Some((action, pressed)) = self.initial_action.take() else { return };
+        if self.since(pressed) > self.options.initial_action_window {
+            return;
+        }
+        match action {
+            InitialAction::Rotate(direction) => {
+                self.try_rotate(direction);
+            }
+            InitialAction::Hold => self.hold_piece(),
+        }
+    }
+
+    /// Remembers a press that can't be used on the current piece for initial rotation/hold.
+    fn buffer_initial_action(&mut self, action: InitialAction) {
+        if !self.options.initial_action_window.is_zero() {
+            self.initial_action = Some((action, self.clock));
+        }
+    }
+
+    /// Tops the next queue up to the configured preview count from the randomizer.
+    fn fill_next_queue(&mut self) {
+        while self.next_queue.len() < self.options.preview_count.max(1) {
+            let id = self.randomizer.next(&mut self.rng);
+            self.next_queue.push_back(id);
+        }
+    }
+
+    /// Swaps the active piece with the held one, or stashes it and spawns the next piece
+    /// if the hold slot is empty. Only allowed once per piece until it locks; a refused
+    /// hold may still apply to the next piece as an initial hold.
+    pub fn hold_piece(&mut self) {
+        if self.holds_remaining == Some(0) {
+            self.set_status_message("No holds left".to_string());
+            return;
+        }
+        if self.hold_used_this_turn || self.clearing.is_some() {
+            self.buffer_initial_action(InitialAction::Hold);
+            return;
+        }
+        self.timer_running = true;
+        let current_id = self.active_piece.id;
+        match self.held_piece_id.replace(current_id) {
+            Some(held_id) => {
+                self.active_piece = ActivePiece::new(held_id, &self.options.pieces, self.width);
+                self.lock_timer = None;
+                self.lock_resets = 0;
+                self.last_action_was_rotation = false;
+                self.record(GameEvent::Spawned { piece: held_id });
+                if self.check_collision(&self.active_piece) {
+                    self.top_out(GameOverReason::BlockOut);
+                } else {
+                    self.drop_into_view();
+                    self.start_finesse();
+                    self.carry_shift_charge();
+                }
+            }
+            None => self.spawn_new_piece(),
+        }
+        self.hold_used_this_turn = true;
+        if let Some(holds) = &mut self.holds_remaining {
+            *holds -= 1;
+        }
+        self.last_gravity_time = self.clock;
+    }
+
+    /// The hold allowance a fresh game starts with, or `None` if holds are unlimited.
+    fn hold_limit(&self) -> Option<u32> {
+        (self.options.max_holds > 0).then_some(self.options.max_holds)
+    }
+
+    /// Returns a copy of the active piece pushed down to where a hard drop would land it.
+    pub fn ghost_piece(&self) -> ActivePiece {
+        let mut ghost = self.active_piece.clone();
+        loop {
+            ghost.y += 1;
+            if self.check_collision(&ghost) {
+                ghost.y -= 1;
+                return ghost;
+            }
+        }
+    }
+
+    /// Moves the active piece by the given offset if nothing is in the way.
+    pub fn try_move(&mut self, dx: isize, dy: isize) -> bool {
+        if self.clearing.is_some() {
+            return false;
+        }
+        let mut test_piece = self.active_piece.clone();
+        test_piece.x += dx;
+        test_piece.y += dy;
+        if !self.check_collision(&test_piece) {
+            self.record(GameEvent::Moved { x: test_piece.x, y: test_piece.y });
+            self.active_piece = test_piece;
+            self.last_action_was_rotation = false;
+            return true;
+        }
+        false
+    }
+
+    fn is_grounded(&self) -> bool {
+        let mut below = self.active_piece.clone();
+        below.y += 1;
+        self.check_collision(&below)
+    }
+
+    /// Restarts a running lock delay after a successful move or rotation, up to
+    /// `MAX_LOCK_RESETS` times per piece so a piece can't be stalled forever.
+    fn extend_lock_delay(&mut self) {
+        if self.lock_timer.is_some() && self.lock_resets < MAX_LOCK_RESETS {
+            self.lock_timer = Some(self.clock);
+            self.lock_resets += 1;
+        }
+    }
+
+    /// Moves the active piece sideways, extending the lock delay if it was grounded.
+    pub fn try_shift(&mut self, dx: isize) -> bool {
+        self.timer_running = true;
+        let moved = self.try_move(dx, 0);
+        if moved {
+            self.extend_lock_delay();
+        }
+        moved
+    }
+
+    /// Starts a sideways move in direction `dir` (-1 left, 1 right): the piece moves one
+    /// cell right away and, with auto shift on, keeps moving every ARR once DAS has elapsed
+    /// until [`Game::release_shift`] is called.
+    pub fn press_shift(&mut self, dir: isize) {
+        self.queue_input(if dir < 0 { PlayerInput::MoveLeft } else { PlayerInput::MoveRight });
+        if self.options.auto_shift {
+            self.held_shift = Some(HeldShift { dir, next_move_at: self.clock + self.options.das });
+        }
+    }
+
+    /// Stops auto shift in direction `dir`. Releasing the other direction is ignored so
+    /// rolling from one key to the other keeps the newer one active.
+    pub fn release_shift(&mut self, dir: isize) {
+        if self.held_shift.is_some_and(|held| held.dir == dir) {
+            self.held_shift = None;
+        }
+    }
+
+    /// Applies any auto-shift moves that came due since the last update.
+    fn apply_auto_shift(&mut self) {
+        let Some(mut held) = self.held_shift else { return };
+        let now = self.clock;
+        if now < held.next_move_at {
+            return;
+        }
+        if self.options.arr.is_zero() {
+            while self.try_shift(held.dir) {}
+            return;
+        }
+        // Catch up on missed repeats, but never more than a board's width in one go.
+        for _ in 0..self.width {
+            if now < held.next_move_at {
+                break;
+            }
+            self.try_shift(held.dir);
+            held.next_move_at += self.options.arr;
+        }
+        held.next_move_at = held.next_move_at.max(now);
+        self.held_shift = Some(held);
+    }
+
+    /// Starts a rotation: the piece turns once right away (or as soon as it can) and,
+    /// with rotate repeat and auto shift on, turns again every interval while the key
+    /// stays down and the piece is airborne, until [`Game::release_rotate`].
+    pub fn press_rotate(&mut self, direction: RotationDir) {
+        self.queue_input(PlayerInput::Rotate(direction));
+        if let (Some(interval), true) = (self.options.rotate_repeat, self.options.auto_shift) {
+            self.held_rotation = Some(HeldRotation { direction, next_turn_at: self.clock + interval });
+        }
+    }
+
+    /// Stops rotate repeat in `direction`; releasing another rotate key is ignored.
+    pub fn release_rotate(&mut self, direction: RotationDir) {
+        if self.held_rotation.is_some_and(|held| held.direction == direction) {
+            self.held_rotation = None;
+        }
+    }
+
+    /// Turns the piece for a held rotate key whose interval has passed. Grounded
+    /// pieces are skipped; the key keeps its timing and turns them once they fall.
+    fn apply_rotate_repeat(&mut self) {
+        let (Some(mut held), Some(interval)) = (self.held_rotation, self.options.rotate_repeat) else { return };
+        if self.clock < held.next_turn_at {
+            return;
+        }
+        if !self.is_grounded() {
+            self.try_rotate(held.direction);
+        }
+        held.next_turn_at = self.clock + interval;
+        self.held_rotation = Some(held);
+    }
+
+    /// Rotates the active piece in `direction`, trying the wall kicks for that turn in
+    /// order if the rotated piece doesn't fit in place. Returns whether the rotation happened.
+    /// While rows are clearing the turn is saved for the next piece as an initial rotation.
+    pub fn try_rotate(&mut self, direction: RotationDir) -> bool {
+        if self.clearing.is_some() {
+            self.buffer_initial_action(InitialAction::Rotate(direction));
+            return false;
+        }
+        self.timer_running = true;
+        let Some(rotated) = self.rotated(&self.active_piece, direction) else { return false };
+        self.record(GameEvent::Rotated { rotation: rotated.rotation, x: rotated.x, y: rotated.y });
+        self.active_piece = rotated;
+        self.extend_lock_delay();
+        self.last_action_was_rotation = true;
+        true
+    }
+
+    /// Counts a finesse fault if the player used more inputs on the active piece than
+    /// it takes to drop it where it is about to lock.
+    fn judge_finesse(&mut self) {
+        // Pieces moved only by gravity, auto-repeat or a bot can't be faulted, so skip the
+        // search. Neither can pieces placed by hand in edit mode.
+        if self.piece_inputs == 0 || self.editing {
+            return;
+        }
+        let cells: Vec<_> = self.active_piece.blocks(&self.options.pieces).collect();
+        if self.finesse_minimum(&self.piece_start, &cells).is_some_and(|minimum| self.piece_inputs > minimum) {
+            self.stats.finesse_faults += 1;
+        }
+    }
+
+    /// Where `piece` ends up turned in `direction` after the first wall kick that fits,
+    /// or `None` if none does.
+    fn rotated(&self, piece: &ActivePiece, direction: RotationDir) -> Option<ActivePiece> {
+        let mut test_piece = piece.clone();
+        let num_rotations = test_piece.definition(&self.options.pieces).rotations.len();
+        let steps = match direction {
+            RotationDir::Clockwise => 1,
+            RotationDir::CounterClockwise => num_rotations - 1,
+            RotationDir::Half => 2,
+        };
+        test_piece.rotation = (test_piece.rotation + steps) % num_rotations;
+
+        // Wall kick attempts. Custom pieces have no rotation boxes or kick tables, so
+        // they turn in place and try the half-turn kicks.
+        let candidates = if self.srs {
+            srs_rotation_candidates(test_piece.id, piece.rotation, test_piece.rotation)
+        } else {
+            HALF_TURN_KICKS.iter().map(|&(kick_x, kick_y)| (kick_x, -kick_y)).collect()
+        };
+        candidates.into_iter().find_map(|(dx, dy)| {
+            let kicked = ActivePiece { x: piece.x + dx, y: piece.y + dy, ..test_piece.clone() };
+            (!self.check_collision(&kicked)).then_some(kicked)
+        })
+    }
+
+    /// The fewest inputs that take a piece from `start` to a spot whose plain drop
+    /// lands on `cells`: single moves, moves held to the wall (DAS) and rotations each
+    /// count one. `None` if no such spot exists, as for pieces tucked under overhangs
+    /// or spun into place.
+    fn finesse_minimum(&self, start: &ActivePiece, cells: &[(isize, isize)]) -> Option<u32> {
+        let mut target = cells.to_vec();
+        target.sort_unstable();
+        let key = |piece: &ActivePiece| (piece.x, piece.y, piece.rotation);
+        let mut seen = HashSet::from([key(start)]);
+        let mut frontier = VecDeque::from([(start.clone(), 0)]);
+        while let Some((piece, inputs)) = frontier.pop_front() {
+            let mut landing = piece.clone();
+            while !self.check_collision(&ActivePiece { y: landing.y + 1, ..landing.clone() }) {
+                landing.y += 1;
+            }
+            let mut landed: Vec<_> = landing.blocks(&self.options.pieces).collect();
+            landed.sort_unstable();
+            if landed == target {
+                return Some(inputs);
+            }
+            let shift = |from: &ActivePiece, dx: isize| {
+                let moved = ActivePiece { x: from.x + dx, ..from.clone() };
+                (!self.check_collision(&moved)).then_some(moved)
+            };
+            let to_wall = |dx: isize| {
+                let mut last = shift(&piece, dx)?;
+                while let Some(next) = shift(&last, dx) {
+                    last = next;
+                }
+                Some(last)
+            };
+            let turns = [RotationDir::Clockwise, RotationDir::CounterClockwise, RotationDir::Half];
+            let next = [shift(&piece, -1), shift(&piece, 1), to_wall(-1), to_wall(1)]
+                .into_iter()
+                .chain(turns.map(|direction| self.rotated(&piece, direction)));
+            for next in next.flatten() {
+                if seen.insert(key(&next)) {
+                    frontier.push_back((next, inputs + 1));
+                }
+            }
+        }
+        None
+    }
+
+    /// Starts a soft drop: the piece drops one row right away and, with auto shift on,
+    /// gravity runs `soft_drop_factor` times faster until [`Game::release_soft_drop`].
+    pub fn press_soft_drop(&mut self) {
+        self.soft_drop();
+        if self.options.auto_shift {
+            self.soft_drop_held = true;
+        }
+    }
+
+    pub fn release_soft_drop(&mut self) {
+        self.soft_drop_held = false;
+    }
+
+    /// Player-requested one-row drop, worth 1 point per cell. Gravity ticks award nothing.
+    pub fn soft_drop(&mut self) {
+        if self.clearing.is_some() {
+            return;
+        }
+        self.timer_running = true;
+        if self.editing {
+            self.try_move(0, 1);
+        } else if self.try_move(0, 1) {
+            self.award(1, |points| &mut points.soft_drop);
+        } else if self.lock_timer.is_none() {
+            self.lock_timer = Some(self.clock);
+        }
+        self.last_gravity_time = self.clock;
+    }
+
+    /// Drops the piece straight down and locks it, worth 2 points per cell fallen.
+    /// The bonus is added before locking so it counts even if the lock ends the game.
+    pub fn hard_drop(&mut self) {
+        if self.clearing.is_some() {
+            return;
+        }
+        self.timer_running = true;
+        if self.editing {
+            self.lock(false);
+            return;
+        }
+        // Jump straight to the landing spot so the move is a single event.
+        let ghost = self.ghost_piece();
+        let distance = (ghost.y - self.active_piece.y) as u32;
+        if distance > 0 {
+            self.record(GameEvent::Moved { x: ghost.x, y: ghost.y });
+            self.active_piece = ghost;
+            self.last_action_was_rotation = false;
+        }
+        self.award(distance * 2, |points| &mut points.hard_drop);
+        self.lock(true);
+        self.last_gravity_time = self.clock;
+    }
+
+    /// Pushes `count` garbage rows up from the bottom, each full except for one hole
+    /// column shared by the whole burst. The active piece is lifted if the garbage
+    /// runs into it. Anything pushed off the top of the buffer tops out.
+    pub fn add_garbage_lines(&mut self, count: u32) {
+        let count = (count as usize).min(self.stored_rows());
+        if count == 0 || self.is_game_over {
+            return;
+        }
+        let hole = self.garbage_rng.gen_range(0..self.width);
+        self.garbage_bursts += 1;
+        let overflow = self.board[..count * self.width].iter().any(Option::is_some);
+
+        self.board.drain(..count * self.width);
+        self.placed_at.drain(..count * self.width);
+        if let Some((cells, _)) = &mut self.lock_flash {
+            let shift = count * self.width;
+            cells.retain(|&i| i >= shift);
+            cells.iter_mut().for_each(|i| *i -= shift);
+        }
+        for _ in 0..count {
+            self.board.extend((0..self.width).map(|x| (x != hole).then_some(GARBAGE_COLOR)));
+        }
+        self.placed_at.resize(self.board.len(), self.clock);
+        self.record(GameEvent::GarbageAdded { count: count as u32, hole });
+
+        if self.check_collision(&self.active_piece) {
+            self.active_piece.y -= count as isize;
+        }
+        if overflow || self.check_collision(&self.active_piece) {
+            self.top_out(GameOverReason::TopOut);
+        }
+    }
+
+    /// Queues `lines` of garbage. Once [`GameOptions::garbage_delay`] has passed, the next
+    /// piece to lock pushes it in as one burst with [`Game::add_garbage_lines`].
+    pub fn queue_garbage(&mut self, lines: u32) {
+        if lines > 0 && !self.is_game_over {
+            self.pending_garbage.push_back((lines, self.clock + self.options.garbage_delay));
+        }
+    }
+
+    /// Queued garbage lines: (ready to go in at the next lock, still on their way).
+    pub fn pending_garbage(&self) -> (u32, u32) {
+        self.pending_garbage.iter().fold((0, 0), |(ready, arriving), &(lines, ready_at)| {
+            if ready_at <= self.clock {
+                (ready.saturating_add(lines), arriving)
+            } else {
+                (ready, arriving.saturating_add(lines))
+            }
+        })
+    }
+
+    /// Pushes in every queued burst whose delay is over, oldest first.
+    fn insert_ready_garbage(&mut self) {
+        while let Some(&(lines, ready_at)) = self.pending_garbage.front() {
+            if ready_at > self.clock || self.is_game_over {
+                break;
+            }
+            self.pending_garbage.pop_front();
+            self.add_garbage_lines(lines);
+        }
+    }
+
+    /// Time between garbage bursts, or `None` when garbage is off.
+    fn garbage_interval(&self) -> Option<Duration> {
+        (self.options.garbage_rate > 0)
+            .then(|| Duration::from_secs(60 * GARBAGE_BURST as u64) / self.options.garbage_rate)
+    }
+
+    /// Writes the active piece into the board, clears any full lines and spawns the next piece.
+    /// With a line-clear delay, full rows flash first and `update` finishes the clear later.
+    pub fn lock_piece(&mut self) {
+        self.lock(false);
+    }
+
+    /// [`Game::lock_piece`], noting in the event whether a hard drop caused it.
+    fn lock(&mut self, hard_drop: bool) {
+        if self.clearing.is_some() {
+            return;
+        }
+        self.lock_timer = None;
+        self.judge_finesse();
+        if let Some(state) = self.spawn_state.take() {
+            if self.undo_history.len() == UNDO_DEPTH {
+                self.undo_history.pop_front();
+            }
+            self.undo_history.push_back(state);
+        }
+        let t_spin = self.is_t_spin();
+        let color = self.active_piece.definition(&self.options.pieces).color;
+        let mut cells = Vec::new();
+        let mut locked_out = true;
+        for (x, y) in self.active_piece.blocks(&self.options.pieces) {
+            let index = self.cell_index(x, y);
+            self.board[index] = Some(color);
+            self.placed_at[index] = self.clock;
+            cells.push(index);
+            locked_out &= y < 0;
+        }
+        self.lock_flash = Some((cells, self.clock));
+        self.record(GameEvent::Locked { piece: self.active_piece.id, hard_drop });
+        if locked_out {
+            // Every block landed in the buffer, out of sight.
+            self.top_out(GameOverReason::LockOut);
+            if self.is_game_over {
+                return;
+            }
+        }
+        let full_rows: Vec<usize> = (0..self.stored_rows())
+            .filter(|&y| self.board[y * self.width..(y + 1) * self.width].iter().all(Option::is_some))
+            .collect();
+        if !full_rows.is_empty() && !self.options.line_clear_delay.is_zero() {
+            self.clearing = Some((full_rows, self.clock));
+            self.clearing_t_spin = t_spin;
+            return;
+        }
+        self.clear_lines(t_spin);
+        if !self.is_game_over {
+            self.spawn_new_piece();
+            self.insert_ready_garbage();
+        }
+    }
+
+    /// A T-spin is a T-piece that was rotated into place (its last movement was a
+    /// rotation) with at least 3 of the 4 diagonal corners around its center blocked.
+    fn is_t_spin(&self) -> bool {
+        let piece = &self.active_piece;
+        if !self.srs || piece.id != T_PIECE || !self.last_action_was_rotation {
+            return false;
+        }
+        let (dx, dy) = T_CENTER[piece.rotation];
+        let (cx, cy) = (piece.x + dx, piece.y + dy);
+        let blocked_corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .iter()
+            .filter(|&&(ox, oy)| self.is_blocked(cx + ox, cy + oy))
+            .count();
+        blocked_corners >= 3
+    }
+
+    /// Adds `points` to the score and to the part of [`Stats::points`] `part` picks.
+    /// Both stop at `u64::MAX` rather than wrapping.
+    fn award(&mut self, points: u32, part: impl FnOnce(&mut ScoreBreakdown) -> &mut u64) {
+        self.score = self.score.saturating_add(points.into());
+        let part = part(&mut self.stats.points);
+        *part = part.saturating_add(points.into());
+    }
+
+    fn clear_lines(&mut self, t_spin: bool) {
+        let cleared_lines_count = self.remove_full_rows();
+        // Rows completed while building a board in edit mode just go: nothing scores.
+        if self.editing {
+            return;
+        }
+
+        let mut points: u32 = if t_spin {
+            match cleared_lines_count {
+                0 => 400,
+                1 => 800,
+                2 => 1200,
+                _ => 1600,
+            }
+        } else {
+            self.options.scoring.line_clear_points(cleared_lines_count, self.level)
+        };
+        // Tetrises and line-clearing T-spins chain into a back-to-back bonus; other
+        // clears break the chain, while placements that clear nothing leave it alone.
+        let difficult = cleared_lines_count > 0 && (t_spin || cleared_lines_count >= 4);
+        if difficult && self.back_to_back {
+            points = (u64::from(points) * 3).div_ceil(2).try_into().unwrap_or(u32::MAX);
+        }
+        if cleared_lines_count > 0 {
+            self.back_to_back = difficult;
+            self.record(GameEvent::LinesCleared { count: cleared_lines_count, t_spin });
+        }
+        if t_spin {
+            self.award(points, |points| &mut points.t_spins);
+        } else {
+            self.award(points, |points| &mut points.clears);
+        }
+        if t_spin {
+            let name = match cleared_lines_count {
+                0 => "T-SPIN!",
+                1 => "T-SPIN SINGLE!",
+                2 => "T-SPIN DOUBLE!",
+                _ => "T-SPIN TRIPLE!",
+            };
+            self.set_status_message(name.to_string());
+            self.stats.t_spins += 1;
+        }
+
+        if cleared_lines_count > 0 {
+            self.combo += 1;
+            if self.combo > 0 {
+                let bonus = (self.combo as u32).saturating_mul(50).saturating_mul(self.level);
+                self.award(bonus, |points| &mut points.combos);
+                self.stats.max_combo = self.stats.max_combo.max(self.combo as u32);
+            }
+        } else {
+            self.combo = no_combo();
+        }
+
+        if cleared_lines_count > 0 {
+            self.stats.clears[cleared_lines_count.min(4) as usize - 1] += 1;
+        }
+        self.total_lines_cleared += cleared_lines_count;
+        if cleared_lines_count > 0 && self.options.gravity_mode == GravityMode::Sticky {
+            self.cascade();
+        }
+        // Only a clear can make a perfect clear; a board that was already empty doesn't count.
+        if cleared_lines_count > 0 && self.board.iter().all(Option::is_none) {
+            self.award(perfect_clear_points(cleared_lines_count), |points| &mut points.perfect_clears);
+            self.stats.perfect_clears += 1;
+            self.set_status_message("PERFECT CLEAR!".to_string());
+        }
+        let new_level = (1 + self.total_lines_cleared / LINES_PER_LEVEL).max(self.stats.start_level);
+        if new_level != self.level && self.options.mode != GameMode::Zen {
+            self.level = new_level;
+            self.gravity_delay = gravity_for_options(&self.options, self.level);
+        }
+
+        if let Some(lines) = self.line_goal() {
+            if self.total_lines_cleared >= lines {
+                self.end_game(GameOverReason::GoalReached);
+            }
+        }
+    }
+
+    /// Removes the full rows, moving everything above them down, and returns how many
+    /// there were.
+    fn remove_full_rows(&mut self) -> u32 {
+        // Placement times follow their cells down; the new empty rows never show.
+        let kept_times: Vec<Duration> = self.board
+            .chunks_exact(self.width)
+            .zip(self.placed_at.chunks_exact(self.width))
+            .filter(|(row, _)| !row.iter().all(Option::is_some))
+            .flat_map(|(_, times)| times.iter().copied())
+            .collect();
+        self.placed_at = vec![Duration::ZERO; self.board.len() - kept_times.len()];
+        self.placed_at.extend(kept_times);
+        // Flashing cells follow their rows down too, and vanish with the rows they completed.
+        let rows = self.stored_rows();
+        if let Some((cells, _)) = &mut self.lock_flash {
+            let width = self.width;
+            let full = |y: usize| self.board[y * width..(y + 1) * width].iter().all(Option::is_some);
+            let full_rows: Vec<usize> = (0..rows).filter(|&y| full(y)).collect();
+            cells.retain(|&i| !full_rows.contains(&(i / width)));
+            for i in cells.iter_mut() {
+                *i += width * full_rows.iter().filter(|&&y| y > *i / width).count();
+            }
+        }
+        let (new_board, cleared_lines_count) = clear_full_rows(&self.board, self.width);
+        self.board = new_board;
+        cleared_lines_count
+    }
+
+    /// Sticky gravity after a clear: clusters fall, and the rows they complete clear as
+    /// the next link of a chain, until nothing more falls. Each link scores the usual
+    /// points for its lines times its place in the chain, so the first cascade doubles.
+    fn cascade(&mut self) {
+        let mut chain = 1;
+        while let Some(destination) = settle_clusters(&self.board, self.width) {
+            let mut board = vec![None; self.board.len()];
+            let mut placed_at = vec![Duration::ZERO; self.board.len()];
+            for (from, &to) in destination.iter().enumerate().filter(|&(from, _)| self.board[from].is_some()) {
+                board[to] = self.board[from];
+                placed_at[to] = self.placed_at[from];
+            }
+            self.board = board;
+            self.placed_at = placed_at;
+            // The flash can't follow a piece whose blocks fell apart.
+            self.lock_flash = None;
+
+            let count = self.remove_full_rows();
+            if count == 0 {
+                return;
+            }
+            chain += 1;
+            self.record(GameEvent::LinesCleared { count, t_spin: false });
+            let points = self.options.scoring.line_clear_points(count, self.level);
+            self.award(points.saturating_mul(chain), |points| &mut points.cascades);
+            self.stats.clears[count.min(4) as usize - 1] += 1;
+            self.total_lines_cleared += count;
+            self.set_status_message(format!("CASCADE x{}!", chain));
+        }
+    }
+
+    /// Advances the game clock by `dt`, then applies gravity, locks pieces whose lock
+    /// delay ran out, and expires the status message. Game time only moves here, so
+    /// stepping with fixed `dt`s plays out the same way however fast frames are drawn.
+    pub fn update(&mut self, dt: Duration) {
+        self.clock += dt;
+        // Clear status message after a couple of seconds
+        if let Some((_, time)) = self.status_message {
+            if self.since(time) > STATUS_TIME {
+                self.status_message = None;
+            }
+        }
+        if self.is_game_over || self.paused {
+            return;
+        }
+        if self.timer_running {
+            self.play_time += dt;
+            if let GameMode::Ultra { time_limit } = self.options.mode {
+                if self.play_time >= time_limit {
+                    self.play_time = time_limit;
+                    self.end_game(GameOverReason::GoalReached);
+                    return;
+                }
+            }
+        }
+        if let Some((_, started)) = &self.clearing {
+            // Gravity and input wait until the flashing rows are gone.
+            if self.since(*started) >= self.options.line_clear_delay {
+                self.finish_line_clear();
+                self.last_gravity_time = self.clock;
+                self.apply_queued_inputs();
+            }
+            return;
+        }
+        if self.editing {
+            // Pieces only move and lock when the player says so.
+            self.apply_auto_shift();
+            self.apply_rotate_repeat();
+            return;
+        }
+        if let Some(interval) = self.garbage_interval() {
+            if self.since(self.last_garbage_time) >= interval {
+                self.last_garbage_time = self.clock;
+                self.queue_garbage(GARBAGE_BURST);
+            }
+        }
+        self.apply_auto_shift();
+        self.apply_rotate_repeat();
+        if let Some(started) = self.lock_timer {
+            if !self.is_grounded() {
+                self.lock_timer = None;
+            } else if self.since(started) >= self.options.lock_delay {
+                self.lock_piece();
+                self.last_gravity_time = self.clock;
+                return;
+            }
+        }
+        let gravity_delay = if self.soft_drop_held {
+            self.gravity_delay / self.options.soft_drop_factor.max(1)
+        } else {
+            self.gravity_delay
+        };
+        if self.since(self.last_gravity_time) >= gravity_delay {
+            if self.try_move(0, 1) {
+                // Held soft drop scores like pressing Down for every row.
+                if self.soft_drop_held {
+                    self.award(1, |points| &mut points.soft_drop);
+                }
+            } else if self.lock_timer.is_none() {
+                self.lock_timer = Some(self.clock);
+            }
+            self.last_gravity_time = self.clock;
+        }
+    }
+
+    /// Removes rows still flashing from a line clear and deals the next piece, as
+    /// if the clear delay had run out. Does nothing when no lines are clearing.
+    pub fn finish_line_clear(&mut self) {
+        if self.clearing.take().is_none() {
+            return;
+        }
+        self.clear_lines(self.clearing_t_spin);
+        if !self.is_game_over {
+            self.spawn_new_piece();
+            self.insert_ready_garbage();
+        }
+    }
+
+    // --- SAVING & LOADING ---
+
+    /// Writes the game to "tetris_save_<slot>.json" in `options.save_dir`.
+    pub fn save_game(&self, slot: u8) -> io::Result<()> {
+        self.write_save(&save_file(slot)?)
+    }
+
+    /// Writes the game to [`AUTOSAVE_FILE`] in `options.save_dir`, for
+    /// [`Game::resume_autosave`] to pick up next time.
+    pub fn autosave(&self) -> io::Result<()> {
+        self.write_save(AUTOSAVE_FILE)
+    }
+
+    /// Replaces this game with the autosaved one, as [`Game::load_game`] does with a
+    /// slot, then deletes the autosave so it is only resumed once. A malformed
+    /// autosave is deleted too, rather than offered again every launch.
+    pub fn resume_autosave(&mut self) -> io::Result<bool> {
+        let path = self.options.save_dir.join(AUTOSAVE_FILE);
+        let matches = self.read_save(AUTOSAVE_FILE).inspect_err(|e| {
+            if e.kind() == io::ErrorKind::InvalidData {
+                let _ = fs::remove_file(&path);
+            }
+        })?;
+        fs::remove_file(path)?;
+        Ok(matches)
+    }
+
+    fn write_save(&self, name: &str) -> io::Result<()> {
+        let path = self.options.save_dir.join(name);
+        if self.clearing.is_some() {
+            return Err(io::Error::other("lines are still clearing"));
+        }
+        let serialized = serde_json::to_string(&self.to_state())
+            .map_err(io::Error::other)?;
+        fs::write(path, serialized)
+    }
+
+    /// Replaces this game with the one stored in `slot`. An empty slot is reported
+    /// as an [`io::ErrorKind::NotFound`] error and a malformed save as
+    /// [`io::ErrorKind::InvalidData`]; either way the game is left untouched.
+    /// Returns `false` if the loaded game doesn't match the hash the save was written
+    /// with, meaning it was edited or written by an incompatible version.
+    pub fn load_game(&mut self, slot: u8) -> io::Result<bool> {
+        self.read_save(&save_file(slot)?)
+    }
+
+    fn read_save(&mut self, name: &str) -> io::Result<bool> {
+        let data = fs::read_to_string(self.options.save_dir.join(name))?;
+        let state: SerializableGameState = serde_json::from_str(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let saved_hash = state.hash;
+        self.load_state(state)
+            .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, format!("invalid save: {}", msg)))?;
+        Ok(saved_hash.is_none_or(|hash| hash == self.state_hash()))
+    }
+
+    /// Replaces this game with a snapshot from [`Game::to_state`], such as another
+    /// player's game sent over the network. A snapshot that doesn't fit this game's
+    /// piece set is rejected and the game left untouched.
+    pub fn load_state(&mut self, state: SerializableGameState) -> Result<(), String> {
+        state.validate(&self.options.pieces)?;
+        self.restore(state);
+        // Undo only goes back through this game, not the one that was replaced.
+        self.undo_history.clear();
+        self.remember_spawn();
+        Ok(())
+    }
+
+    /// A fingerprint of the board, score and piece sequence: games in the same state
+    /// hash the same, so two runs (or a game and its save) that drift apart can be
+    /// caught. Timers aren't included.
+    pub fn state_hash(&self) -> u64 {
+        let mut fnv = Fnv::new();
+        for n in [self.width, self.height] {
+            fnv.write_u64(n as u64);
+        }
+        // The buffer is left out while it's empty, so saves from before it existed still match.
+        let hashed_cells = if self.board[..BUFFER_ROWS * self.width].iter().all(Option::is_none) {
+            self.board()
+        } else {
+            &self.board
+        };
+        for cell in hashed_cells {
+            match cell {
+                Some(Color(r, g, b)) => fnv.write(&[1, *r, *g, *b]),
+                None => fnv.write(&[0]),
+            }
+        }
+        let piece = &self.active_piece;
+        for n in [piece.id as u64, piece.rotation as u64, piece.x as u64, piece.y as u64] {
+            fnv.write_u64(n);
+        }
+        for n in [self.score, self.level as u64, self.total_lines_cleared as u64, self.combo as u64] {
+            fnv.write_u64(n);
+        }
+        fnv.write(&[self.back_to_back as u8, self.hold_used_this_turn as u8, self.is_game_over as u8]);
+        fnv.write_u64(self.held_piece_id.map_or(u64::MAX, |id| id as u64));
+        // Left out when unlimited so games without a limit hash as they always did.
+        if let Some(holds) = self.holds_remaining {
+            fnv.write_u64(holds as u64);
+        }
+        let (bag, bag_refills, randomizer) = self.randomizer.to_saved();
+        for n in [self.seed, bag_refills, self.garbage_bursts] {
+            fnv.write_u64(n);
+        }
+        // Lengths keep the queue and bag apart, so moving an id from one to the other changes the hash.
+        for ids in [self.next_queue.iter().copied().collect::<Vec<_>>(), bag] {
+            fnv.write_u64(ids.len() as u64);
+            for id in ids {
+                fnv.write_u64(id as u64);
+            }
+        }
+        // Only the other randomizers add to the hash, so bag games hash as they always did.
+        match randomizer {
+            Some(SavedRandomizer::Pure { rolls }) => fnv.write_u64(rolls),
+            Some(SavedRandomizer::History { rolls, history, dealt }) => {
+                fnv.write_u64(rolls);
+                fnv.write(&[dealt as u8]);
+                for id in history {
+                    fnv.write_u64(id as u64);
+                }
+            }
+            None => {}
+        }
+        fnv.0
+    }
+
+    /// A serializable snapshot of the game, as written to save files.
+    pub fn to_state(&self) -> SerializableGameState {
+        let (bag, bag_refills, randomizer) = self.randomizer.to_saved();
+        SerializableGameState {
+            board: self.board.clone(),
+            width: self.width,
+            height: self.height,
+            active_piece: self.active_piece.clone(),
+            next_piece_id: self.next_queue[0],
+            is_game_over: self.is_game_over,
+            game_over_reason: self.game_over_reason,
+            gravity_delay_ms: self.gravity_delay.as_millis() as u64,
+            level: self.level,
+            total_lines_cleared: self.total_lines_cleared,
+            score: self.score,
+            held_piece_id: self.held_piece_id,
+            hold_used_this_turn: self.hold_used_this_turn,
+            holds_remaining: self.holds_remaining,
+            bag,
+            combo: self.combo,
+            back_to_back: self.back_to_back,
+            garbage_rate: self.options.garbage_rate,
+            garbage_bursts: self.garbage_bursts,
+            pending_garbage: self
+                .pending_garbage
+                .iter()
+                .map(|&(lines, ready_at)| (lines, ready_at.saturating_sub(self.clock).as_millis() as u64))
+                .collect(),
+            play_time_ms: self.play_time.as_millis() as u64,
+            stats: self.stats.clone(),
+            seed: Some(self.seed),
+            bag_refills,
+            randomizer,
+            next_queue: self.next_queue.clone(),
+            hash: Some(self.state_hash()),
+        }
+    }
+
+    fn restore(&mut self, state: SerializableGameState) {
+        self.width = state.width;
+        self.height = state.height;
+        self.board = vec![None; self.width * self.stored_rows() - state.board.len()];
+        self.board.extend(state.board);
+        self.active_piece = state.active_piece;
+        self.next_queue = state.next_queue;
+        if self.next_queue.is_empty() {
+            self.next_queue.push_back(state.next_piece_id);
+        }
+        self.is_game_over = state.is_game_over;
+        self.game_over_reason = state.game_over_reason;
+        self.gravity_delay = Duration::from_millis(state.gravity_delay_ms);
+        self.total_lines_cleared = state.total_lines_cleared;
+        // Saves from before level progression don't record a level; derive it from the lines.
+        self.level = state.level.max(1 + state.total_lines_cleared / LINES_PER_LEVEL);
+        self.score = state.score;
+        self.held_piece_id = state.held_piece_id;
+        self.hold_used_this_turn = state.hold_used_this_turn;
+        self.holds_remaining = state.holds_remaining.or(self.hold_limit());
+        self.combo = state.combo;
+        self.back_to_back = state.back_to_back;
+        self.seed = state.seed.unwrap_or_else(rand::random);
+        self.rng = StdRng::seed_from_u64(self.seed);
+        let saved = (state.bag, state.bag_refills, state.randomizer);
+        self.randomizer = PieceRandomizer::from_saved(&self.options.pieces, saved, &mut self.rng);
+        self.garbage_rng = StdRng::seed_from_u64(self.seed ^ GARBAGE_SEED_SALT);
+        for _ in 0..state.garbage_bursts {
+            self.garbage_rng.gen_range(0..self.width);
+        }
+        self.garbage_bursts = state.garbage_bursts;
+        self.options.garbage_rate = state.garbage_rate;
+        self.last_garbage_time = self.clock;
+        let ready_at = |wait| self.clock + Duration::from_millis(wait);
+        self.pending_garbage = state.pending_garbage.iter().map(|&(lines, wait)| (lines, ready_at(wait))).collect();
+        self.play_time = Duration::from_millis(state.play_time_ms);
+        self.stats = state.stats;
+        self.stats.pieces.resize(self.options.pieces.len(), 0);
+        self.stats.blind &= self.next_hidden;
+        // The loaded stack shows briefly before an invisible game hides it again.
+        self.placed_at = vec![self.clock; self.board.len()];
+        self.lock_flash = None;
+        self.reveals_left = self.options.reveals;
+        self.revealed_at = None;
+        self.timer_running = false;
+        self.goal_reached = self.game_over_reason == Some(GameOverReason::GoalReached);
+        self.fill_next_queue();
+        self.paused = false; // Always unpause on load
+        self.lock_timer = None;
+        self.lock_resets = 0;
+        self.clearing = None;
+        self.initial_action = None;
+        self.input_queue.clear();
+        self.start_finesse();
+        self.last_gravity_time = self.clock; // Reset gravity timer
+    }
+}
+
+/// Takes the next piece id from the bag, refilling it with a shuffled set of all
+/// `piece_count` pieces once it runs empty. With the standard pieces every piece
+/// therefore appears once per seven spawns. `refills` counts the shuffles.
+fn draw_from_bag(bag: &mut Vec<usize>, piece_count: usize, rng: &mut StdRng, refills: &mut u64) -> usize {
+    if bag.is_empty() {
+        bag.extend(0..piece_count);
+        bag.shuffle(rng);
+        *refills += 1;
+    }
+    bag.pop().expect("bag was just refilled")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The row a piece the shape of the active one sits at once it drops into view: its
+    /// bottom row at the top of the board.
+    fn spawn_y(game: &Game) -> isize {
+        1 - game.active_piece.definition(game.pieces()).size(game.active_piece.rotation).1 as isize
+    }
+
+    /// Fills row `y` of the board except for the listed columns.
+    fn fill_row_except(game: &mut Game, y: usize, holes: &[usize]) {
+        for x in 0..game.width {
+            if !holes.contains(&x) {
+                let index = game.cell_index(x as isize, y as isize);
+                game.board[index] = Some(Color(255, 255, 255));
+            }
+        }
+    }
+
+    /// Puts a block in the top-left corner, so clearing the rows below it isn't a
+    /// perfect clear.
+    fn leave_a_block(game: &mut Game) {
+        let index = game.cell_index(0, 0);
+        game.board[index] = Some(Color(255, 255, 255));
+    }
+
+    #[test]
+    fn gravity_speeds_up_per_level_down_to_floor() {
+        let ms = |level, curve| gravity_for_level(level, curve).as_millis();
+        assert_eq!(ms(1, GravityCurve::Linear), 1000);
+        assert_eq!(ms(2, GravityCurve::Linear), 925);
+        assert_eq!(ms(12, GravityCurve::Linear), 175);
+        assert_eq!(ms(13, GravityCurve::Linear), 150);
+        assert_eq!(ms(100, GravityCurve::Linear), 150);
+
+        assert_eq!(ms(1, GravityCurve::ClassicNes), 799);
+        assert_eq!(ms(9, GravityCurve::ClassicNes), 133);
+        assert_eq!(ms(20, GravityCurve::ClassicNes), 33);
+        assert_eq!(ms(100, GravityCurve::ClassicNes), 16);
+
+        assert_eq!(ms(1, GravityCurve::Guideline), 1000);
+        assert_eq!(ms(2, GravityCurve::Guideline), 793);
+        assert_eq!(ms(5, GravityCurve::Guideline), 355);
+        assert_eq!(gravity_for_level(100, GravityCurve::Guideline), MIN_GRAVITY_DELAY);
+
+        for curve in GravityCurve::ALL {
+            assert!(gravity_for_level(5, curve) < gravity_for_level(4, curve));
+            assert_eq!(curve.name().parse(), Ok(curve));
+        }
+    }
+
+    #[test]
+    fn gravity_ticks_do_not_award_drop_points() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.update(Duration::from_secs(2));
+        assert_eq!(game.active_piece.y, spawn_y(&game) + 1);
+        assert_eq!(game.score, 0);
+    }
+
+    #[test]
+    fn collision_detects_walls_floor_and_blocks() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let mut piece = ActivePiece { id: 1, rotation: 0, x: -1, y: 0 };
+        assert!(game.check_collision(&piece));
+        piece.x = 8;
+        assert!(!game.check_collision(&piece));
+        piece.x = 9;
+        assert!(game.check_collision(&piece));
+        piece = ActivePiece { id: 1, rotation: 0, x: 4, y: 19 };
+        assert!(game.check_collision(&piece));
+
+        let index = game.cell_index(4, 18);
+        game.board[index] = Some(Color(1, 1, 1));
+        piece.y = 17;
+        assert!(game.check_collision(&piece));
+    }
+
+    /// A board from rows of `#` (filled) and `.` (empty), top row first.
+    fn board_from(rows: &[&str]) -> Vec<Option<Color>> {
+        rows.iter()
+            .flat_map(|row| row.chars().map(|c| (c == '#').then_some(Color(1, 2, 3))))
+            .collect()
+    }
+
+    #[test]
+    fn clearing_the_bottom_row_drops_everything_above_it() {
+        let board = board_from(&["...", "#..", ".#.", "###"]);
+        assert_eq!(clear_full_rows(&board, 3), (board_from(&["...", "...", "#..", ".#."]), 1));
+    }
+
+    #[test]
+    fn clearing_separate_rows_keeps_the_rows_between_them_in_order() {
+        let board = board_from(&["..#", "###", "#..", "###", ".#."]);
+        assert_eq!(clear_full_rows(&board, 3), (board_from(&["...", "...", "..#", "#..", ".#."]), 2));
+    }
+
+    #[test]
+    fn clearing_the_top_row_leaves_an_empty_row_in_its_place() {
+        let board = board_from(&["###", "#..", ".##"]);
+        assert_eq!(clear_full_rows(&board, 3), (board_from(&["...", "#..", ".##"]), 1));
+    }
+
+    #[test]
+    fn clearing_every_row_empties_the_board() {
+        let board = board_from(&["##", "##", "##"]);
+        assert_eq!(clear_full_rows(&board, 2), (board_from(&["..", "..", ".."]), 3));
+    }
+
+    #[test]
+    fn clearing_four_rows_at_once_is_a_tetris() {
+        let board = board_from(&["....", ".#..", "####", "####", "####", "####", "#.##"]);
+        let (cleared, count) = clear_full_rows(&board, 4);
+        assert_eq!((cleared, count), (board_from(&["....", "....", "....", "....", "....", ".#..", "#.##"]), 4));
+
+        let mut game = Game::new(4, 7, GameOptions::default());
+        game.board[4 * BUFFER_ROWS..].clone_from_slice(&board);
+        game.clear_lines(false);
+        assert_eq!(game.score(), 800);
+        assert_eq!(game.stats().clears, [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn full_rows_are_cleared_and_scored() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        fill_row_except(&mut game, 19, &[]);
+        fill_row_except(&mut game, 18, &[]);
+        fill_row_except(&mut game, 17, &[0]);
+        game.clear_lines(false);
+
+        assert_eq!(game.score, 300);
+        assert_eq!(game.total_lines_cleared, 2);
+        // The partial row fell to the bottom, keeping its hole.
+        assert!(game.board()[19 * 10].is_none());
+        assert!(game.board()[19 * 10 + 1..20 * 10].iter().all(Option::is_some));
+        assert!(game.board()[..19 * 10].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn score_breakdown_adds_up_to_the_score() {
+        let mut game = Game::new(10, 20, GameOptions { line_clear_delay: Duration::ZERO, ..GameOptions::default() });
+        game.soft_drop();
+        game.soft_drop();
+        game.hard_drop();
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+
+        let points = game.stats().points;
+        assert_eq!(points.soft_drop, 2);
+        assert!(points.hard_drop > 0);
+        assert_eq!(points.clears, 200);
+        assert_eq!(points.combos, 50);
+        assert_eq!(points.t_spins, 0);
+        assert_eq!(points.total(), game.score());
+    }
+
+    #[test]
+    fn column_heights_and_holes_are_measured_from_the_floor() {
+        let mut game = Game::new(4, 6, GameOptions::default());
+        assert_eq!(game.column_heights(), [0, 0, 0, 0]);
+        fill_row_except(&mut game, 5, &[1]);
+        fill_row_except(&mut game, 3, &[0, 3]);
+        assert_eq!(game.column_heights(), [1, 3, 3, 1]);
+        assert_eq!(game.holes(), [(1, 4), (2, 4), (1, 5)]);
+
+        // A block up in the buffer counts too, and covers the visible cells below it.
+        let index = game.cell_index(0, -2);
+        game.board[index] = Some(Color(255, 255, 255));
+        assert_eq!(game.column_heights()[0], 8);
+        assert_eq!(game.holes().iter().filter(|&&(x, _)| x == 0).count(), 5);
+    }
+
+    #[test]
+    fn sticky_gravity_drops_loose_blocks_into_a_chain_clear() {
+        let sticky = GameOptions { gravity_mode: GravityMode::Sticky, ..GameOptions::default() };
+        for (options, lines) in [(GameOptions::default(), 1), (sticky, 2)] {
+            let mut game = Game::new(4, 6, options);
+            fill_row_except(&mut game, 5, &[2]);
+            fill_row_except(&mut game, 4, &[]);
+            fill_row_except(&mut game, 3, &[0, 1, 3]);
+            game.clear_lines(false);
+            assert_eq!(game.total_lines_cleared, lines);
+            if lines == 1 {
+                // Naive gravity leaves the block hanging over the hole.
+                assert!(game.board()[4 * 4 + 2].is_some() && game.board()[5 * 4 + 2].is_none());
+            } else {
+                // It fell into the hole, completing the bottom row as a cascade worth double
+                // and leaving the board empty.
+                assert!(game.board().iter().all(Option::is_none));
+                assert_eq!(game.stats().points.cascades, 200);
+                assert_eq!(game.score(), 300 + 800);
+            }
+        }
+    }
+
+    #[test]
+    fn clearing_every_block_earns_a_perfect_clear_bonus() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.clear_lines(false);
+        assert_eq!(game.stats().perfect_clears, 0);
+
+        fill_row_except(&mut game, 18, &[]);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!((game.score(), game.stats().points.perfect_clears), (300 + 1200, 1200));
+        assert_eq!((game.stats().perfect_clears, game.status_message()), (1, Some("PERFECT CLEAR!")));
+
+        // The board is still empty, but nothing was cleared this time.
+        game.clear_lines(false);
+        assert_eq!(game.stats().perfect_clears, 1);
+    }
+
+    #[test]
+    fn settling_lands_clusters_on_each_other() {
+        let x = Some(Color(1, 1, 1));
+        // A floating L above a floating block that sits under its arm.
+        let board = vec![
+            x,    None, None,
+            x,    x,    None,
+            None, None, None,
+            None, x,    None,
+            None, None, None,
+        ];
+        let destination = settle_clusters(&board, 3).unwrap();
+        // The block drops to the floor and the L falls until its arm rests on it.
+        assert_eq!(destination[10], 13);
+        assert_eq!((destination[0], destination[3], destination[4]), (6, 9, 10));
+        assert!(settle_clusters(&[None, x, None, x], 2).is_none());
+    }
+
+    #[test]
+    fn consecutive_clears_build_a_combo() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        leave_a_block(&mut game);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!((game.combo, game.score), (0, 100));
+
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!((game.combo, game.score), (1, 100 + 100 + 50));
+
+        game.clear_lines(false);
+        assert_eq!((game.combo, game.score), (-1, 250));
+        assert_eq!(game.stats().max_combo, 1);
+    }
+
+    #[test]
+    fn peeking_upcoming_pieces_does_not_change_what_spawns() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(7), ..GameOptions::default() });
+        let ahead = game.upcoming(20);
+        assert_eq!(ahead.len(), 20);
+        assert_eq!(ahead[..game.next_queue().len()], game.next_queue().iter().copied().collect::<Vec<_>>()[..]);
+        assert_eq!(game.upcoming(3), ahead[..3]);
+        assert_eq!(game.upcoming(20), ahead);
+        assert!(game.upcoming(0).is_empty());
+        assert_eq!(spawn_sequence(&mut game, 20), ahead);
+    }
+
+    #[test]
+    fn history_randomizer_never_opens_with_s_z_or_o() {
+        let pieces = standard_pieces();
+        let id = |name: &str| pieces.iter().position(|piece| piece.name == name).unwrap();
+        let unwanted = [id("S"), id("Z"), id("O")];
+        for seed in 0..50 {
+            let randomizer = RandomizerKind::History;
+            let game = Game::new(10, 20, GameOptions { seed: Some(seed), randomizer, ..GameOptions::default() });
+            assert!(!unwanted.contains(&game.active_piece.id), "seed {} opened with {}", seed, game.active_piece.id);
+        }
+        assert_eq!("history".parse(), Ok(RandomizerKind::History));
+        assert!("tgm".parse::<RandomizerKind>().is_err());
+    }
+
+    #[test]
+    fn saved_randomizers_deal_the_same_pieces_after_loading() {
+        for randomizer in RandomizerKind::ALL {
+            let mut game = Game::new(10, 20, GameOptions { seed: Some(3), randomizer, ..GameOptions::default() });
+            spawn_sequence(&mut game, 9);
+            let state = game.to_state();
+            let ahead = game.upcoming(20);
+            let json = serde_json::to_string(&state).unwrap();
+            let mut loaded = Game::new(10, 20, GameOptions { randomizer, ..GameOptions::default() });
+            loaded.load_state(serde_json::from_str(&json).unwrap()).unwrap();
+            assert_eq!(loaded.state_hash(), game.state_hash(), "{}", randomizer.name());
+            assert_eq!(spawn_sequence(&mut loaded, 20), ahead, "{}", randomizer.name());
+        }
+
+        let game = Game::new(10, 20, GameOptions { randomizer: RandomizerKind::History, ..GameOptions::default() });
+        let mut state = game.to_state();
+        let Some(SavedRandomizer::History { history, .. }) = &mut state.randomizer else { unreachable!() };
+        history.resize(HISTORY_LENGTH + 1, 0);
+        let mut loaded = Game::new(10, 20, GameOptions::default());
+        let error = format!("randomizer history has {} pieces, at most {}", HISTORY_LENGTH + 1, HISTORY_LENGTH);
+        assert_eq!(loaded.load_state(state), Err(error));
+    }
+
+    #[test]
+    fn leftward_gravity_clears_full_columns_on_screen() {
+        let dir = GravityDir::Left;
+        let mut game = Game::new(10, 20, GameOptions { gravity_dir: dir, ..GameOptions::default() });
+        assert_eq!(dir.screen_size(10, 20), (20, 10));
+        let on_screen = |game: &Game| -> HashSet<(isize, isize)> {
+            let filled = game.board().iter().enumerate().filter(|(_, cell)| cell.is_some());
+            filled.map(|(i, _)| dir.to_screen(((i % 10) as isize, (i / 10) as isize), 10, 20)).collect()
+        };
+        // The bottom row is the leftmost screen column and the one above it the next.
+        fill_row_except(&mut game, 19, &[]);
+        fill_row_except(&mut game, 18, &[3]);
+        let full: HashSet<_> = (0..10).map(|y| (0, y)).collect();
+        assert!(on_screen(&game).is_superset(&full));
+        assert!(!on_screen(&game).contains(&(1, 3)));
+
+        game.clear_lines(false);
+        assert_eq!(game.lines_cleared(), 1);
+        // The column with a gap falls left into the cleared one's place.
+        let expected: HashSet<_> = (0..10).filter(|&y| y != 3).map(|y| (0, y)).collect();
+        assert_eq!(on_screen(&game), expected);
+
+        for dir in GravityDir::ALL {
+            for cell in [(0, 0), (9, 0), (3, 17), (9, 19)] {
+                assert_eq!(dir.from_screen(dir.to_screen(cell, 10, 20), 10, 20), cell);
+            }
+        }
+    }
+
+    #[test]
+    fn line_clear_points_follow_the_scoring_config() {
+        let clear = |scoring, rows: usize| {
+            let mut game = Game::new(10, 20, GameOptions { scoring, ..GameOptions::default() });
+            game.level = 3;
+            leave_a_block(&mut game);
+            for y in 20 - rows..20 {
+                fill_row_except(&mut game, y, &[]);
+            }
+            game.clear_lines(false);
+            game.score()
+        };
+        let scores = |scoring| [1, 2, 3, 4].map(|rows| clear(scoring, rows));
+        assert_eq!(scores(ScoringConfig::default()), [100, 300, 500, 800]);
+        let nes = ScoringConfig { lines: [40, 100, 300, 1200], level_multiplier: true };
+        assert_eq!(scores(nes), [120, 300, 900, 3600]);
+        assert_eq!(nes.line_clear_points(0, 3), 0);
+
+        // A back-to-back bonus on a huge table stops at the most one clear can score.
+        let huge = ScoringConfig { lines: [100, 300, 500, 3_000_000_000], level_multiplier: false };
+        let mut game = Game::new(10, 20, GameOptions { scoring: huge, ..GameOptions::default() });
+        game.back_to_back = true;
+        leave_a_block(&mut game);
+        for y in 16..20 {
+            fill_row_except(&mut game, y, &[]);
+        }
+        game.clear_lines(false);
+        assert_eq!(game.stats().points.clears, u64::from(u32::MAX));
+    }
+
+    #[test]
+    fn score_goes_past_u32_and_stops_at_u64_max() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.score = u64::from(u32::MAX) - 100;
+        game.back_to_back = true;
+        // A back-to-back tetris perfect clear: 1200 for the tetris and 2000 for the clear.
+        for y in 16..20 {
+            fill_row_except(&mut game, y, &[]);
+        }
+        game.clear_lines(false);
+        assert_eq!(game.score(), u64::from(u32::MAX) - 100 + 1200 + 2000);
+
+        game.score = u64::MAX - 1;
+        game.stats.points.clears = u64::MAX - 1;
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!((game.score(), game.stats().points.clears), (u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn consecutive_tetrises_earn_back_to_back_bonus() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        leave_a_block(&mut game);
+        let tetris = |game: &mut Game| {
+            for y in 16..20 {
+                fill_row_except(game, y, &[]);
+            }
+            game.clear_lines(false);
+        };
+        tetris(&mut game);
+        assert!(game.back_to_back);
+        assert_eq!(game.score, 800);
+
+        // An empty placement keeps the chain; combo bonuses are left out of the sums.
+        game.clear_lines(false);
+        tetris(&mut game);
+        assert_eq!(game.score, 800 + 1200);
+
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert!(!game.back_to_back);
+    }
+
+    #[test]
+    fn queued_garbage_waits_for_its_delay_then_the_next_lock() {
+        let mut game = seeded(3);
+        game.options.garbage_delay = Duration::from_millis(500);
+        game.queue_garbage(2);
+        game.update(Duration::from_millis(100));
+        game.queue_garbage(1);
+        assert_eq!(game.pending_garbage(), (0, 3));
+        // Locking before the delay is over leaves it queued.
+        game.hard_drop();
+        assert!(!game.board().contains(&Some(GARBAGE_COLOR)));
+        assert_eq!(game.pending_garbage(), (0, 3));
+
+        game.update(Duration::from_millis(450));
+        assert_eq!(game.pending_garbage(), (2, 1));
+        let mut loaded = Game::new(10, 20, GameOptions::default());
+        loaded.restore(game.to_state());
+        assert_eq!(loaded.pending_garbage(), (2, 1));
+
+        game.hard_drop();
+        assert_eq!(game.pending_garbage(), (0, 1));
+        let garbage_rows = game.board().chunks(10).filter(|row| row.contains(&Some(GARBAGE_COLOR))).count();
+        assert_eq!(garbage_rows, 2);
+    }
+
+    #[test]
+    fn garbage_pushes_the_board_up_with_one_hole_per_burst() {
+        let mut game = seeded(3);
+        fill_row_except(&mut game, 19, &[0]);
+        game.add_garbage_lines(2);
+
+        // The old bottom row moved up two rows.
+        assert!(game.board()[17 * 10].is_none());
+        assert!(game.board()[17 * 10 + 1..18 * 10].iter().all(Option::is_some));
+        let hole = (0..10).find(|&x| game.board()[18 * 10 + x].is_none()).unwrap();
+        for y in 18..20 {
+            let holes: Vec<usize> = (0..10).filter(|&x| game.board()[y * 10 + x].is_none()).collect();
+            assert_eq!(holes, [hole]);
+        }
+        assert!(!game.is_game_over);
+    }
+
+    #[test]
+    fn garbage_pushing_blocks_off_the_top_ends_the_game() {
+        let mut game = seeded(3);
+        let top = game.cell_index(5, -(BUFFER_ROWS as isize));
+        game.board[top] = Some(Color(1, 1, 1));
+        game.add_garbage_lines(1);
+        assert!(game.is_game_over);
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::TopOut));
+    }
+
+    #[test]
+    fn pieces_locking_wholly_above_the_board_lock_out() {
+        let mut game = seeded(3);
+        game.active_piece.y = -4;
+        game.lock_piece();
+        assert!(game.is_game_over());
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::LockOut));
+        assert!(game.board().iter().all(Option::is_none));
+
+        // Poking up into the buffer is fine as long as part of the piece is on the board.
+        let mut game = seeded(3);
+        game.active_piece.x = 0;
+        game.active_piece.y = -1;
+        game.lock_piece();
+        assert!(!game.is_game_over());
+    }
+
+    #[test]
+    fn spawning_into_the_stack_blocks_out() {
+        let mut game = seeded(3);
+        let spawn_rows_end = game.cell_index(0, 0);
+        game.board[..spawn_rows_end].fill(Some(Color(1, 1, 1)));
+        game.spawn_new_piece();
+        assert!(game.is_game_over());
+        assert_eq!(game.game_over_reason(), Some(GameOverReason::BlockOut));
+
+        let state = game.to_state();
+        let mut restored = seeded(4);
+        restored.restore(state);
+        assert_eq!(restored.game_over_reason(), Some(GameOverReason::BlockOut));
+    }
+
+    #[test]
+    fn zen_games_empty_the_board_instead_of_ending_and_keep_their_speed() {
+        let options =
+            GameOptions { mode: GameMode::Zen, seed: Some(3), line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        for _ in 0..200 {
+            game.hard_drop();
+        }
+        assert!(!game.is_game_over());
+        assert!(game.max_stack_height() < 20);
+
+        fill_row_except(&mut game, 19, &[]);
+        game.total_lines_cleared = 50;
+        game.clear_lines(false);
+        assert_eq!(game.level(), 1);
+        assert_eq!(game.gravity_delay, gravity_for_level(1, GravityCurve::Guideline));
+    }
+
+    #[test]
+    fn sprint_times_play_from_first_move_and_ends_at_goal() {
+        let options = GameOptions { mode: GameMode::Sprint { lines: 2 }, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        game.update(Duration::from_secs(3));
+        assert_eq!(game.play_time(), Duration::ZERO);
+
+        game.try_rotate(RotationDir::Clockwise);
+        game.update(Duration::from_millis(500));
+        game.toggle_pause();
+        game.update(Duration::from_secs(5));
+        game.toggle_pause();
+        assert_eq!(game.play_time(), Duration::from_millis(500));
+
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert!(!game.is_game_over);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert!(game.is_game_over && game.goal_reached());
+    }
+
+    #[test]
+    fn start_level_holds_until_lines_catch_up() {
+        let options = GameOptions { start_level: 3, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        assert_eq!(game.level(), 3);
+        assert_eq!(game.gravity_delay, gravity_for_level(3, GravityCurve::Guideline));
+        assert_eq!(game.stats().start_level, 3);
+
+        game.total_lines_cleared = 19;
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!(game.level(), 3);
+        game.total_lines_cleared = 29;
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert_eq!(game.level(), 4);
+        assert_eq!(game.gravity_delay, gravity_for_level(4, GravityCurve::Guideline));
+    }
+
+    #[test]
+    fn gravity_option_and_no_speedup_set_the_pace() {
+        let level_up = |options: GameOptions| {
+            let mut game = Game::new(10, 20, options);
+            let start = game.gravity_delay;
+            game.total_lines_cleared = 9;
+            fill_row_except(&mut game, 19, &[]);
+            game.clear_lines(false);
+            assert_eq!(game.level(), 2);
+            (start, game.gravity_delay)
+        };
+        let ms = Duration::from_millis;
+
+        let fixed = GameOptions { speedup: false, ..GameOptions::default() };
+        assert_eq!(level_up(fixed), (ms(1000), ms(1000)));
+        let fixed = GameOptions { speedup: false, gravity: Some(ms(300)), ..GameOptions::default() };
+        assert_eq!(level_up(fixed), (ms(300), ms(300)));
+        // The curve is scaled to start at the given speed: 793ms at level 2 becomes half.
+        let scaled = GameOptions { gravity: Some(ms(500)), ..GameOptions::default() };
+        assert_eq!(level_up(scaled).1.as_millis(), 396);
+        let tiny = GameOptions { gravity: Some(ms(1)), ..GameOptions::default() };
+        assert_eq!(level_up(tiny), (MIN_GRAVITY, MIN_GRAVITY));
+    }
+
+    #[test]
+    fn invisible_cells_fade_and_reveals_run_out() {
+        let options = GameOptions { invisible: true, reveals: 1, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        game.hard_drop();
+        let filled: Vec<usize> = (0..game.board().len()).filter(|&i| game.board()[i].is_some()).collect();
+        assert!(filled.iter().all(|&i| game.cell_visible(i)));
+
+        game.update(Duration::from_millis(1000));
+        assert!(filled.iter().all(|&i| !game.cell_visible(i)));
+
+        game.reveal_board();
+        assert_eq!(game.reveals_left(), Some(0));
+        assert!(filled.iter().all(|&i| game.cell_visible(i)));
+        game.update(REVEAL_TIME);
+        assert!(filled.iter().all(|&i| !game.cell_visible(i)));
+        game.reveal_board();
+        assert!(filled.iter().all(|&i| !game.cell_visible(i)));
+    }
+
+    #[test]
+    fn marathon_is_won_at_its_line_goal_unless_endless() {
+        let options = GameOptions { mode: GameMode::Marathon { lines: 1 }, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert!(game.is_game_over && game.goal_reached());
+
+        let options = GameOptions { mode: GameMode::Marathon { lines: 0 }, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        assert_eq!(game.line_goal(), None);
+        fill_row_except(&mut game, 19, &[]);
+        game.clear_lines(false);
+        assert!(!game.is_game_over);
+    }
+
+    #[test]
+    fn ultra_counts_down_from_first_move_and_ends_at_zero() {
+        let options = GameOptions { mode: GameMode::Ultra { time_limit: Duration::from_secs(2) }, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        game.update(Duration::from_secs(3));
+        assert_eq!(game.time_remaining(), Some(Duration::from_secs(2)));
+
+        game.try_rotate(RotationDir::Clockwise);
+        game.update(Duration::from_millis(1500));
+        game.toggle_pause();
+        game.update(Duration::from_secs(5));
+        game.toggle_pause();
+        assert_eq!(game.time_remaining(), Some(Duration::from_millis(500)));
+        assert!(!game.is_game_over);
+
+        game.update(Duration::from_millis(600));
+        assert_eq!(game.time_remaining(), Some(Duration::ZERO));
+        assert!(game.is_game_over && game.goal_reached());
+    }
+
+    #[test]
+    fn held_soft_drop_speeds_up_gravity_and_scores_each_row() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let start_y = game.active_piece().y;
+        game.press_soft_drop();
+        // Level 1 gravity is one row per second, so one row every 50ms at 20x.
+        for _ in 0..100 {
+            game.update(Duration::from_millis(5));
+        }
+        assert_eq!(game.active_piece().y, start_y + 11);
+        assert_eq!(game.score(), 11);
+
+        game.release_soft_drop();
+        game.update(Duration::from_millis(500));
+        assert_eq!(game.active_piece().y, start_y + 11);
+    }
+
+    #[test]
+    fn full_rows_flash_before_they_clear() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        leave_a_block(&mut game);
+        fill_row_except(&mut game, 19, &[0, 1, 2, 3]);
+        game.active_piece = ActivePiece { id: 0, rotation: 0, x: 0, y: 19 };
+        game.lock_piece();
+
+        let (rows, _) = game.clearing_rows().unwrap();
+        assert_eq!(rows, [19]);
+        assert_eq!(game.score, 0);
+        // Input is ignored while the rows flash.
+        assert!(!game.try_move(0, 1));
+
+        game.update(game.options.line_clear_delay);
+        assert!(game.clearing_rows().is_none());
+        assert_eq!((game.score, game.total_lines_cleared), (100, 1));
+        assert_eq!(game.active_piece.y, spawn_y(&game));
+    }
+
+    #[test]
+    fn locked_cells_flash_briefly_and_follow_cleared_rows_down() {
+        let options = GameOptions { line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        fill_row_except(&mut game, 19, &[0, 1]);
+        game.active_piece = ActivePiece { id: 1, rotation: 0, x: 0, y: 18 };
+        game.lock_piece();
+
+        // The O's bottom half cleared with row 19; its top half dropped into that row.
+        let mut cells = game.lock_flash().to_vec();
+        cells.sort();
+        assert_eq!(cells, vec![190, 191]);
+        game.update(LOCK_FLASH_TIME);
+        assert!(game.lock_flash().is_empty());
+
+        game.hard_drop();
+        assert_eq!(game.lock_flash().len(), 4);
+        let state = game.to_state();
+        game.restore(state);
+        assert!(game.lock_flash().is_empty());
+    }
+
+    #[test]
+    fn inputs_during_a_clear_wait_for_the_next_piece() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(4), ..GameOptions::default() });
+        fill_row_except(&mut game, 19, &[0, 1, 2, 3]);
+        game.active_piece = ActivePiece { id: 0, rotation: 0, x: 0, y: 19 };
+        game.lock_piece();
+        game.queue_input(PlayerInput::MoveRight);
+        game.queue_input(PlayerInput::MoveRight);
+        game.queue_input(PlayerInput::HardDrop);
+        assert!(game.clearing_rows().is_some());
+
+        let next = ActivePiece::new(game.next_queue()[0], game.pieces(), 10);
+        let moved = ActivePiece { x: next.x + 2, ..next };
+        game.update(game.options.line_clear_delay);
+        // The cleared board was empty, so all that's left is the new piece, dropped two
+        // columns right of where it spawned before a third was dealt.
+        assert_eq!(game.stats().total_pieces(), 3);
+        let mut columns: Vec<usize> = (0..200).filter(|&i| game.board()[i].is_some()).map(|i| i % 10).collect();
+        let mut expected: Vec<usize> = moved.blocks(game.pieces()).map(|(x, _)| x as usize).collect();
+        columns.sort();
+        expected.sort();
+        assert_eq!(columns, expected);
+        assert!(game.input_queue.is_empty());
+    }
+
+    #[test]
+    fn pause_menu_wraps_and_resume_keeps_timers_where_they_were() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.update(Duration::from_millis(600));
+        game.toggle_pause();
+        assert_eq!(game.pause_selection(), PauseSelection::Resume);
+        game.move_pause_selection(-1);
+        assert_eq!(game.pause_selection(), PauseSelection::QuitToMenu);
+        game.move_pause_selection(2);
+        assert_eq!(game.pause_selection(), PauseSelection::Restart);
+
+        // A long pause must not turn into an immediate gravity step, nor lose the
+        // 600ms that had already passed toward the next one.
+        let spawn_y = spawn_y(&game);
+        game.update(Duration::from_secs(10));
+        assert_eq!(game.active_piece.y, spawn_y);
+        game.toggle_pause();
+        game.update(Duration::from_millis(399));
+        assert_eq!(game.active_piece.y, spawn_y);
+        game.update(Duration::from_millis(1));
+        assert_eq!(game.active_piece.y, spawn_y + 1);
+    }
+
+    #[test]
+    fn paused_time_never_advances_the_lock_delay() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.active_piece.y = game.ghost_piece().y;
+        game.update(game.gravity_delay);
+        assert!(game.lock_timer.is_some());
+        game.update(Duration::from_millis(300));
+
+        game.toggle_pause();
+        game.update(Duration::from_secs(10));
+        game.toggle_pause();
+        game.update(Duration::from_millis(199));
+        assert!(game.lock_timer.is_some());
+        game.update(Duration::from_millis(1));
+        assert!(game.lock_timer.is_none());
+        assert_eq!(game.stats().total_pieces(), 2);
+    }
+
+    #[test]
+    fn held_shift_repeats_after_das_until_released() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let start_x = game.active_piece.x;
+        game.press_shift(-1);
+        assert_eq!(game.active_piece.x, start_x - 1);
+
+        // Still charging DAS: no extra movement yet.
+        game.update(game.options.das - Duration::from_millis(1));
+        assert_eq!(game.active_piece.x, start_x - 1);
+
+        // DAS charged and another ARR period has passed.
+        game.update(game.options.arr + Duration::from_millis(1));
+        assert_eq!(game.active_piece.x, start_x - 3);
+
+        game.release_shift(-1);
+        assert!(game.held_shift.is_none());
+    }
+
+    #[test]
+    fn held_shift_keeps_its_charge_into_the_next_piece_only_with_das_carry() {
+        for das_carry in [false, true] {
+            let options =
+                GameOptions { das_carry, arr: Duration::ZERO, line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+            let mut game = Game::new(10, 20, options);
+            game.press_shift(-1);
+            game.update(game.options.das);
+            assert_eq!(game.active_piece.blocks(&game.options.pieces).map(|(x, _)| x).min(), Some(0));
+
+            game.hard_drop();
+            let spawn_x = ActivePiece::new(game.active_piece.id, &game.options.pieces, 10).x;
+            if das_carry {
+                // Charged: the new piece slams to the wall as it appears.
+                assert_eq!(game.active_piece.blocks(&game.options.pieces).map(|(x, _)| x).min(), Some(0));
+            } else {
+                assert_eq!(game.active_piece.x, spawn_x);
+                game.update(game.options.das - Duration::from_millis(1));
+                assert_eq!(game.active_piece.x, spawn_x);
+                game.update(Duration::from_millis(1));
+                assert_eq!(game.active_piece.blocks(&game.options.pieces).map(|(x, _)| x).min(), Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn setup_fills_the_bottom_rows_and_deals_its_pieces_first() {
+        let pieces = standard_pieces();
+        let text = "board = \"\"\"\n  ....\n  ##.t\n\"\"\"\npieces = [\"T\", \"i\"]\n";
+        let setup = Setup::parse(text, &pieces, 4, 6).unwrap();
+        let t_color = pieces[setup.pieces[0]].color;
+        assert_eq!(setup.rows[1], [Some(GARBAGE_COLOR), Some(GARBAGE_COLOR), None, Some(t_color)]);
+
+        let plain = Game::new(4, 6, GameOptions { seed: Some(3), ..GameOptions::default() });
+        let options = GameOptions { seed: Some(3), setup: Some(Arc::new(setup.clone())), ..GameOptions::default() };
+        let game = Game::new(4, 6, options);
+        assert_eq!(game.board()[20..], [Some(GARBAGE_COLOR), Some(GARBAGE_COLOR), None, Some(t_color)]);
+        assert!(game.board()[..20].iter().all(Option::is_none));
+        assert_eq!(game.active_piece().id, setup.pieces[0]);
+        let mut dealt: Vec<usize> = vec![setup.pieces[1], plain.active_piece().id];
+        dealt.extend(plain.next_queue());
+        assert!(game.next_queue().iter().eq(dealt.iter()));
+        assert_eq!(game.stats().pieces[setup.pieces[0]], 1);
+
+        for (text, error) in [
+            ("board = \"...\"", "row 1 has 3 cells, but the board is 4 wide"),
+            ("board = \"####\"", "row 1 is full"),
+            ("board = \"..?.\"", "row 1: unknown cell `?`"),
+            ("board = \"....\"\npieces = [\"Q\"]", "unknown piece `Q`"),
+            ("board = \"\"\"\n.\n.\n.\n.\n.\n.\n.\n\"\"\"", "board has 7 rows, but the board is only 6 high"),
+        ] {
+            assert_eq!(Setup::parse(text, &pieces, 4, 6), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn edit_mode_stops_gravity_and_exports_the_board_it_builds() {
+        let mut scored = Game::new(10, 20, GameOptions::default());
+        scored.toggle_edit_mode();
+        assert!(!scored.is_editing());
+
+        let options = GameOptions { practice: true, line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        game.toggle_edit_mode();
+        let y = game.active_piece().y;
+        game.update(Duration::from_secs(10));
+        assert_eq!(game.active_piece().y, y);
+        game.soft_drop();
+        assert_eq!((game.active_piece().y, game.score()), (y + 1, 0));
+
+        // Hard drop locks the piece in mid-air, well above the floor.
+        for _ in 0..8 {
+            game.soft_drop();
+        }
+        let piece = game.active_piece().id;
+        let top = game.active_piece().blocks(game.pieces()).map(|(_, y)| y).min().unwrap();
+        game.hard_drop();
+        assert_eq!(game.max_stack_height(), 20 - top as usize);
+        let setup = Setup::from_game(&game);
+        assert_eq!(setup.rows.len(), game.max_stack_height());
+        assert_eq!(setup.pieces[0], game.active_piece().id);
+        let text = setup.to_toml(game.pieces());
+        assert!(text.contains(&game.pieces()[piece].name));
+        assert_eq!(Setup::parse(&text, game.pieces(), 10, 20), Ok(setup));
+
+        // A row the placed piece completes goes away without scoring or leveling up.
+        for _ in 0..4 {
+            game.soft_drop();
+        }
+        let blocks: Vec<_> = game.active_piece().blocks(game.pieces()).collect();
+        let bottom = blocks.iter().map(|&(_, y)| y).max().unwrap();
+        let holes: Vec<usize> = blocks.iter().filter(|&&(_, y)| y == bottom).map(|&(x, _)| x as usize).collect();
+        fill_row_except(&mut game, bottom as usize, &holes);
+        let level = game.level();
+        game.hard_drop();
+        assert!(game.board()[bottom as usize * 10..][..10].iter().any(Option::is_none));
+        assert_eq!((game.score(), game.level(), game.lines_cleared()), (0, level, 0));
+
+        let y = game.active_piece().y;
+        game.toggle_edit_mode();
+        game.update(Duration::from_secs(10));
+        assert_eq!(game.active_piece().y, y + 1);
+    }
+
+    #[test]
+    fn extra_moves_and_turns_count_as_finesse_faults() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(5), ..GameOptions::default() });
+        let mut place = |inputs: &[PlayerInput]| {
+            for &input in inputs {
+                game.queue_input(input);
+            }
+            game.queue_input(PlayerInput::HardDrop);
+            game.stats().finesse_faults
+        };
+        assert_eq!(place(&[PlayerInput::MoveLeft]), 0);
+        assert_eq!(place(&[PlayerInput::MoveLeft, PlayerInput::MoveRight, PlayerInput::MoveLeft]), 1);
+        // Walking to the wall one cell at a time takes more presses than holding the key.
+        assert_eq!(place(&[PlayerInput::MoveRight; 5]), 2);
+        assert_eq!(place(&[PlayerInput::Rotate(RotationDir::Clockwise); 4]), 3);
+        assert_eq!(place(&[]), 3);
+    }
+
+    #[test]
+    fn held_rotate_repeats_slowly_only_while_airborne() {
+        let interval = Duration::from_millis(300);
+        // Slow gravity, so the piece stays airborne until it is pushed to the floor.
+        let options = GameOptions {
+            rotate_repeat: Some(interval),
+            gravity: Some(Duration::from_secs(60)),
+            record_events: true,
+            ..GameOptions::default()
+        };
+        let mut game = Game::new(10, 20, options);
+        let turns = |game: &mut Game| {
+            game.take_events().iter().filter(|event| matches!(event, GameEvent::Rotated { .. })).count()
+        };
+
+        // A tap turns exactly once.
+        game.press_rotate(RotationDir::Clockwise);
+        game.release_rotate(RotationDir::Clockwise);
+        game.update(interval * 3);
+        assert_eq!(turns(&mut game), 1);
+
+        game.press_rotate(RotationDir::Clockwise);
+        game.update(interval - Duration::from_millis(1));
+        assert_eq!(turns(&mut game), 1);
+        game.update(Duration::from_millis(1));
+        assert_eq!(turns(&mut game), 1);
+
+        // Resting on the floor, the held key no longer turns the piece.
+        while game.try_move(0, 1) {}
+        game.update(interval);
+        assert_eq!(turns(&mut game), 0);
+        game.release_rotate(RotationDir::Clockwise);
+        assert!(game.held_rotation.is_none());
+    }
+
+    #[test]
+    fn shift_without_auto_shift_moves_once() {
+        let options = GameOptions { auto_shift: false, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        let start_x = game.active_piece.x;
+        game.press_shift(1);
+        assert_eq!(game.active_piece.x, start_x + 1);
+        assert!(game.held_shift.is_none());
+    }
+
+    #[test]
+    fn save_slots_outside_range_are_rejected() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        assert_eq!(game.save_game(0).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(game.load_game(SAVE_SLOTS + 1).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(save_file(3).unwrap(), "tetris_save_3.json");
+    }
+
+    #[test]
+    fn pieces_spawn_in_the_middle_columns_and_can_turn_at_the_top() {
+        let columns = |width: usize, id: usize| -> Vec<isize> {
+            let pieces = standard_pieces();
+            let mut xs: Vec<isize> = ActivePiece::new(id, &pieces, width).blocks(&pieces).map(|(x, _)| x).collect();
+            xs.sort_unstable();
+            xs.dedup();
+            xs
+        };
+        assert_eq!(columns(10, I_PIECE), [3, 4, 5, 6]);
+        assert_eq!(columns(10, 1), [4, 5]);
+        assert_eq!(columns(10, T_PIECE), [3, 4, 5]);
+        // The O-block stays in the middle of the I-block's columns on odd widths too.
+        for width in 4..=13 {
+            assert_eq!(columns(width, 1)[0], columns(width, I_PIECE)[1]);
+        }
+
+        for id in 0..standard_pieces().len() {
+            for width in [4, 9, 10] {
+                let mut game = Game::new(width, 20, GameOptions::default());
+                game.active_piece = ActivePiece::new(id, game.pieces(), width);
+                assert!(game.active_piece().blocks(game.pieces()).all(|(_, y)| y < 0));
+                assert!(!game.check_collision(game.active_piece()));
+                assert!(game.try_rotate(RotationDir::Clockwise));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_saves_are_rejected_without_touching_the_game() {
+        let dir = std::env::temp_dir().join(format!("mad_tris_bad_save_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut game = Game::new(10, 20, GameOptions { save_dir: dir.clone(), ..GameOptions::default() });
+        game.score = 1234;
+
+        let mut state = game.to_state();
+        state.board.truncate(50);
+        fs::write(dir.join(save_file(1).unwrap()), serde_json::to_string(&state).unwrap()).unwrap();
+        let mut state = game.to_state();
+        state.next_queue[0] = 9;
+        fs::write(dir.join(save_file(2).unwrap()), serde_json::to_string(&state).unwrap()).unwrap();
+        fs::write(dir.join(save_file(3).unwrap()), "{ not json").unwrap();
+
+        let mut loaded = Game::new(10, 20, GameOptions { save_dir: dir.clone(), ..GameOptions::default() });
+        for slot in 1..=3 {
+            let err = loaded.load_game(slot).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(loaded.score(), 0);
+        }
+        assert!(loaded.load_game(1).unwrap_err().to_string().contains("board has 50 cells, expected 250"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut state = game.to_state();
+        (state.width, state.height) = (usize::MAX, usize::MAX);
+        assert_eq!(loaded.load_state(state), Err(format!("board is {}x{}", usize::MAX, usize::MAX)));
+    }
+
+    fn seeded(seed: u64) -> Game {
+        Game::new(10, 20, GameOptions { seed: Some(seed), ..GameOptions::default() })
+    }
+
+    #[test]
+    fn next_change_is_the_nearest_timer() {
+        let mut game = seeded(4);
+        assert_eq!(game.time_until_next_change(), Some(Duration::from_secs(1)));
+        game.update(Duration::from_millis(300));
+        assert_eq!(game.time_until_next_change(), Some(Duration::from_millis(700)));
+        game.set_status_message("hi".to_string());
+        game.toggle_pause();
+        assert_eq!(game.time_until_next_change(), Some(STATUS_TIME));
+        game.update(STATUS_TIME * 2);
+        assert_eq!(game.time_until_next_change(), None);
+    }
+
+    #[test]
+    fn equal_games_hash_equally_and_saves_check_their_hash() {
+        let (mut a, mut b) = (seeded(21), seeded(21));
+        assert_eq!(a.state_hash(), b.state_hash());
+        for game in [&mut a, &mut b] {
+            game.try_shift(1);
+            game.hard_drop();
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+        b.try_shift(-1);
+        assert_ne!(a.state_hash(), b.state_hash());
+        assert_ne!(seeded(22).state_hash(), seeded(21).state_hash());
+
+        let dir = std::env::temp_dir().join(format!("mad_tris_hash_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(21), save_dir: dir.clone(), ..GameOptions::default() });
+        game.hard_drop();
+        game.save_game(1).unwrap();
+        let mut state = game.to_state();
+        state.score += 1000;
+        fs::write(dir.join(save_file(2).unwrap()), serde_json::to_string(&state).unwrap()).unwrap();
+
+        let mut loaded = Game::new(10, 20, GameOptions { save_dir: dir.clone(), ..GameOptions::default() });
+        assert!(loaded.load_game(1).unwrap());
+        assert_eq!(loaded.state_hash(), game.state_hash());
+        assert!(!loaded.load_game(2).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn autosave_is_resumed_once() {
+        let dir = std::env::temp_dir().join(format!("mad_tris_autosave_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let options = GameOptions { seed: Some(8), save_dir: dir.clone(), ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options.clone());
+        game.hard_drop();
+        game.autosave().unwrap();
+
+        let mut resumed = Game::new(10, 20, GameOptions { seed: Some(9), ..options.clone() });
+        assert!(resumed.resume_autosave().unwrap());
+        assert_eq!(resumed.state_hash(), game.state_hash());
+        assert!(!dir.join(AUTOSAVE_FILE).exists());
+        assert_eq!(resumed.resume_autosave().unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        // A game quit mid-clear is saved once the rows are gone.
+        let options = GameOptions { line_clear_delay: Duration::from_millis(300), ..options };
+        let mut game = Game::new(10, 20, options.clone());
+        fill_row_except(&mut game, 19, &[0, 1, 2, 3]);
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 0, x: 0, y: 18 };
+        game.hard_drop();
+        assert!(game.autosave().is_err());
+        game.finish_line_clear();
+        game.autosave().unwrap();
+        assert!(resumed.resume_autosave().unwrap());
+        assert_eq!((resumed.lines_cleared(), resumed.max_stack_height()), (1, 0));
+
+        fs::write(dir.join(AUTOSAVE_FILE), "{").unwrap();
+        assert_eq!(resumed.resume_autosave().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(!dir.join(AUTOSAVE_FILE).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stack_can_reach_into_the_hidden_buffer_rows() {
+        let mut game = seeded(5);
+        fill_row_except(&mut game, 19, &[0, 1, 2, 3]);
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 1, x: 0, y: -3 };
+        assert!(!game.check_collision(&game.active_piece));
+        game.active_piece.y = -(BUFFER_ROWS as isize) - 1;
+        assert!(game.check_collision(&game.active_piece));
+        game.active_piece.y = -2;
+        game.lock_piece();
+        assert!(!game.is_game_over());
+        assert_eq!(game.max_stack_height(), 22);
+        assert_eq!(game.board().iter().filter(|cell| cell.is_some()).count(), 6 + 2);
+
+        // Saves from before the buffer existed hold only the visible rows, and still match their hash.
+        let dir = std::env::temp_dir().join(format!("mad_tris_buffer_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let game = Game::new(10, 20, GameOptions { seed: Some(5), ..GameOptions::default() });
+        let mut state = game.to_state();
+        state.board.drain(..BUFFER_ROWS * 10);
+        fs::write(dir.join(save_file(1).unwrap()), serde_json::to_string(&state).unwrap()).unwrap();
+        let mut loaded = Game::new(10, 20, GameOptions { save_dir: dir.clone(), ..GameOptions::default() });
+        assert!(loaded.load_game(1).unwrap());
+        assert_eq!(loaded.board, game.board);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The next `n` spawned piece ids.
+    fn spawn_sequence(game: &mut Game, n: usize) -> Vec<usize> {
+        (0..n)
+            .map(|_| {
+                game.spawn_new_piece();
+                game.active_piece.id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn custom_piece_sets_are_validated_and_dealt() {
+        let piece = |rotations: Vec<(usize, Vec<u8>)>| Piece { name: String::new(), rotations, color: Color(1, 2, 3), spawn_column: 0 };
+        assert!(validate_pieces(&[]).is_err());
+        assert!(validate_pieces(&[piece(vec![])]).is_err());
+        assert!(validate_pieces(&[piece(vec![(2, vec![1, 1, 1])])]).is_err());
+        assert!(validate_pieces(&[piece(vec![(0, vec![])])]).is_err());
+        assert!(validate_pieces(&[piece(vec![(2, vec![0, 0])])]).is_err());
+        assert!(validate_pieces(&[piece(vec![(6, vec![1; 6])])]).is_err());
+
+        // A five-wide I-pentomino and a domino: bags of two, and the pentomino spawns
+        // on the board even though it is wider than the spawn columns.
+        let pentomino = piece(vec![(5, vec![1; 5]), (1, vec![1; 5])]);
+        let domino = piece(vec![(2, vec![1, 1]), (1, vec![1, 1])]);
+        let pieces: Arc<[Piece]> = vec![pentomino, domino].into();
+        assert_eq!(validate_pieces(&pieces), Ok(()));
+        let options = GameOptions { pieces, line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(5, 20, options);
+        assert!(!game.srs);
+        for _ in 0..4 {
+            assert!(game.active_piece().blocks(game.pieces()).all(|(x, _)| (0..5).contains(&x)));
+            game.hard_drop();
+        }
+        // Five pieces dealt from bags of two.
+        assert_eq!(game.stats().pieces.len(), 2);
+        assert_eq!(game.stats().total_pieces(), 5);
+        assert!(game.stats().pieces.iter().all(|&count| count >= 2));
+        assert!(game.try_rotate(RotationDir::Clockwise));
+    }
+
+    #[test]
+    fn rotations_and_holds_pressed_just_before_a_spawn_apply_to_the_new_piece() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let delay = game.options.line_clear_delay;
+        let clear_a_line = |game: &mut Game| {
+            fill_row_except(game, 19, &[0, 1, 2, 3]);
+            game.active_piece = ActivePiece { id: I_PIECE, rotation: 0, x: 0, y: 0 };
+            game.hard_drop();
+            assert!(game.clearing_rows().is_some());
+        };
+
+        // Too early: the press is older than the window when the next piece spawns.
+        clear_a_line(&mut game);
+        game.try_rotate(RotationDir::Clockwise);
+        game.update(delay);
+        assert!(game.clearing_rows().is_none());
+        assert_eq!(game.active_piece().rotation, 0);
+
+        clear_a_line(&mut game);
+        game.update(delay - Duration::from_millis(50));
+        game.try_rotate(RotationDir::Half);
+        game.update(Duration::from_millis(50));
+        let rotations = game.pieces()[game.active_piece().id].rotations.len();
+        assert_eq!(game.active_piece().rotation, 2 % rotations);
+
+        // A hold refused because hold was already used carries over to the next piece.
+        game.hold_piece();
+        let held = game.held_piece_id();
+        game.hold_piece();
+        game.hard_drop();
+        assert_ne!(game.held_piece_id(), held);
+        assert!(game.hold_used_this_turn);
+    }
+
+    #[test]
+    fn hold_limit_runs_out_and_survives_a_save() {
+        let options = GameOptions { max_holds: 2, line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        assert_eq!(game.holds_remaining(), Some(2));
+        game.hold_piece();
+        game.hard_drop();
+        game.hold_piece();
+        assert_eq!(game.holds_remaining(), Some(0));
+        let state = game.to_state();
+
+        game.hard_drop();
+        let (held, active) = (game.held_piece_id(), game.active_piece().id);
+        game.hold_piece();
+        assert_eq!((game.held_piece_id(), game.active_piece().id), (held, active));
+        assert_eq!(game.status_message(), Some("No holds left"));
+
+        let mut loaded = Game::new(10, 20, GameOptions::default());
+        loaded.restore(state);
+        assert_eq!(loaded.holds_remaining(), Some(0));
+        assert_eq!(Game::new(10, 20, GameOptions::default()).holds_remaining(), None);
+    }
+
+    #[test]
+    fn initial_actions_can_be_turned_off() {
+        let options = GameOptions { initial_action_window: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        game.hold_piece();
+        game.hold_piece();
+        game.hard_drop();
+        assert!(!game.hold_used_this_turn);
+    }
+
+    #[test]
+    fn same_seed_gives_same_pieces_and_every_bag_has_all_seven() {
+        let mut a = seeded(42);
+        let mut b = seeded(42);
+        let sequence = spawn_sequence(&mut a, 21);
+        assert_eq!(sequence, spawn_sequence(&mut b, 21));
+        assert_ne!(sequence, spawn_sequence(&mut seeded(43), 21));
+
+        // Spawn k shows piece k + 2 of the game, so the second bag is spawns 6 to 12.
+        let mut bag: Vec<usize> = sequence[6..13].to_vec();
+        bag.sort_unstable();
+        assert_eq!(bag, (0..7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn stats_count_dealt_pieces_and_clear_sizes_and_survive_save() {
+        let mut game = seeded(3);
+        spawn_sequence(&mut game, 6);
+        // The first seven pieces are one full bag.
+        assert_eq!(game.stats().pieces, [1; 7]);
+        assert_eq!(game.stats().total_pieces(), 7);
+
+        fill_row_except(&mut game, 19, &[]);
+        fill_row_except(&mut game, 18, &[]);
+        game.clear_lines(false);
+        assert_eq!(game.stats().clears, [0, 1, 0, 0]);
+
+        game.timer_running = true;
+        game.update(Duration::from_secs(2));
+        assert_eq!(game.pieces_per_second(), 3.5);
+
+        let mut loaded = seeded(1);
+        loaded.restore(game.to_state());
+        assert_eq!(loaded.stats(), game.stats());
+        assert_eq!(Game::new(10, 20, GameOptions::default()).stats().total_pieces(), 1);
+    }
+
+    #[test]
+    fn restored_game_continues_the_same_sequence() {
+        let mut game = seeded(7);
+        spawn_sequence(&mut game, 10);
+        let state = game.to_state();
+
+        let mut loaded = seeded(1);
+        loaded.restore(state);
+        assert_eq!(loaded.seed(), 7);
+        assert_eq!(spawn_sequence(&mut loaded, 20), spawn_sequence(&mut game, 20));
+    }
+
+    #[test]
+    fn practice_undo_rolls_back_placements_up_to_the_depth() {
+        let options =
+            GameOptions { practice: true, seed: Some(3), line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        let snapshot = |game: &Game| {
+            let piece = game.active_piece();
+            (game.board().to_vec(), game.score(), (piece.id, piece.x, piece.y), game.next_queue().clone())
+        };
+        let mut before = Vec::new();
+        for _ in 0..UNDO_DEPTH + 2 {
+            before.push(snapshot(&game));
+            game.hard_drop();
+        }
+        for expected in before.iter().rev().take(UNDO_DEPTH) {
+            game.undo();
+            assert_eq!(&snapshot(&game), expected);
+        }
+        game.undo();
+        assert_eq!(game.status_message(), Some("Nothing to undo"));
+        assert_eq!(game.stats().total_pieces(), 3);
+
+        // Replaying the undone placement gets back to the same game.
+        game.hard_drop();
+        assert_eq!(snapshot(&game), before[3]);
+
+        let mut scored = seeded(3);
+        scored.hard_drop();
+        scored.undo();
+        assert_eq!(scored.stats().total_pieces(), 2);
+    }
+
+    #[test]
+    fn hiding_the_next_queue_keeps_the_sequence_and_showing_it_ends_blind() {
+        let options = GameOptions { seed: Some(9), line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut shown = Game::new(10, 20, options.clone());
+        let mut hidden = Game::new(10, 20, GameOptions { hide_next: true, ..options });
+        assert!(hidden.next_hidden() && hidden.stats().blind);
+        assert!(!shown.stats().blind);
+        for _ in 0..10 {
+            shown.hard_drop();
+            hidden.hard_drop();
+            assert_eq!(shown.next_queue(), hidden.next_queue());
+        }
+
+        hidden.toggle_next_hidden();
+        hidden.toggle_next_hidden();
+        assert!(hidden.next_hidden());
+        assert!(!hidden.stats().blind);
+    }
+
+    #[test]
+    fn next_queue_keeps_preview_count_and_feeds_spawns() {
+        let options = GameOptions { preview_count: 3, seed: Some(5), ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        assert_eq!(game.next_queue().len(), 3);
+        let upcoming: Vec<usize> = game.next_queue().iter().copied().collect();
+        assert_eq!(spawn_sequence(&mut game, 3), upcoming);
+        assert_eq!(game.next_queue().len(), 3);
+    }
+
+    /// Bottom rows of a T-spin double: a one-wide well at column 4 under a three-wide
+    /// slot, with an overhang at (3, 17) so the T has to be rotated in.
+    fn t_spin_double_setup() -> Game {
+        let options = GameOptions { line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        fill_row_except(&mut game, 19, &[4]);
+        fill_row_except(&mut game, 18, &[3, 4, 5]);
+        let index = game.cell_index(3, 17);
+        game.board[index] = Some(Color(255, 255, 255));
+        // Pointing down into the slot, as if it had just been rotated there.
+        game.active_piece = ActivePiece { id: T_PIECE, rotation: 2, x: 3, y: 18 };
+        game
+    }
+
+    #[test]
+    fn rotated_t_in_slot_scores_t_spin_double() {
+        let mut game = t_spin_double_setup();
+        game.last_action_was_rotation = true;
+        assert!(game.is_t_spin());
+        game.lock_piece();
+
+        assert_eq!(game.score, 1200);
+        assert_eq!(game.total_lines_cleared, 2);
+        assert_eq!(game.status_message(), Some("T-SPIN DOUBLE!"));
+        assert_eq!(game.stats().t_spins, 1);
+    }
+
+    #[test]
+    fn t_kicks_down_into_a_t_spin_triple_slot() {
+        let options = GameOptions { line_clear_delay: Duration::ZERO, ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        // A three-deep well at column 3 with a notch at (4, 18), capped by an overhang
+        // at (3, 15). Only the last 0->R kick, one left and two down, fits.
+        fill_row_except(&mut game, 19, &[3]);
+        fill_row_except(&mut game, 18, &[3, 4]);
+        fill_row_except(&mut game, 17, &[3]);
+        let index = game.cell_index(3, 15);
+        game.board[index] = Some(Color(255, 255, 255));
+        game.active_piece = ActivePiece { id: T_PIECE, rotation: 0, x: 3, y: 15 };
+
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (1, 3, 17));
+        game.lock_piece();
+        assert_eq!(game.total_lines_cleared, 3);
+        assert_eq!(game.status_message(), Some("T-SPIN TRIPLE!"));
+    }
+
+    #[test]
+    fn i_piece_kicks_off_the_wall_and_rotates_in_its_box() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        // Vertical against the left wall: the plain turn and the first kick poke
+        // through the wall, so the third candidate (two right) is used.
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 1, x: 0, y: 5 };
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (2, 0, 7));
+
+        // In open space four turns bring the piece back to where it started.
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 0, x: 3, y: 5 };
+        for _ in 0..4 {
+            assert!(game.try_rotate(RotationDir::Clockwise));
+        }
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (0, 3, 5));
+    }
+
+    #[test]
+    fn t_kicks_left_off_the_right_wall() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        // Pointing left with its stem in the last column: turning flat would poke
+        // through the wall, so the first L->0 kick moves it one left.
+        game.active_piece = ActivePiece { id: T_PIECE, rotation: 3, x: 8, y: 5 };
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (0, 7, 5));
+    }
+
+    #[test]
+    fn i_piece_climbs_out_of_a_shallow_gap() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        // Standing in a two-deep, one-wide gap: lying down on either row of the gap
+        // hits the stack, so only the R->2 kick that lifts it two rows fits.
+        fill_row_except(&mut game, 19, &[4]);
+        fill_row_except(&mut game, 18, &[4]);
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 1, x: 4, y: 16 };
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (2, 1, 16));
+    }
+
+    #[test]
+    fn t_blocked_on_both_sides_kicks_up_to_turn() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        // Flat in a three-wide notch with blocks either side of its nub. Turning in
+        // place or one left digs into the floor; one left and one up fits.
+        fill_row_except(&mut game, 19, &[0]);
+        fill_row_except(&mut game, 18, &[3, 4, 5]);
+        for x in [2, 6] {
+            let index = game.cell_index(x, 17);
+            game.board[index] = Some(Color(255, 255, 255));
+        }
+        game.active_piece = ActivePiece { id: T_PIECE, rotation: 0, x: 3, y: 17 };
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (1, 3, 16));
+    }
+
+    #[test]
+    fn rotation_with_no_fitting_kick_leaves_the_piece_alone() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        // Deep in a one-wide well, every kick for lying the I down ends up in the stack.
+        for y in 16..20 {
+            fill_row_except(&mut game, y, &[4]);
+        }
+        for direction in [RotationDir::Clockwise, RotationDir::CounterClockwise] {
+            game.active_piece = ActivePiece { id: I_PIECE, rotation: 1, x: 4, y: 16 };
+            assert!(!game.try_rotate(direction));
+            let piece = game.active_piece();
+            assert_eq!((piece.rotation, piece.x, piece.y), (1, 4, 16));
+        }
+    }
+
+    #[test]
+    fn counterclockwise_and_half_turns_undo_and_kick() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.active_piece = ActivePiece { id: T_PIECE, rotation: 0, x: 3, y: 5 };
+        assert!(game.try_rotate(RotationDir::Clockwise));
+        assert!(game.try_rotate(RotationDir::CounterClockwise));
+        assert!(game.try_rotate(RotationDir::Half));
+        assert_eq!(game.active_piece().rotation, 2);
+        assert!(game.try_rotate(RotationDir::Half));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (0, 3, 5));
+
+        // Counter-clockwise from L against the right wall uses the mirrored I kicks.
+        game.active_piece = ActivePiece { id: I_PIECE, rotation: 3, x: 9, y: 5 };
+        assert!(game.try_rotate(RotationDir::CounterClockwise));
+        let piece = game.active_piece();
+        assert_eq!((piece.rotation, piece.x, piece.y), (2, 6, 7));
+    }
+
+    #[test]
+    fn t_dropped_into_slot_is_not_a_t_spin() {
+        let mut game = t_spin_double_setup();
+        game.last_action_was_rotation = false;
+        assert!(!game.is_t_spin());
+        game.lock_piece();
+
+        assert_eq!(game.score, 300);
+    }
+}
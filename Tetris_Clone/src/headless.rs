@@ -0,0 +1,180 @@
+//! Headless play over newline-delimited JSON, for bots and other external tools.
+//!
+//! Each input line is one command such as `{"action": "move_left"}` or
+//! `{"action": "tick", "ms": 16}`. Time only passes through `tick`, so a session is
+//! fully deterministic for a given seed. Every game event is written as one output
+//! line: the event's fields plus a `state` snapshot of the whole game.
+//!
+//! To replay a recorded session and catch it drifting, give a command the `hash` its
+//! `state` had after it, as in `{"action": "hard_drop", "hash": 123}`. When that
+//! command locks a piece and the game hashes differently, a `desync` line reports the
+//! number of `tick` commands played so far.
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::game::{Game, GameEvent, PlayerInput, RotationDir, SerializableGameState};
+
+/// A command read from the input stream.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Command {
+    MoveLeft,
+    MoveRight,
+    Rotate,
+    RotateCcw,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    /// Advances the game clock by `ms` milliseconds.
+    Tick { ms: u64 },
+    /// Writes a `state` line without changing anything.
+    State,
+    Quit,
+}
+
+/// An input line: a command and, optionally, the [`Game::state_hash`] the game is
+/// expected to have after it.
+#[derive(Debug, Deserialize)]
+struct CommandLine {
+    #[serde(flatten)]
+    command: Command,
+    #[serde(default)]
+    hash: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct EventLine<'a> {
+    #[serde(flatten)]
+    event: &'a GameEvent,
+    state: &'a SerializableGameState,
+}
+
+/// Plays `game` from the commands in `input`, writing events to `output`, until the
+/// input ends or a `quit` command arrives. Lines that aren't valid commands are
+/// answered with an `error` line and otherwise ignored. Needs `record_events` on.
+pub fn run<R: BufRead, W: Write>(game: &mut Game, input: R, mut output: W) -> io::Result<()> {
+    write_line(&mut output, &json!({ "type": "started", "state": game.to_state() }))?;
+    write_events(game, &mut output)?;
+    let mut ticks: u64 = 0;
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let CommandLine { command, hash } = match serde_json::from_str(&line) {
+            Ok(command) => command,
+            Err(e) => {
+                write_line(&mut output, &json!({ "type": "error", "message": e.to_string() }))?;
+                continue;
+            }
+        };
+        match command {
+            // Like key presses, moves made while rows clear wait for the next piece.
+            Command::MoveLeft => game.queue_input(PlayerInput::MoveLeft),
+            Command::MoveRight => game.queue_input(PlayerInput::MoveRight),
+            Command::Rotate => game.queue_input(PlayerInput::Rotate(RotationDir::Clockwise)),
+            Command::RotateCcw => game.queue_input(PlayerInput::Rotate(RotationDir::CounterClockwise)),
+            Command::Rotate180 => game.queue_input(PlayerInput::Rotate(RotationDir::Half)),
+            Command::SoftDrop => game.queue_input(PlayerInput::SoftDrop),
+            Command::HardDrop => game.queue_input(PlayerInput::HardDrop),
+            Command::Hold => game.queue_input(PlayerInput::Hold),
+            Command::Tick { ms } => {
+                ticks += 1;
+                game.update(Duration::from_millis(ms));
+            }
+            Command::State => write_line(&mut output, &json!({ "type": "state", "state": game.to_state() }))?,
+            Command::Quit => break,
+        }
+        let locked = write_events(game, &mut output)?;
+        if let Some(expected) = hash.filter(|&hash| locked && hash != game.state_hash()) {
+            let desync = json!({ "type": "desync", "tick": ticks, "expected": expected, "actual": game.state_hash() });
+            write_line(&mut output, &desync)?;
+            output.flush()?;
+        }
+    }
+    output.flush()
+}
+
+/// Writes one line per recorded event, each with the game state after the command.
+/// Returns whether a piece locked.
+fn write_events<W: Write>(game: &mut Game, output: &mut W) -> io::Result<bool> {
+    let events = game.take_events();
+    if events.is_empty() {
+        return Ok(false);
+    }
+    let state = game.to_state();
+    for event in &events {
+        write_line(output, &EventLine { event, state: &state })?;
+    }
+    output.flush()?;
+    Ok(events.iter().any(|event| matches!(event, GameEvent::Locked { .. })))
+}
+
+fn write_line<W: Write, T: Serialize>(output: &mut W, value: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *output, value).map_err(io::Error::other)?;
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameOptions;
+
+    fn play(commands: &str) -> Vec<serde_json::Value> {
+        let options = GameOptions { record_events: true, seed: Some(1), ..GameOptions::default() };
+        let mut game = Game::new(10, 20, options);
+        let mut output = Vec::new();
+        run(&mut game, commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn hard_drop_reports_move_lock_and_spawn_with_state() {
+        let lines = play("{\"action\": \"hard_drop\"}\n");
+        let types: Vec<&str> = lines.iter().map(|line| line["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["started", "moved", "locked", "spawned"]);
+        let board = lines[3]["state"]["board"].as_array().unwrap();
+        // Buffer rows included.
+        assert_eq!(board.len(), 250);
+        assert_eq!(board.iter().filter(|cell| !cell.is_null()).count(), 4);
+    }
+
+    #[test]
+    fn bad_commands_get_an_error_and_quit_stops_reading() {
+        let lines = play("{\"action\": \"teleport\"}\n{\"action\": \"quit\"}\n{\"action\": \"hard_drop\"}\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["type"], "error");
+    }
+
+    #[test]
+    fn replays_report_a_desync_when_a_lock_hashes_differently() {
+        let recorded = play("{\"action\": \"tick\", \"ms\": 16}\n{\"action\": \"hard_drop\"}\n");
+        let hash = recorded.iter().find(|line| line["type"] == "locked").unwrap()["state"]["hash"].as_u64().unwrap();
+        let replay = |hash: u64| {
+            play(&format!("{{\"action\": \"tick\", \"ms\": 16}}\n{{\"action\": \"hard_drop\", \"hash\": {}}}\n", hash))
+        };
+        assert!(replay(hash).iter().all(|line| line["type"] != "desync"));
+        let tampered = replay(hash ^ 1);
+        let desync = tampered.last().unwrap();
+        assert_eq!((desync["type"].as_str(), desync["tick"].as_u64()), (Some("desync"), Some(1)));
+        assert_eq!((desync["expected"].as_u64(), desync["actual"].as_u64()), (Some(hash ^ 1), Some(hash)));
+    }
+
+    #[test]
+    fn ticks_drive_gravity() {
+        let lines = play("{\"action\": \"tick\", \"ms\": 999}\n{\"action\": \"tick\", \"ms\": 1}\n");
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["type"], "moved");
+        let spawn_y = lines[0]["state"]["active_piece"]["y"].as_i64().unwrap();
+        assert_eq!(lines[1]["y"], spawn_y + 1);
+    }
+}
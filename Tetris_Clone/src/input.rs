@@ -0,0 +1,420 @@
+//! Player actions and the key bindings that trigger them.
+//!
+//! Keys are described with a small terminal-neutral [`Key`] type so bindings can be
+//! loaded from `controls.toml` and tested without a terminal backend.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use serde::Deserialize;
+
+const CONTROLS_FILE: &str = "controls.toml";
+
+/// Something the player can ask the game to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Rotate,
+    RotateCcw,
+    Rotate180,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    Undo,
+    Edit,
+    /// Starts a fresh game on the spot once confirmed, throwing away the current one
+    /// and its score.
+    Restart,
+    Stats,
+    Heights,
+    Reveal,
+    Music,
+    HideNext,
+    Pause,
+    Save,
+    Load,
+    Quit,
+}
+
+impl Action {
+    /// Every action, in the order the controls panel lists them.
+    pub const ALL: [Action; 20] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Rotate,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::Hold,
+        Action::Undo,
+        Action::Edit,
+        Action::Restart,
+        Action::Stats,
+        Action::Heights,
+        Action::Reveal,
+        Action::Music,
+        Action::HideNext,
+        Action::Pause,
+        Action::Save,
+        Action::Load,
+        Action::Quit,
+    ];
+
+    /// Short description shown in the controls panel.
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Left",
+            Action::MoveRight => "Right",
+            Action::Rotate => "Rotate",
+            Action::RotateCcw => "Rotate CCW",
+            Action::Rotate180 => "Rotate 180",
+            Action::SoftDrop => "Soft Drop",
+            Action::HardDrop => "Hard Drop",
+            Action::Hold => "Hold",
+            Action::Undo => "Undo",
+            Action::Edit => "Edit",
+            Action::Restart => "Restart",
+            Action::Stats => "Stats",
+            Action::Heights => "Heights",
+            Action::Reveal => "Reveal",
+            Action::Music => "Music",
+            Action::HideNext => "Hide Next",
+            Action::Pause => "Pause",
+            Action::Save => "Save",
+            Action::Load => "Load",
+            Action::Quit => "Quit",
+        }
+    }
+
+    /// Name used for the action in `controls.toml`.
+    fn config_name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Rotate => "rotate",
+            Action::RotateCcw => "rotate_ccw",
+            Action::Rotate180 => "rotate_180",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::Hold => "hold",
+            Action::Undo => "undo",
+            Action::Edit => "edit",
+            Action::Restart => "restart",
+            Action::Stats => "stats",
+            Action::Heights => "heights",
+            Action::Reveal => "reveal",
+            Action::Music => "music",
+            Action::HideNext => "hide_next",
+            Action::Pause => "pause",
+            Action::Save => "save",
+            Action::Load => "load",
+            Action::Quit => "quit",
+        }
+    }
+}
+
+/// A physical key, independent of the terminal library that reported it.
+/// Letters are always stored lowercase so bindings ignore Shift/Caps Lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Enter,
+    Esc,
+    Tab,
+    Backspace,
+    Shift,
+}
+
+impl Key {
+    /// Builds a character key, folding letters to lowercase.
+    pub fn char(c: char) -> Key {
+        Key::Char(c.to_ascii_lowercase())
+    }
+
+    /// Parses a key name as written in `controls.toml`: a single character, or one of
+    /// `Left`, `Right`, `Up`, `Down`, `Space`, `Enter`, `Esc`, `Tab`, `Backspace`, `Shift`.
+    pub fn parse(name: &str) -> Option<Key> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(Key::char(c));
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "left" => Some(Key::Left),
+            "right" => Some(Key::Right),
+            "up" => Some(Key::Up),
+            "down" => Some(Key::Down),
+            "space" => Some(Key::Char(' ')),
+            "enter" => Some(Key::Enter),
+            "esc" | "escape" => Some(Key::Esc),
+            "tab" => Some(Key::Tab),
+            "backspace" => Some(Key::Backspace),
+            "shift" => Some(Key::Shift),
+            _ => None,
+        }
+    }
+
+    /// Compact label (at most three columns) for the controls panel.
+    pub fn label(self) -> String {
+        match self {
+            Key::Char(' ') => "Spc".to_string(),
+            Key::Char(c) => c.to_ascii_uppercase().to_string(),
+            Key::Left => "←".to_string(),
+            Key::Right => "→".to_string(),
+            Key::Up => "↑".to_string(),
+            Key::Down => "↓".to_string(),
+            Key::Enter => "Ent".to_string(),
+            Key::Esc => "Esc".to_string(),
+            Key::Tab => "Tab".to_string(),
+            Key::Backspace => "Bks".to_string(),
+            Key::Shift => "Sft".to_string(),
+        }
+    }
+
+    /// Like [`Key::label`], but with the arrows spelled in ASCII.
+    pub fn ascii_label(self) -> String {
+        match self {
+            Key::Left => "<".to_string(),
+            Key::Right => ">".to_string(),
+            Key::Up => "^".to_string(),
+            Key::Down => "v".to_string(),
+            key => key.label(),
+        }
+    }
+}
+
+/// One key name or a list of them, as accepted for each action in `controls.toml`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Maps each action to the keys that trigger it.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = Action::ALL
+            .iter()
+            .map(|&action| {
+                let keys = match action {
+                    Action::MoveLeft => vec![Key::Left],
+                    Action::MoveRight => vec![Key::Right],
+                    Action::Rotate => vec![Key::Up],
+                    Action::RotateCcw => vec![Key::Char('z')],
+                    Action::Rotate180 => vec![Key::Char('a')],
+                    Action::SoftDrop => vec![Key::Down],
+                    Action::HardDrop => vec![Key::Char(' ')],
+                    Action::Hold => vec![Key::Char('c'), Key::Shift],
+                    Action::Undo => vec![Key::Char('u')],
+                    Action::Edit => vec![Key::Char('e')],
+                    Action::Restart => vec![Key::Char('r')],
+                    Action::Stats => vec![Key::Char('t')],
+                    Action::Heights => vec![Key::Char('h')],
+                    Action::Reveal => vec![Key::Char('v')],
+                    Action::Music => vec![Key::Char('m')],
+                    Action::HideNext => vec![Key::Char('n')],
+                    Action::Pause => vec![Key::Char('p')],
+                    Action::Save => vec![Key::Char('s')],
+                    Action::Load => vec![Key::Char('l')],
+                    Action::Quit => vec![Key::Char('q'), Key::Esc],
+                };
+                (action, keys)
+            })
+            .collect();
+        KeyBindings { keys }
+    }
+}
+
+impl KeyBindings {
+    /// Loads bindings from "controls.toml", or the defaults if there is no such file.
+    /// An unreadable or invalid file is reported as an error so the caller can warn
+    /// and fall back to [`KeyBindings::default`].
+    pub fn load() -> Result<Self, String> {
+        match fs::read_to_string(CONTROLS_FILE) {
+            Ok(text) => Self::from_toml(&text).map_err(|e| format!("{}: {}", CONTROLS_FILE, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", CONTROLS_FILE, e)),
+        }
+    }
+
+    /// Parses bindings from TOML such as `move_left = ["a", "Left"]`. Actions that
+    /// aren't mentioned keep their default keys. Unknown actions or key names, and
+    /// keys bound to more than one action, are errors.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let table: HashMap<String, KeyNames> = toml::from_str(text).map_err(|e| e.to_string())?;
+        let mut bindings = Self::default();
+        for (name, names) in table {
+            let action = Action::ALL
+                .iter()
+                .copied()
+                .find(|action| action.config_name() == name)
+                .ok_or_else(|| format!("unknown action `{}`", name))?;
+            let names = match names {
+                KeyNames::One(name) => vec![name],
+                KeyNames::Many(names) => names,
+            };
+            let keys = names
+                .iter()
+                .map(|key| Key::parse(key).ok_or_else(|| format!("unknown key `{}` for `{}`", key, name)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if keys.is_empty() {
+                return Err(format!("no keys given for `{}`", name));
+            }
+            bindings.keys.insert(action, keys);
+        }
+
+        let mut seen: HashMap<Key, Action> = HashMap::new();
+        for action in Action::ALL {
+            for &key in bindings.keys_for(action) {
+                if let Some(other) = seen.insert(key, action) {
+                    if other != action {
+                        return Err(format!(
+                            "key `{}` is bound to both `{}` and `{}`",
+                            key.label(),
+                            other.config_name(),
+                            action.config_name()
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// The action bound to `key`, if any.
+    pub fn action_for(&self, key: Key) -> Option<Action> {
+        Action::ALL.into_iter().find(|&action| self.keys_for(action).contains(&key))
+    }
+
+    /// Bindings for two players sharing a keyboard: player one moves with the arrows,
+    /// rotates with Up and `/`, hard drops with Enter and holds with `.`; player two
+    /// uses A/D, W and Q, S, Space and E. Either may pause (P), toggle music (M) or
+    /// quit (Esc). Other actions are left unbound.
+    pub fn versus() -> [KeyBindings; 2] {
+        let players = [
+            [Key::Left, Key::Right, Key::Up, Key::char('/'), Key::Down, Key::Enter, Key::char('.')],
+            ['a', 'd', 'w', 'q', 's', ' ', 'e'].map(Key::char),
+        ];
+        players.map(|keys| {
+            let moves = [
+                Action::MoveLeft,
+                Action::MoveRight,
+                Action::Rotate,
+                Action::RotateCcw,
+                Action::SoftDrop,
+                Action::HardDrop,
+                Action::Hold,
+            ];
+            let shared = [(Action::Pause, Key::char('p')), (Action::Music, Key::char('m')), (Action::Quit, Key::Esc)];
+            let keys = moves.into_iter().zip(keys).chain(shared).map(|(action, key)| (action, vec![key])).collect();
+            KeyBindings { keys }
+        })
+    }
+
+    /// These bindings with every action but `actions` unbound.
+    pub fn restricted_to(&self, actions: &[Action]) -> KeyBindings {
+        let keys = self.keys.iter().filter(|(action, _)| actions.contains(action));
+        KeyBindings { keys: keys.map(|(&action, keys)| (action, keys.clone())).collect() }
+    }
+
+    /// All keys bound to `action`; the first one is the primary key shown in help text.
+    pub fn keys_for(&self, action: Action) -> &[Key] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// One "key: action" line per bound action for the controls panel, in plain ASCII
+    /// if `ascii`.
+    pub fn help_lines(&self, ascii: bool) -> Vec<String> {
+        let label = if ascii { Key::ascii_label } else { Key::label };
+        Action::ALL
+            .iter()
+            .filter_map(|&action| {
+                let &key = self.keys_for(action).first()?;
+                Some(format!("{:>3}: {}", label(key), action.description()))
+            })
+            .collect()
+    }
+
+    /// The pause and quit keys, which lead to everything else, as one line for when
+    /// there's no room for [`KeyBindings::help_lines`]; in plain ASCII if `ascii`.
+    pub fn hint_line(&self, ascii: bool) -> String {
+        let label = if ascii { Key::ascii_label } else { Key::label };
+        let parts: Vec<String> = [Action::Pause, Action::Quit]
+            .iter()
+            .filter_map(|&action| {
+                let &key = self.keys_for(action).first()?;
+                Some(format!("{}: {}", label(key), action.description()))
+            })
+            .collect();
+        parts.join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_cover_arrows_and_letters_in_any_case() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.action_for(Key::Left), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for(Key::char('C')), Some(Action::Hold));
+        assert_eq!(bindings.action_for(Key::Esc), Some(Action::Quit));
+        assert_eq!(bindings.action_for(Key::char('Z')), Some(Action::RotateCcw));
+        assert_eq!(bindings.action_for(Key::char('y')), None);
+    }
+
+    #[test]
+    fn toml_overrides_only_listed_actions() {
+        let bindings = KeyBindings::from_toml(
+            "move_left = [\"a\", \"Left\"]\nmove_right = \"d\"\nrotate = \"w\"\nrotate_180 = \"x\"\nsoft_drop = \"S\"\nsave = \"F\"",
+        )
+        .unwrap();
+        assert_eq!(bindings.action_for(Key::char('a')), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for(Key::Left), Some(Action::MoveLeft));
+        assert_eq!(bindings.action_for(Key::Right), None);
+        assert_eq!(bindings.action_for(Key::char('s')), Some(Action::SoftDrop));
+        assert_eq!(bindings.action_for(Key::Char(' ')), Some(Action::HardDrop));
+        assert_eq!(bindings.help_lines(false)[2], "  W: Rotate");
+        assert_eq!(KeyBindings::default().help_lines(false)[0], "  ←: Left");
+        assert_eq!(KeyBindings::default().help_lines(true)[0], "  <: Left");
+    }
+
+    #[test]
+    fn rejects_duplicate_and_unknown_bindings() {
+        // "s" is still the default Save key.
+        assert!(KeyBindings::from_toml("soft_drop = \"s\"").is_err());
+        assert!(KeyBindings::from_toml("rotate = \"Meta\"").is_err());
+        assert!(KeyBindings::from_toml("teleport = \"t\"").is_err());
+    }
+
+    #[test]
+    fn versus_players_share_only_pause_music_and_quit() {
+        let [one, two] = KeyBindings::versus();
+        assert_eq!(one.action_for(Key::Left), Some(Action::MoveLeft));
+        assert_eq!(two.action_for(Key::char('A')), Some(Action::MoveLeft));
+        assert_eq!(one.action_for(Key::char('a')), None);
+        assert_eq!(two.action_for(Key::Left), None);
+        assert_eq!(two.action_for(Key::char(' ')), Some(Action::HardDrop));
+        for key in [Key::char('p'), Key::char('m'), Key::Esc] {
+            assert_eq!(one.action_for(key), two.action_for(key));
+        }
+        assert_eq!(one.help_lines(false).len(), 10);
+        assert_eq!(two.help_lines(false)[4], "  S: Soft Drop");
+        assert_eq!(KeyBindings::default().restricted_to(&[Action::Quit]).help_lines(false), ["  Q: Quit"]);
+        assert_eq!(KeyBindings::default().hint_line(false), "P: Pause  Q: Quit");
+        assert_eq!(KeyBindings::default().restricted_to(&[Action::Quit]).hint_line(false), "Q: Quit");
+    }
+}
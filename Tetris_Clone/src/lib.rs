@@ -0,0 +1,19 @@
+//! Terminal-independent Tetris engine.
+//!
+//! The [`game`] module holds the rules and state of a single game; frontends such as the
+//! bundled crossterm TUI drive it with player actions and draw it through its accessors.
+//! The [`render`] module lays a game out on screen through a pluggable [`render::Renderer`],
+//! and [`headless`] plays a game over newline-delimited JSON for bots and tooling.
+//! [`bot`] has built-in computer players, [`sound`] plays effects for game events, and
+//! [`cast`] records terminal output as an asciinema recording. [`versus`] pits two games
+//! against each other on one screen, and [`net`] plays such a match over TCP.
+
+pub mod bot;
+pub mod cast;
+pub mod game;
+pub mod headless;
+pub mod input;
+pub mod net;
+pub mod render;
+pub mod sound;
+pub mod versus;
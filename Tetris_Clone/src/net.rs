@@ -0,0 +1,387 @@
+//! Versus play over TCP, one player on each end.
+//!
+//! Both ends play the same piece sequence: the host picks the board size, seed and
+//! randomizer and sends them in [`Message::Hello`], so the two seeded games deal identical pieces
+//! without exchanging them. After that each end simulates only its own game. Clears
+//! send [`Message::Garbage`] to the other end, and every change to a game is sent as a
+//! [`Message::Board`] snapshot so the opponent's board can be drawn.
+//!
+//! Messages are newline-delimited JSON. The protocol assumes a LAN: a message takes a
+//! few milliseconds at most, well inside the default garbage delay, so latency only
+//! shifts when garbage shows up in the meter. On slower links the opponent's board
+//! lags behind by the round trip and garbage arrives correspondingly late.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{
+    Game, GameEvent, GameMode, GameOptions, RandomizerKind, SerializableGameState, BUFFER_ROWS, MAX_COLUMNS, MAX_LINES,
+    MIN_BOARD_SIZE,
+};
+use crate::versus::attack_lines;
+
+/// Everything the two ends say to each other.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Sent by the host as soon as a player joins: the board size, seed and
+    /// randomizer both games are played with.
+    Hello {
+        width: usize,
+        height: usize,
+        seed: u64,
+        #[serde(default)]
+        randomizer: RandomizerKind,
+    },
+    /// The sender cleared lines that send `lines` garbage to the receiver.
+    Garbage { lines: u32 },
+    /// The sender's game after its latest change.
+    Board { state: Box<SerializableGameState> },
+    /// The sender is leaving the match.
+    Bye,
+}
+
+/// Longest line read as a message. Snapshots of the biggest boards are well under it;
+/// anything longer is cut off, fails to parse and ends the connection.
+const MAX_MESSAGE_BYTES: u64 = 1 << 20;
+
+/// A connection to the other player. Incoming messages are read on a background
+/// thread, so checking for them never blocks.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+}
+
+impl Connection {
+    /// Waits on `port` for the other player to connect.
+    pub fn host(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let (stream, _) = listener.accept()?;
+        Self::new(stream)
+    }
+
+    /// Connects to a player hosting at `addr`, such as "192.168.1.20:7878".
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Self::new(TcpStream::connect(addr)?)
+    }
+
+    /// Wraps an open stream. The reader thread stops at the end of the stream or at
+    /// the first line that isn't a valid message, including one over [`MAX_MESSAGE_BYTES`].
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        // Snapshots are small and frequent; don't let Nagle's algorithm hold them back.
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match (&mut reader).take(MAX_MESSAGE_BYTES).read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+                let Ok(message) = serde_json::from_slice(&line) else { break };
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Connection { stream, incoming })
+    }
+
+    /// Sends `message` as one line.
+    pub fn send(&mut self, message: &Message) -> io::Result<()> {
+        let mut line = serde_json::to_vec(message).map_err(io::Error::other)?;
+        line.push(b'\n');
+        self.stream.write_all(&line)
+    }
+
+    /// The next message that has arrived, if any. Once the other end has gone and
+    /// everything it sent has been read, this is a [`io::ErrorKind::ConnectionAborted`]
+    /// error.
+    pub fn try_receive(&self) -> io::Result<Option<Message>> {
+        match self.incoming.try_recv() {
+            Ok(message) => Ok(Some(message)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(disconnected()),
+        }
+    }
+
+    /// Waits up to `timeout` for the next message.
+    pub fn receive(&self, timeout: Duration) -> io::Result<Message> {
+        self.incoming.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => io::Error::new(io::ErrorKind::TimedOut, "no reply from the other player"),
+            RecvTimeoutError::Disconnected => disconnected(),
+        })
+    }
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, "the other player disconnected")
+}
+
+/// How a network match ended, for this end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The opponent topped out.
+    Won,
+    /// This player topped out.
+    Lost,
+    /// The opponent quit or the connection dropped, which also counts as a win.
+    OpponentLeft,
+}
+
+/// One end of a network match: the local game, and the opponent's as last reported.
+pub struct NetMatch {
+    pub game: Game,
+    /// Only ever replaced by the opponent's snapshots, never updated here.
+    pub opponent: Game,
+    connection: Connection,
+    /// Garbage lines sent to and received from the opponent.
+    sent: u32,
+    received: u32,
+    disconnected: bool,
+}
+
+impl NetMatch {
+    /// Starts the host's end: picks the seed (unless `options` has one), tells the
+    /// other player the board size, seed and randomizer, and sends the starting board.
+    pub fn host(mut connection: Connection, width: usize, height: usize, options: GameOptions) -> io::Result<Self> {
+        let seed = options.seed.unwrap_or_else(rand::random);
+        connection.send(&Message::Hello { width, height, seed, randomizer: options.randomizer })?;
+        Ok(Self::start(connection, width, height, GameOptions { seed: Some(seed), ..options }))
+    }
+
+    /// Starts the joining player's end, waiting up to `timeout` for the host's board
+    /// size, seed and randomizer, which replace the ones in `options`. A size the command line wouldn't allow is an
+    /// [`io::ErrorKind::InvalidData`] error.
+    pub fn join(connection: Connection, options: GameOptions, timeout: Duration) -> io::Result<Self> {
+        match connection.receive(timeout)? {
+            Message::Hello { width, height, .. }
+                if !(MIN_BOARD_SIZE..=MAX_COLUMNS).contains(&width)
+                    || !(MIN_BOARD_SIZE..=MAX_LINES).contains(&height) =>
+            {
+                let msg = format!("the host's {}x{} board is out of range", width, height);
+                Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+            }
+            Message::Hello { width, height, seed, randomizer } => {
+                let options = GameOptions { seed: Some(seed), randomizer, ..options };
+                Ok(Self::start(connection, width, height, options))
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "the host didn't start with a hello")),
+        }
+    }
+
+    /// Both ends play endless marathon with events recorded, since garbage and
+    /// snapshots are sent from them.
+    fn start(connection: Connection, width: usize, height: usize, options: GameOptions) -> Self {
+        let options = GameOptions {
+            mode: GameMode::Marathon { lines: 0 },
+            record_events: true,
+            practice: false,
+            ..options
+        };
+        let mut net = NetMatch {
+            game: Game::new(width, height, options.clone()),
+            opponent: Game::new(width, height, GameOptions { record_events: false, ..options }),
+            connection,
+            sent: 0,
+            received: 0,
+            disconnected: false,
+        };
+        net.send_board();
+        net
+    }
+
+    /// Advances the local game by `elapsed`, sends garbage and a snapshot for what
+    /// changed, and takes in whatever the opponent sent. Returns the local game's
+    /// events. Nothing is played once the match is over.
+    pub fn update(&mut self, elapsed: Duration) -> Vec<GameEvent> {
+        self.receive_all();
+        if self.outcome().is_some() {
+            return Vec::new();
+        }
+        self.game.update(elapsed);
+        let events = self.game.take_events();
+        let attack: u32 = events
+            .iter()
+            .map(|event| match *event {
+                GameEvent::LinesCleared { count, t_spin } => attack_lines(count, t_spin),
+                _ => 0,
+            })
+            .sum();
+        if attack > 0 {
+            self.send(&Message::Garbage { lines: attack });
+            self.sent = self.sent.saturating_add(attack);
+        }
+        if !events.is_empty() {
+            self.send_board();
+        }
+        events
+    }
+
+    /// How the match ended, or `None` while both players are still in it.
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.game.is_game_over() {
+            Some(Outcome::Lost)
+        } else if self.opponent.is_game_over() {
+            Some(Outcome::Won)
+        } else if self.disconnected {
+            Some(Outcome::OpponentLeft)
+        } else {
+            None
+        }
+    }
+
+    /// Garbage lines this player has sent the opponent.
+    pub fn sent(&self) -> u32 {
+        self.sent
+    }
+
+    /// Garbage lines the opponent has sent this player.
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Tells the opponent this player is leaving.
+    pub fn leave(&mut self) {
+        self.send(&Message::Bye);
+    }
+
+    fn send_board(&mut self) {
+        self.send(&Message::Board { state: Box::new(self.game.to_state()) });
+    }
+
+    /// Sends `message`; a failed send means the opponent is gone.
+    fn send(&mut self, message: &Message) {
+        if !self.disconnected && self.connection.send(message).is_err() {
+            self.disconnected = true;
+        }
+    }
+
+    fn receive_all(&mut self) {
+        while !self.disconnected {
+            match self.connection.try_receive() {
+                Ok(None) => break,
+                // No clear sends more lines than the board holds, so a bigger burst is a
+                // protocol violation rather than an attack.
+                Ok(Some(Message::Garbage { lines })) if lines as usize > self.game.height() + BUFFER_ROWS => {
+                    self.disconnected = true;
+                }
+                Ok(Some(Message::Garbage { lines })) => {
+                    self.game.queue_garbage(lines);
+                    self.received = self.received.saturating_add(lines);
+                }
+                // A board that doesn't fit means the two ends disagree about the match.
+                Ok(Some(Message::Board { state })) => {
+                    let size = (self.opponent.width(), self.opponent.height());
+                    self.disconnected = state.size() != size || self.opponent.load_state(*state).is_err();
+                }
+                Ok(Some(Message::Hello { .. })) => {}
+                Ok(Some(Message::Bye)) | Err(_) => self.disconnected = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Color, Piece, PlayerInput};
+
+    /// The host's and the joining player's ends of a match over loopback.
+    fn connected(options: GameOptions) -> (NetMatch, NetMatch) {
+        connected_with(options.clone(), options)
+    }
+
+    /// [`connected`], with each end starting from its own options.
+    fn connected_with(host_options: GameOptions, joiner_options: GameOptions) -> (NetMatch, NetMatch) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let joiner = thread::spawn(move || Connection::connect(addr).unwrap());
+        let host = Connection::new(listener.accept().unwrap().0).unwrap();
+        let host = NetMatch::host(host, 4, 8, host_options).unwrap();
+        let joiner = NetMatch::join(joiner.join().unwrap(), joiner_options, Duration::from_secs(5)).unwrap();
+        (host, joiner)
+    }
+
+    /// Updates `net` until `done` holds, giving messages time to arrive.
+    fn wait_for(net: &mut NetMatch, done: impl Fn(&NetMatch) -> bool) {
+        for _ in 0..500 {
+            net.update(Duration::ZERO);
+            if done(net) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("timed out");
+    }
+
+    #[test]
+    fn both_ends_deal_the_same_pieces_and_trade_garbage() {
+        // On a four-wide board every 4x2 slab clears two lines, sending one.
+        let rotations = vec![(4, vec![1; 8])];
+        let slab = Piece { name: "B".to_string(), rotations, color: Color(9, 9, 9), spawn_column: 0 };
+        let options = GameOptions {
+            pieces: vec![slab].into(),
+            garbage_delay: Duration::ZERO,
+            line_clear_delay: Duration::ZERO,
+            ..GameOptions::default()
+        };
+        let (mut host, mut joiner) = connected(options);
+        assert_eq!((joiner.game.seed(), joiner.game.width()), (host.game.seed(), 4));
+
+        host.game.queue_input(PlayerInput::HardDrop);
+        host.update(Duration::from_millis(1));
+        assert_eq!(host.sent(), 1);
+        wait_for(&mut joiner, |net| net.received() == 1);
+        assert_eq!(joiner.game.pending_garbage(), (1, 0));
+        wait_for(&mut joiner, |net| net.opponent.lines_cleared() == 2);
+
+        host.leave();
+        wait_for(&mut joiner, |net| net.outcome().is_some());
+        assert_eq!(joiner.outcome(), Some(Outcome::OpponentLeft));
+
+        // The joiner deals from the host's randomizer, whatever its own options say.
+        let pure = GameOptions { randomizer: RandomizerKind::Pure, ..GameOptions::default() };
+        let history = GameOptions { randomizer: RandomizerKind::History, ..GameOptions::default() };
+        let (host, joiner) = connected_with(pure, history);
+        assert_eq!(joiner.game.options().randomizer, RandomizerKind::Pure);
+        assert_eq!(joiner.game.upcoming(50), host.game.upcoming(50));
+    }
+
+    #[test]
+    fn boards_the_match_could_not_be_played_on_are_refused() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let joiner = thread::spawn(move || Connection::connect(addr).unwrap());
+        let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+        let hello = Message::Hello { width: 0, height: usize::MAX, seed: 1, randomizer: RandomizerKind::Bag7 };
+        host.send(&hello).unwrap();
+        let joined = NetMatch::join(joiner.join().unwrap(), GameOptions::default(), Duration::from_secs(5));
+        assert_eq!(joined.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+
+        // A snapshot of a board of another size ends the match.
+        let (mut host, mut joiner) = connected(GameOptions::default());
+        host.send(&Message::Board { state: Box::new(Game::new(5, 8, GameOptions::default()).to_state()) });
+        wait_for(&mut joiner, |net| net.outcome().is_some());
+        assert_eq!(joiner.outcome(), Some(Outcome::OpponentLeft));
+
+        // So does garbage no clear could send.
+        let (mut host, mut joiner) = connected(GameOptions::default());
+        host.send(&Message::Garbage { lines: u32::MAX });
+        host.send(&Message::Garbage { lines: u32::MAX });
+        wait_for(&mut joiner, |net| net.outcome().is_some());
+        assert_eq!((joiner.received(), joiner.game.pending_garbage()), (0, (0, 0)));
+
+        // So does a line longer than any message, without waiting for it to end.
+        let (mut host, mut joiner) = connected(GameOptions::default());
+        host.connection.stream.write_all(&vec![b'['; MAX_MESSAGE_BYTES as usize + 1]).unwrap();
+        wait_for(&mut joiner, |net| net.outcome().is_some());
+    }
+}
@@ -0,0 +1,1754 @@
+//! Output-agnostic drawing: the screen layout of a game expressed against a small
+//! [`Renderer`] trait, plus a [`StringRenderer`] that captures frames as plain text
+//! and a [`FrameBuffer`] that works out what changed between frames.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::game::{Color, Game, GameMode, GameOverReason, GravityDir, PauseSelection, Piece, SPAWN_ROTATION};
+
+/// Colors the layout can ask for. Renderers map them to whatever their target supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paint {
+    Rgb(Color),
+    White,
+    DarkGrey,
+    Yellow,
+    Red,
+    Green,
+    Cyan,
+    Magenta,
+}
+
+/// Color palette and block glyphs for the pieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// The original bright colors with solid blocks.
+    #[default]
+    Classic,
+    /// Softer versions of the classic colors.
+    Pastel,
+    /// No color at all; each piece has its own fill pattern.
+    Monochrome,
+    /// Okabe-Ito hues, which stay distinct under common color blindness, plus the
+    /// monochrome patterns.
+    Colorblind,
+}
+
+const PASTEL_COLORS: [Color; 7] = [
+    Color(160, 240, 240),
+    Color(250, 240, 160),
+    Color(210, 170, 240),
+    Color(250, 200, 150),
+    Color(160, 180, 250),
+    Color(170, 240, 170),
+    Color(250, 160, 160),
+];
+const COLORBLIND_COLORS: [Color; 7] = [
+    Color(86, 180, 233),
+    Color(240, 228, 66),
+    Color(204, 121, 167),
+    Color(230, 159, 0),
+    Color(0, 114, 178),
+    Color(0, 158, 115),
+    Color(213, 94, 0),
+];
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::Classic, Theme::Pastel, Theme::Monochrome, Theme::Colorblind];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::Pastel => "pastel",
+            Theme::Monochrome => "monochrome",
+            Theme::Colorblind => "colorblind",
+        }
+    }
+
+    /// Color and glyph from `glyphs` for blocks of piece `piece_id` of `pieces`. Piece
+    /// sets bigger than the standard seven reuse the theme's styles in turn.
+    pub fn piece_style(self, pieces: &[Piece], piece_id: usize, glyphs: &Glyphs) -> (Paint, &'static str) {
+        let style = piece_id % glyphs.patterns.len();
+        match self {
+            Theme::Classic => (Paint::Rgb(pieces[piece_id].color), glyphs.block),
+            Theme::Pastel => (Paint::Rgb(PASTEL_COLORS[style]), glyphs.block),
+            Theme::Monochrome => (Paint::White, glyphs.patterns[style]),
+            Theme::Colorblind => (Paint::Rgb(COLORBLIND_COLORS[style]), glyphs.patterns[style]),
+        }
+    }
+
+    /// Color and glyph for a locked board cell. The board stores each piece's classic
+    /// color, which identifies the piece; other colors are drawn as plain blocks.
+    pub fn cell_style(self, pieces: &[Piece], color: Color, glyphs: &Glyphs) -> (Paint, &'static str) {
+        match pieces.iter().position(|piece| piece.color == color) {
+            Some(piece_id) => self.piece_style(pieces, piece_id, glyphs),
+            None if self == Theme::Monochrome => (Paint::White, glyphs.block),
+            None => (Paint::Rgb(color), glyphs.block),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Theme::ALL
+            .into_iter()
+            .find(|theme| theme.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Theme::ALL.iter().map(|theme| theme.name()).collect();
+                format!("unknown theme `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// How blocks are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderStyle {
+    /// Every cell is a solid block (or the theme's pattern).
+    #[default]
+    Blocks,
+    /// Each piece, and each same-colored region of the stack, is drawn as an outline.
+    Outline,
+}
+
+impl RenderStyle {
+    pub const ALL: [RenderStyle; 2] = [RenderStyle::Blocks, RenderStyle::Outline];
+
+    /// Name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderStyle::Blocks => "blocks",
+            RenderStyle::Outline => "outline",
+        }
+    }
+}
+
+impl FromStr for RenderStyle {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        RenderStyle::ALL
+            .into_iter()
+            .find(|style| style.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = RenderStyle::ALL.iter().map(|style| style.name()).collect();
+                format!("unknown render style `{}` (expected one of: {})", name, names.join(", "))
+            })
+    }
+}
+
+/// The two columns of an outlined cell, drawn with `lines`. `joined(dx, dy)` tells
+/// whether the neighbor in that direction belongs to the same region; edges to anything
+/// else get a border.
+fn outline_glyph(lines: &OutlineGlyphs, joined: impl Fn(isize, isize) -> bool) -> String {
+    let (top, bottom) = (!joined(0, -1), !joined(0, 1));
+    let half = |side: bool, corners: [char; 3]| match (side, top, bottom) {
+        (true, true, true) => corners[0],
+        (true, true, false) => corners[1],
+        (true, false, true) => corners[2],
+        (true, false, false) => lines.side,
+        (false, true, true) => lines.closed,
+        (false, true, false) | (false, false, true) => lines.edge,
+        (false, false, false) => ' ',
+    };
+    [half(!joined(-1, 0), lines.left), half(!joined(1, 0), lines.right)].iter().collect()
+}
+
+/// The glyph for the block at (`x`, `y`) of a piece made of `blocks`.
+fn piece_glyph(
+    style: RenderStyle,
+    glyphs: &Glyphs,
+    blocks: &[(isize, isize)],
+    x: isize,
+    y: isize,
+    glyph: &str,
+) -> String {
+    match style {
+        RenderStyle::Blocks => glyph.to_string(),
+        RenderStyle::Outline => outline_glyph(&glyphs.outline, |dx, dy| blocks.contains(&(x + dx, y + dy))),
+    }
+}
+
+/// A drawing target. Coordinates are terminal cells: `x` is the column, `y` the row.
+pub trait Renderer {
+    /// Starts a new frame with a blank screen.
+    fn clear(&mut self) -> io::Result<()>;
+    /// Draws one board cell; `glyph` is two columns wide.
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, paint: Paint) -> io::Result<()>;
+    /// Draws a string starting at the given position.
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, paint: Paint) -> io::Result<()>;
+    /// Finishes the frame, making everything drawn since `clear` visible.
+    fn present(&mut self) -> io::Result<()>;
+    /// Size of the drawing area as (columns, rows); the side panel is trimmed to fit.
+    fn size(&self) -> io::Result<(u16, u16)>;
+}
+
+/// Passes everything through drawn in white, for high-contrast mode.
+struct HighContrast<'a, R: Renderer>(&'a mut R);
+
+impl<R: Renderer> Renderer for HighContrast<'_, R> {
+    fn clear(&mut self) -> io::Result<()> {
+        self.0.clear()
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, _paint: Paint) -> io::Result<()> {
+        self.0.draw_cell(x, y, glyph, Paint::White)
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, _paint: Paint) -> io::Result<()> {
+        self.0.draw_text(x, y, text, Paint::White)
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        self.0.present()
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        self.0.size()
+    }
+}
+
+/// Box-drawing characters for a rectangular frame.
+pub struct FrameGlyphs {
+    top: [&'static str; 3],
+    side: &'static str,
+    bottom: [&'static str; 3],
+}
+
+/// Characters for outlined cells: the left and right halves of a cell closed at the
+/// top and bottom, open below and open above; a side on its own; a cell closed at the
+/// top and bottom; and a single top or bottom edge.
+pub struct OutlineGlyphs {
+    left: [char; 3],
+    right: [char; 3],
+    side: char,
+    closed: char,
+    edge: char,
+}
+
+/// Everything [`draw_game`] draws that isn't plain text, so the whole screen can switch
+/// to characters a terminal without Unicode can show. Block glyphs are two columns wide.
+pub struct Glyphs {
+    frame: FrameGlyphs,
+    /// The frame in high-contrast mode.
+    heavy_frame: FrameGlyphs,
+    block: &'static str,
+    /// Fill patterns per piece id for themes that can't rely on color alone.
+    patterns: [&'static str; 7],
+    /// Every other empty board cell, making a checkerboard.
+    background: &'static str,
+    ghost: &'static str,
+    /// A ghost that still stands out once everything is white.
+    high_contrast_ghost: &'static str,
+    hole: &'static str,
+    /// One row of the incoming garbage meter, a single column wide.
+    meter: &'static str,
+    outline: OutlineGlyphs,
+}
+
+/// Box drawing and block elements, for any terminal with a Unicode font.
+pub const UNICODE_GLYPHS: Glyphs = Glyphs {
+    frame: FrameGlyphs { top: ["╔", "═", "╗"], side: "║", bottom: ["╚", "═", "╝"] },
+    heavy_frame: FrameGlyphs { top: ["┏", "━", "┓"], side: "┃", bottom: ["┗", "━", "┛"] },
+    block: "██",
+    patterns: ["██", "▓▓", "[]", "<>", "()", "{}", "##"],
+    background: "··",
+    ghost: "▒▒",
+    high_contrast_ghost: "░░",
+    hole: "▒▒",
+    meter: "█",
+    outline: OutlineGlyphs { left: ['[', '┌', '└'], right: [']', '┐', '┘'], side: '│', closed: '═', edge: '─' },
+};
+
+/// Plain ASCII, for consoles that garble anything else.
+pub const ASCII_GLYPHS: Glyphs = Glyphs {
+    frame: FrameGlyphs { top: ["+", "-", "+"], side: "|", bottom: ["+", "-", "+"] },
+    heavy_frame: FrameGlyphs { top: ["#", "=", "#"], side: "#", bottom: ["#", "=", "#"] },
+    block: "[]",
+    patterns: ["[]", "##", "%%", "<>", "()", "{}", "@@"],
+    background: ". ",
+    ghost: "::",
+    high_contrast_ghost: "::",
+    hole: "xx",
+    meter: "#",
+    outline: OutlineGlyphs { left: ['[', '+', '+'], right: [']', '+', '+'], side: '|', closed: '=', edge: '-' },
+};
+
+impl Glyphs {
+    /// The glyphs `game` was started with.
+    pub fn of(game: &Game) -> &'static Glyphs {
+        if game.options().ascii { &ASCII_GLYPHS } else { &UNICODE_GLYPHS }
+    }
+}
+
+impl FrameGlyphs {
+    /// The top edge of a frame around `inside` columns.
+    fn top_edge(&self, inside: usize) -> String {
+        format!("{}{}{}", self.top[0], self.top[1].repeat(inside), self.top[2])
+    }
+
+    /// The bottom edge of a frame around `inside` columns.
+    fn bottom_edge(&self, inside: usize) -> String {
+        format!("{}{}{}", self.bottom[0], self.bottom[1].repeat(inside), self.bottom[2])
+    }
+}
+
+const BOARD_TOP_Y: u16 = 1;
+const BOARD_LEFT_X: u16 = 1;
+/// Columns the side panel needs, starting three columns right of the board frame.
+const PANEL_WIDTH: u16 = 16;
+
+/// How many times bigger than normal each cell is drawn: 2 in big mode, where a cell
+/// is its glyph repeated in a 2x2 block.
+fn cell_scale(game: &Game) -> usize {
+    if game.options().big { 2 } else { 1 }
+}
+
+/// Size of the board on screen in cells, as (columns, rows): sideways gravity turns it.
+fn board_screen_size(game: &Game) -> (usize, usize) {
+    game.options().gravity_dir.screen_size(game.width(), game.height())
+}
+
+/// Extra panel rows taken by the edges of the high-contrast score box.
+fn score_box_rows(game: &Game) -> u16 {
+    if game.options().high_contrast { 2 } else { 0 }
+}
+
+/// Rows used by each piece in the next queue: the preview box plus a gap, so three for
+/// the standard pieces.
+fn preview_rows(game: &Game) -> u16 {
+    (preview_box(game.pieces(), game.options().gravity_dir).1 * cell_scale(game)) as u16 + 1
+}
+
+/// Size in cells of the box each next and hold piece is centered in: four columns, or as
+/// wide as the widest piece set's spawn rotation, and as tall as the tallest, once
+/// turned by `gravity_dir`.
+fn preview_box(pieces: &[Piece], gravity_dir: GravityDir) -> (usize, usize) {
+    let sizes = pieces.iter().map(|piece| {
+        let (width, height) = piece.size(SPAWN_ROTATION);
+        gravity_dir.screen_size(width, height)
+    });
+    let (width, height) = sizes.fold((4, 0), |(w, h), (pw, ph)| (w.max(pw), h.max(ph)));
+    (width, height)
+}
+
+/// The filled cells of `piece` in its spawn rotation, relative to their bounding box,
+/// and where that box goes inside a preview box of `box_size` cells to be centered:
+/// (terminal columns at normal scale, rows). Empty edge rows and columns in the bitmap
+/// don't count, and columns are centered to the half cell, so a three-wide piece sits
+/// one column in from the left of a four-wide box. The cells are flipped if `mirror`
+/// and then turned by `gravity_dir`, as the board is, so the preview shows the piece
+/// the way it will appear.
+fn preview_layout(
+    piece: &Piece,
+    box_size: (usize, usize),
+    mirror: bool,
+    gravity_dir: GravityDir,
+) -> (Vec<(isize, isize)>, (u16, u16)) {
+    let (width, bitmap) = &piece.rotations[SPAWN_ROTATION];
+    let cells: Vec<(isize, isize)> = bitmap
+        .iter()
+        .enumerate()
+        .filter(|&(_, &cell)| cell == 1)
+        .map(|(i, _)| ((i % width) as isize, (i / width) as isize))
+        .collect();
+    let left = cells.iter().map(|&(x, _)| x).min().unwrap_or(0);
+    let top = cells.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let right = cells.iter().map(|&(x, _)| x).max().unwrap_or(0);
+    let bottom = cells.iter().map(|&(_, y)| y).max().unwrap_or(0);
+    let (width, height) = ((right - left + 1) as usize, (bottom - top + 1) as usize);
+    let cells = cells.into_iter().map(|(x, y)| {
+        let x = if mirror { right - x } else { x - left };
+        gravity_dir.to_screen((x, y - top), width, height)
+    });
+    let (cells_wide, cells_high) = gravity_dir.screen_size(width, height);
+    let offset = (
+        box_size.0.saturating_sub(cells_wide) as u16,
+        (box_size.1.saturating_sub(cells_high) / 2) as u16,
+    );
+    (cells.collect(), offset)
+}
+
+/// The stats block: piece totals and rate, pieces dealt by shape, clears by size, then
+/// points by where they came from.
+fn stats_lines(game: &Game) -> Vec<String> {
+    let stats = game.stats();
+    let mut lines = vec![
+        format!("Pieces {}", stats.total_pieces()),
+        format!("PPS {:.2}", game.pieces_per_second()),
+        format!("Faults {}", stats.finesse_faults),
+    ];
+    for pair in game.pieces().iter().zip(&stats.pieces).collect::<Vec<_>>().chunks(2) {
+        let line: Vec<String> = pair.iter().map(|(piece, count)| format!("{:.1} {:<4}", piece.name, count)).collect();
+        lines.push(line.join(" ").trim_end().to_string());
+    }
+    let [singles, doubles, triples, tetrises] = stats.clears;
+    lines.push(format!("Sgl {:<3} Dbl {}", singles, doubles));
+    lines.push(format!("Tpl {:<3} Tet {}", triples, tetrises));
+    let points = stats.points;
+    lines.push(format!("Clears {}", points.clears));
+    lines.push(format!("T-spins {}", points.t_spins));
+    lines.push(format!("Combos {}", points.combos));
+    if points.cascades > 0 {
+        lines.push(format!("Cascades {}", points.cascades));
+    }
+    if points.perfect_clears > 0 {
+        lines.push(format!("Perfect {}", points.perfect_clears));
+    }
+    lines.push(format!("Soft drop {}", points.soft_drop));
+    lines.push(format!("Hard drop {}", points.hard_drop));
+    lines
+}
+
+/// Remaining ultra time below which the countdown is drawn in red.
+const ULTRA_WARNING_TIME: Duration = Duration::from_secs(10);
+
+/// How long each on/off phase of the line-clear flash lasts.
+const FLASH_INTERVAL_MS: u128 = 50;
+/// How long each on/off phase of the danger warning lasts.
+const DANGER_FLASH_MS: u128 = 250;
+
+/// Whether the stack has reached the top rows the game's options call dangerous.
+fn in_danger(game: &Game) -> bool {
+    let rows = game.options().danger_rows;
+    rows > 0 && !game.is_game_over() && game.max_stack_height() > game.height().saturating_sub(rows)
+}
+
+/// How a game is arranged on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+    /// The board with the side panel beside it.
+    Full,
+    /// The board alone, with the score, level and next piece written over the top of
+    /// its frame and a one-line hint below it, so it is never taller than the full
+    /// layout. Used with `--compact`, or when the full layout doesn't fit.
+    Compact,
+}
+
+/// Smallest screen, as (columns, rows), that fits `game` laid out as `layout`.
+fn layout_size(game: &Game, layout: Layout) -> (u16, u16) {
+    let scale = cell_scale(game);
+    let (board_width, board_height) = board_screen_size(game);
+    let (board_columns, board_rows) = ((board_width * 2 * scale) as u16, (board_height * scale) as u16);
+    match layout {
+        Layout::Full => {
+            // Big previews of wide pieces can outgrow the panel's usual width.
+            let preview_columns = preview_box(game.pieces(), game.options().gravity_dir).0;
+            let panel_width = PANEL_WIDTH.max((preview_columns * 2 * scale) as u16);
+            // The side panel needs room for one queued piece and the hold box.
+            let panel_rows = 8 + score_box_rows(game) + 2 * preview_rows(game) + 1;
+            (board_columns + 5 + panel_width, (board_rows + 3).max(panel_rows))
+        }
+        // The frame, the garbage meter beside it, and the hint line.
+        Layout::Compact => (board_columns + 4, board_rows + 3),
+    }
+}
+
+/// The layouts `game` may use, preferred first.
+fn layouts(game: &Game) -> &'static [Layout] {
+    if game.options().compact { &[Layout::Compact] } else { &[Layout::Full, Layout::Compact] }
+}
+
+/// The first of `game`'s layouts that fits a `columns` by `rows` screen.
+fn choose_layout(game: &Game, columns: u16, rows: u16) -> Option<Layout> {
+    layouts(game).iter().copied().find(|&layout| {
+        let (min_columns, min_rows) = layout_size(game, layout);
+        columns >= min_columns && rows >= min_rows
+    })
+}
+
+/// Smallest screen, as (columns, rows), that fits the board, its status line and the side
+/// panel, or just the board and its hint line with `--compact`.
+pub fn min_screen_size(game: &Game) -> (u16, u16) {
+    layout_size(game, layouts(game)[0])
+}
+
+/// Whether a screen of `columns` by `rows` is big enough to play `game` on, in the
+/// compact layout if not the full one.
+pub fn fits_screen(game: &Game, columns: u16, rows: u16) -> bool {
+    choose_layout(game, columns, rows).is_some()
+}
+
+/// What the game screen says about the keys: the help lines listed under "Controls" in
+/// the side panel, and the one-line hint the compact layout shows instead.
+#[derive(Debug, Clone, Default)]
+pub struct Controls {
+    pub lines: Vec<String>,
+    pub hint: String,
+}
+
+/// Draws a full frame: the board with its pieces, the side panel, and any overlays,
+/// centered on the screen, with `controls` in the panel or under the board. A screen
+/// too narrow or short for the panel gets the compact layout instead, and one too
+/// small for that only gets a message asking for more room.
+pub fn draw_game<R: Renderer>(game: &Game, controls: &Controls, r: &mut R) -> io::Result<()> {
+    draw_frame(game, controls, None, r)
+}
+
+/// [`draw_game`] with the falling piece drawn where `fall` has eased it to.
+pub fn draw_game_smooth<R: Renderer>(game: &Game, controls: &Controls, fall: &SmoothFall, r: &mut R) -> io::Result<()> {
+    draw_frame(game, controls, Some(fall.row(game)), r)
+}
+
+/// [`draw_game`], drawing the active piece at `piece_row` instead of its own row if given.
+fn draw_frame<R: Renderer>(game: &Game, controls: &Controls, piece_row: Option<isize>, r: &mut R) -> io::Result<()> {
+    let (columns, rows) = r.size()?;
+    if !fits_screen(game, columns, rows) {
+        let sizes: Vec<_> = layouts(game).iter().map(|&layout| layout_size(game, layout)).collect();
+        return draw_too_small(r, &sizes);
+    }
+    r.clear()?;
+    draw_region(game, controls, piece_row, r, 0, columns, rows)?;
+    r.present()
+}
+
+/// How long the drawn piece takes to close about two thirds of the gap to its real row.
+const SMOOTH_FALL_TIME: f32 = 0.03;
+
+/// Where the falling piece is drawn with `--smooth`: a fractional row that eases down
+/// toward the piece's real row, so a piece falling several rows per frame passes
+/// through each of them on screen. Only drawing uses it; the game never sees it.
+#[derive(Debug, Clone, Default)]
+pub struct SmoothFall {
+    visual_y: f32,
+    /// The piece being followed, as its id and the number of pieces dealt when it
+    /// appeared; a new piece starts at its own row.
+    piece: Option<(usize, u32)>,
+}
+
+impl SmoothFall {
+    /// Eases the drawn row `elapsed` further toward the active piece's row. A new piece,
+    /// or one that moved up, is drawn at its real row straight away.
+    pub fn advance(&mut self, game: &Game, elapsed: Duration) {
+        let piece = Some((game.active_piece().id, game.stats().total_pieces()));
+        let target = game.active_piece().y as f32;
+        if self.piece != piece || target < self.visual_y {
+            self.piece = piece;
+            self.visual_y = target;
+            return;
+        }
+        self.visual_y += (target - self.visual_y) * (1.0 - (-elapsed.as_secs_f32() / SMOOTH_FALL_TIME).exp());
+    }
+
+    /// The row to draw the active piece at: the eased row, or the real one if the piece
+    /// would overlap the stack up there.
+    pub fn row(&self, game: &Game) -> isize {
+        let mut piece = game.active_piece().clone();
+        piece.y = (self.visual_y.round() as isize).min(piece.y);
+        if game.check_collision(&piece) { game.active_piece().y } else { piece.y }
+    }
+}
+
+/// Smallest screen, as (columns, rows), that fits `games` side by side in equal shares
+/// of its width.
+pub fn min_split_size(games: &[&Game]) -> (u16, u16) {
+    let (columns, rows) = games
+        .iter()
+        .map(|game| min_screen_size(game))
+        .fold((0, 0), |(columns, rows), (c, r)| (columns.max(c), rows.max(r)));
+    (columns * games.len() as u16, rows)
+}
+
+/// Draws a frame of several games side by side, each laid out as [`draw_game`] would in
+/// an equal share of the screen's width, with its own `controls`. A screen too
+/// small for all of them only gets a message asking for more room.
+pub fn draw_split<R: Renderer>(games: &[&Game], controls: &[Controls], r: &mut R) -> io::Result<()> {
+    let (columns, rows) = r.size()?;
+    let (min_columns, min_rows) = min_split_size(games);
+    if columns < min_columns || rows < min_rows {
+        return draw_too_small(r, &[(min_columns, min_rows)]);
+    }
+    r.clear()?;
+    let share = columns / games.len().max(1) as u16;
+    for (i, (game, controls)) in games.iter().zip(controls).enumerate() {
+        draw_region(game, controls, None, r, share * i as u16, share, rows)?;
+    }
+    r.present()
+}
+
+/// The whole frame when the screen is smaller than every layout's minimum size, listed
+/// in `sizes` with the preferred one first.
+fn draw_too_small<R: Renderer>(r: &mut R, sizes: &[(u16, u16)]) -> io::Result<()> {
+    let (columns, rows) = r.size()?;
+    r.clear()?;
+    r.draw_text(0, 0, "Terminal too small", Paint::Red)?;
+    for (i, (min_columns, min_rows)) in sizes.iter().enumerate() {
+        let line = match i {
+            0 => format!("Need {}x{}, have {}x{}", min_columns, min_rows, columns, rows),
+            _ => format!("  or {}x{}", min_columns, min_rows),
+        };
+        r.draw_text(0, 1 + i as u16, &line, Paint::White)?;
+    }
+    r.present()
+}
+
+/// Lays `game` out centered in the `columns` by `rows` part of `r` that starts at
+/// column `left`, which should fit it.
+fn draw_region<R: Renderer>(
+    game: &Game,
+    controls: &Controls,
+    piece_row: Option<isize>,
+    r: &mut R,
+    left: u16,
+    columns: u16,
+    rows: u16,
+) -> io::Result<()> {
+    let (layout, dx, dy) = arrange(game, columns, rows);
+    let mut region = Region { inner: r, dx: left + dx, dy, columns: columns - dx, rows: rows - dy };
+    if game.options().high_contrast {
+        draw_layout(game, controls, piece_row, layout, &mut HighContrast(&mut region))
+    } else {
+        draw_layout(game, controls, piece_row, layout, &mut region)
+    }
+}
+
+/// The layout [`draw_game`] uses on a `columns` by `rows` screen and how far it shifts
+/// it to center it there. A screen too small for any layout gets the preferred one
+/// in the top-left corner.
+fn arrange(game: &Game, columns: u16, rows: u16) -> (Layout, u16, u16) {
+    let layout = choose_layout(game, columns, rows).unwrap_or(layouts(game)[0]);
+    let (min_columns, min_rows) = layout_size(game, layout);
+    (layout, columns.saturating_sub(min_columns) / 2, rows.saturating_sub(min_rows) / 2)
+}
+
+/// The board cell, as (column, row), drawn at screen position (`x`, `y`) by
+/// [`draw_game`] on a `columns` by `rows` screen; `None` outside the board.
+pub fn board_cell_at(game: &Game, columns: u16, rows: u16, x: u16, y: u16) -> Option<(usize, usize)> {
+    if !fits_screen(game, columns, rows) {
+        return None;
+    }
+    let (_, dx, dy) = arrange(game, columns, rows);
+    // Board cells start just inside the frame and are two characters wide, both ways
+    // doubled in big mode.
+    let scale = cell_scale(game);
+    let column = x.checked_sub(dx + BOARD_LEFT_X + 1)? as usize / (2 * scale);
+    let row = y.checked_sub(dy + BOARD_TOP_Y)? as usize / scale;
+    let (board_width, board_height) = board_screen_size(game);
+    if column >= board_width || row >= board_height {
+        return None;
+    }
+    let (width, height) = (game.width(), game.height());
+    let (column, row) = game.options().gravity_dir.from_screen((column as isize, row as isize), width, height);
+    let column = if game.options().mirror { width as isize - 1 - column } else { column };
+    Some((column as usize, row as usize))
+}
+
+/// Where [`draw_game`] puts the middle of the board on a `columns` by `rows` screen, for
+/// drawing over it; `None` if the screen is too small for the layout.
+pub fn board_center(game: &Game, columns: u16, rows: u16) -> Option<(u16, u16)> {
+    if !fits_screen(game, columns, rows) {
+        return None;
+    }
+    let (_, dx, dy) = arrange(game, columns, rows);
+    let scale = cell_scale(game);
+    let (board_width, board_height) = board_screen_size(game);
+    let (x, y) = (BOARD_LEFT_X + 1 + (board_width * scale) as u16, BOARD_TOP_Y + (board_height * scale / 2) as u16);
+    Some((dx + x, dy + y))
+}
+
+/// Moves everything drawn through it right by `dx` and down by `dy`, and reports its
+/// size as `columns` by `rows`.
+struct Region<'a, R: Renderer> {
+    inner: &'a mut R,
+    dx: u16,
+    dy: u16,
+    columns: u16,
+    rows: u16,
+}
+
+impl<R: Renderer> Renderer for Region<'_, R> {
+    fn clear(&mut self) -> io::Result<()> {
+        self.inner.clear()
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, paint: Paint) -> io::Result<()> {
+        self.inner.draw_cell(x + self.dx, y + self.dy, glyph, paint)
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, paint: Paint) -> io::Result<()> {
+        self.inner.draw_text(x + self.dx, y + self.dy, text, paint)
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        self.inner.present()
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok((self.columns, self.rows))
+    }
+}
+
+/// Draws `layout` with the board frame in the top-left corner of `r`, over whatever
+/// is already there. The active piece is drawn at `piece_row` if given.
+fn draw_layout<R: Renderer>(
+    game: &Game,
+    controls: &Controls,
+    piece_row: Option<isize>,
+    layout: Layout,
+    r: &mut R,
+) -> io::Result<()> {
+    let width = game.width();
+    let height = game.height();
+    let board_top_y = BOARD_TOP_Y;
+    let board_left_x = BOARD_LEFT_X;
+    let scale = cell_scale(game);
+    let (screen_width, screen_height) = board_screen_size(game);
+    // Size of the inside of the frame in screen characters.
+    let (board_columns, board_rows) = (screen_width * 2 * scale, screen_height * scale);
+
+    // High contrast swaps in heavy borders and drops the checkerboard behind the stack.
+    let glyphs = Glyphs::of(game);
+    let high_contrast = game.options().high_contrast;
+    let frame = if high_contrast { &glyphs.heavy_frame } else { &glyphs.frame };
+    // A stack in the danger zone turns the frame red and, unless the compact layout's
+    // info line is there, puts a warning in its top edge.
+    let danger = in_danger(game);
+    let border = if danger { Paint::Red } else { Paint::White };
+    r.draw_text(board_left_x, board_top_y - 1, &frame.top_edge(board_columns), border)?;
+    for row in 0..board_rows {
+        let row_y = board_top_y + row as u16;
+        r.draw_text(board_left_x, row_y, frame.side, border)?;
+        for x in 0..screen_width {
+            let checkered = !high_contrast && (x + row / scale).is_multiple_of(2);
+            let bg_glyph = if checkered { glyphs.background } else { "  " };
+            r.draw_cell(board_left_x + 1 + (x * 2 * scale) as u16, row_y, &bg_glyph.repeat(scale), Paint::DarkGrey)?;
+        }
+        r.draw_text(board_left_x + 1 + board_columns as u16, row_y, frame.side, border)?;
+    }
+    r.draw_text(board_left_x, board_top_y + board_rows as u16, &frame.bottom_edge(board_columns), border)?;
+    let lit = !game.options().danger_flash || (game.play_time().as_millis() / DANGER_FLASH_MS).is_multiple_of(2);
+    if danger && lit && layout == Layout::Full && board_columns >= "DANGER".len() {
+        let x = board_left_x + 1 + ((board_columns - "DANGER".len()) / 2) as u16;
+        r.draw_text(x, board_top_y - 1, "DANGER", Paint::Red)?;
+    }
+
+    // Draws a cell's glyph `scale` times across and down at board cell (x, y).
+    let draw_scaled = |r: &mut R, left: u16, top: u16, x: isize, y: isize, glyph: &str, paint: Paint| {
+        let glyph = glyph.repeat(scale);
+        let s = scale as isize;
+        for dy in 0..s {
+            r.draw_cell((left as isize + x * 2 * s) as u16, (top as isize + y * s + dy) as u16, &glyph, paint)?;
+        }
+        io::Result::Ok(())
+    };
+    let draw_block = |r: &mut R, x: isize, y: isize, glyph: &str, paint: Paint| -> io::Result<()> {
+        draw_scaled(r, board_left_x + 1, board_top_y, x, y, glyph, paint)
+    };
+    // With --mirror the board is flipped left to right, and with --gravity-dir it is then
+    // turned: board cell (x, y) shows at screen cell `place(x, y)`, and `unplace` goes
+    // back. Pieces are placed as a whole before their outlines are worked out.
+    let mirror = game.options().mirror;
+    let gravity_dir = game.options().gravity_dir;
+    let flip = |x: isize| if mirror { width as isize - 1 - x } else { x };
+    let place = |x: isize, y: isize| gravity_dir.to_screen((flip(x), y), width, height);
+    let unplace = |x: isize, y: isize| {
+        let (x, y) = gravity_dir.from_screen((x, y), width, height);
+        (flip(x), y)
+    };
+    let place_blocks = |blocks: &mut Vec<(isize, isize)>| blocks.iter_mut().for_each(|(x, y)| (*x, *y) = place(*x, *y));
+
+    // Without color to tell pieces apart, high contrast needs a theme with patterns.
+    let theme = match game.options().theme {
+        Theme::Classic | Theme::Pastel if high_contrast => Theme::Monochrome,
+        theme => theme,
+    };
+    let style = game.options().render_style;
+    let lock_flash = game.lock_flash();
+    for (i, cell) in game.board().iter().enumerate() {
+        if let (Some(color), true) = (cell, game.cell_visible(i)) {
+            let (paint, glyph) = theme.cell_style(game.pieces(), *color, glyphs);
+            // A piece that just locked shows white for a moment before taking its colour.
+            let paint = if lock_flash.contains(&i) { Paint::White } else { paint };
+            let (x, y) = place((i % width) as isize, (i / width) as isize);
+            let glyph = match style {
+                RenderStyle::Blocks => glyph.to_string(),
+                RenderStyle::Outline => outline_glyph(&glyphs.outline, |dx, dy| {
+                    let (nx, ny) = unplace(x + dx, y + dy);
+                    let inside = (0..width as isize).contains(&nx) && (0..height as isize).contains(&ny);
+                    inside && game.board()[ny as usize * width + nx as usize] == Some(*color)
+                }),
+            };
+            draw_block(r, x, y, &glyph, paint)?;
+        }
+    }
+
+    // Rows being cleared alternate between solid white and blank.
+    let clearing = game.clearing_rows();
+    if let Some((rows, elapsed)) = &clearing {
+        let glyph = if (elapsed.as_millis() / FLASH_INTERVAL_MS).is_multiple_of(2) { glyphs.block } else { "  " };
+        for &y in rows {
+            for x in 0..width {
+                let (x, y) = place(x as isize, y as isize);
+                draw_block(r, x, y, glyph, Paint::White)?;
+            }
+        }
+    }
+
+    // The teaching overlay: holes stand out in red and each column's height sits in the
+    // frame beneath it. Sideways, the columns run across the screen and go unlabelled.
+    if game.heights_visible() {
+        for (x, y) in game.holes() {
+            let (x, y) = place(x as isize, y as isize);
+            draw_block(r, x, y, glyphs.hole, Paint::Red)?;
+        }
+        let labelled = if gravity_dir.is_sideways() { 0 } else { width };
+        for (x, column_height) in game.column_heights().into_iter().enumerate().take(labelled) {
+            let label = format!("{:^1$}", column_height, 2 * scale);
+            let label_x = board_left_x + 1 + (place(x as isize, 0).0 as usize * 2 * scale) as u16;
+            r.draw_text(label_x, board_top_y + board_rows as u16, &label, Paint::Cyan)?;
+        }
+    }
+
+    // Incoming garbage rises beside the board from the bottom: red for lines the next
+    // lock pushes in, yellow above them for lines still on their way.
+    let (ready, arriving) = game.pending_garbage();
+    let meter_x = board_left_x + 2 + board_columns as u16;
+    let meter = (0..ready).map(|_| Paint::Red).chain((0..arriving).map(|_| Paint::Yellow));
+    for (line, paint) in meter.take(screen_height).enumerate() {
+        for dy in 1..=scale {
+            r.draw_text(meter_x, board_top_y + (board_rows - line * scale - dy) as u16, glyphs.meter, paint)?;
+        }
+    }
+
+    // While rows clear, the locked piece is already part of the board.
+    let clearing = clearing.is_some();
+    if game.options().show_ghost && !game.is_game_over() && !game.is_paused() && !clearing {
+        let ghost = game.ghost_piece();
+        if ghost.y != game.active_piece().y {
+            let mut blocks: Vec<(isize, isize)> = ghost.blocks(game.pieces()).collect();
+            place_blocks(&mut blocks);
+            // The usual ghost glyph is too close to the patterns once everything is white.
+            let glyph = if high_contrast { glyphs.high_contrast_ghost } else { glyphs.ghost };
+            for &(x, y) in &blocks {
+                if unplace(x, y).1 >= 0 {
+                    draw_block(r, x, y, &piece_glyph(style, glyphs, &blocks, x, y, glyph), Paint::DarkGrey)?;
+                }
+            }
+        }
+    }
+
+    if !game.is_game_over() && !clearing {
+        let (paint, glyph) = theme.piece_style(game.pieces(), game.active_piece().id, glyphs);
+        let mut piece = game.active_piece().clone();
+        piece.y = piece_row.unwrap_or(piece.y);
+        let mut blocks: Vec<(isize, isize)> = piece.blocks(game.pieces()).collect();
+        place_blocks(&mut blocks);
+        // In high contrast the falling piece is outlined so it stands out from the patterned stack.
+        let style = if high_contrast { RenderStyle::Outline } else { style };
+        for &(x, y) in &blocks {
+            if unplace(x, y).1 >= 0 {
+                draw_block(r, x, y, &piece_glyph(style, glyphs, &blocks, x, y, glyph), paint)?;
+            }
+        }
+    }
+
+    if layout == Layout::Compact {
+        draw_info_line(game, theme, glyphs, board_columns, r)?;
+        return draw_messages(game, board_top_y, board_columns, board_rows, Some(&controls.hint), r);
+    }
+
+    let panel_x = (board_columns + 5) as u16;
+    // Sprints are about time, so the clock takes the score's place.
+    let sprint = matches!(game.options().mode, GameMode::Sprint { .. });
+    if let Some(remaining) = game.time_remaining() {
+        // The countdown sits above everything else and turns red near the end.
+        let paint = if remaining <= ULTRA_WARNING_TIME { Paint::Red } else { Paint::Yellow };
+        r.draw_text(panel_x, 0, &format!("Time {}", format_time(remaining)), paint)?;
+    } else if !sprint {
+        // Just for reference: how long this game has been going, pauses left out.
+        r.draw_text(panel_x, 0, &format!("Time {}", format_clock(game.play_time())), Paint::White)?;
+    }
+    if let Some(reveals) = game.reveals_left() {
+        r.draw_text(panel_x, 1, &format!("Reveals {}", reveals), Paint::Cyan)?;
+    }
+    // High contrast frames the score and level in a box, pushing the rest of the panel down.
+    let box_rows = score_box_rows(game);
+    let (score_y, level_y) = (2 + box_rows / 2, 4 + box_rows / 2);
+    if high_contrast {
+        let inside = (PANEL_WIDTH - 2) as usize;
+        r.draw_text(panel_x, 2, &frame.top_edge(inside), Paint::White)?;
+        for y in score_y..=level_y {
+            r.draw_text(panel_x, y, frame.side, Paint::White)?;
+            r.draw_text(panel_x + PANEL_WIDTH - 1, y, frame.side, Paint::White)?;
+        }
+        r.draw_text(panel_x, level_y + 1, &frame.bottom_edge(inside), Paint::White)?;
+    }
+    let text_x = if high_contrast { panel_x + 2 } else { panel_x };
+    if sprint {
+        r.draw_text(text_x, score_y, "Time", Paint::White)?;
+        r.draw_text(text_x, score_y + 1, &format_time(game.play_time()), Paint::Yellow)?;
+    } else {
+        r.draw_text(text_x, score_y, "Score", Paint::White)?;
+        r.draw_text(text_x, score_y + 1, &format!("{:0>8}", game.score()), Paint::Yellow)?;
+    }
+
+    let preview_box = preview_box(game.pieces(), gravity_dir);
+    // A dimmed preview shows a piece that can't be used yet.
+    let draw_preview = |r: &mut R, piece_id: usize, top_y: u16, dim: bool| -> io::Result<()> {
+        let (paint, glyph) = theme.piece_style(game.pieces(), piece_id, glyphs);
+        let paint = if dim { Paint::DarkGrey } else { paint };
+        let (blocks, (dx, dy)) = preview_layout(&game.pieces()[piece_id], preview_box, mirror, gravity_dir);
+        let (left, top) = (panel_x + dx * scale as u16, top_y + dy * scale as u16);
+        for &(x, y) in &blocks {
+            draw_scaled(r, left, top, x, y, &piece_glyph(style, glyphs, &blocks, x, y, glyph), paint)?;
+        }
+        Ok(())
+    };
+
+    r.draw_text(text_x, level_y, &format!("Level {}", game.level()), Paint::White)?;
+    let lines = match game.line_goal() {
+        Some(goal) => format!("Lines {}/{}", game.lines_cleared(), goal),
+        None => format!("Lines {}", game.lines_cleared()),
+    };
+    r.draw_text(panel_x, 5 + box_rows, &lines, Paint::White)?;
+    if game.combo() > 0 {
+        r.draw_text(panel_x, 6 + box_rows, &format!("Combo x{}", game.combo()), Paint::Magenta)?;
+    }
+    if game.back_to_back() {
+        r.draw_text(panel_x + 11, 6 + box_rows, "B2B", Paint::Cyan)?;
+    }
+
+    // Show as much of the queue as fits while leaving room for the hold box below it.
+    let (_, screen_rows) = r.size()?;
+    let queue_top = 8 + box_rows;
+    let preview_rows = preview_rows(game);
+    let fits = (screen_rows.saturating_sub(queue_top + preview_rows) / preview_rows).max(1) as usize;
+    let shown = game.options().preview_count.min(fits).max(1);
+    // A hidden queue keeps its space so the hold box doesn't jump when it is toggled.
+    if game.next_hidden() {
+        r.draw_text(panel_x, queue_top - 1, "Next (hidden)", Paint::DarkGrey)?;
+    } else {
+        r.draw_text(panel_x, queue_top - 1, "Next", Paint::White)?;
+        for (i, &piece_id) in game.next_queue().iter().take(shown).enumerate() {
+            draw_preview(r, piece_id, queue_top + i as u16 * preview_rows, false)?;
+        }
+    }
+
+    let hold_y = queue_top + shown as u16 * preview_rows;
+    // Until the active piece locks, pressing hold again does nothing; say so rather
+    // than ignoring the key silently.
+    let (hold_heading, heading_paint) = match game.holds_remaining() {
+        Some(0) => ("Hold (0 left)".to_string(), Paint::White),
+        _ if game.hold_locked() => ("HOLD LOCKED".to_string(), Paint::DarkGrey),
+        Some(holds) => (format!("Hold ({} left)", holds), Paint::White),
+        None => ("Hold".to_string(), Paint::White),
+    };
+    r.draw_text(panel_x, hold_y, &hold_heading, heading_paint)?;
+    if let Some(held_id) = game.held_piece_id() {
+        draw_preview(r, held_id, hold_y + 1, game.hold_locked())?;
+    }
+
+    let controls_y = hold_y + 1 + preview_rows;
+    let (heading, lines) = if game.stats_visible() {
+        ("Stats", stats_lines(game))
+    } else {
+        ("Controls", controls.lines.clone())
+    };
+    r.draw_text(panel_x, controls_y, heading, Paint::White)?;
+    for (i, line) in lines.iter().enumerate() {
+        let y = controls_y + 1 + i as u16;
+        if y >= screen_rows {
+            break;
+        }
+        r.draw_text(panel_x, y, line, Paint::White)?;
+    }
+    draw_messages(game, board_top_y, board_columns, board_rows, None, r)
+}
+
+/// The compact layout's line over the top of the frame: the score (or the time, in a sprint),
+/// the next piece, the level, the lines and the held piece, as many as fit between the
+/// corners of a frame `board_columns` wide inside. Ultra's countdown comes first.
+fn draw_info_line<R: Renderer>(
+    game: &Game,
+    theme: Theme,
+    glyphs: &Glyphs,
+    board_columns: usize,
+    r: &mut R,
+) -> io::Result<()> {
+    let mut parts = Vec::new();
+    if let Some(remaining) = game.time_remaining() {
+        let paint = if remaining <= ULTRA_WARNING_TIME { Paint::Red } else { Paint::Yellow };
+        parts.push((format!("Time {}", format_time(remaining)), paint));
+    }
+    if matches!(game.options().mode, GameMode::Sprint { .. }) {
+        parts.push((format!("Time {}", format_time(game.play_time())), Paint::Yellow));
+    } else {
+        parts.push((format!("Score {}", game.score()), Paint::Yellow));
+    }
+    // Pieces go by their names, or by their block in their colour if they have none.
+    let piece = |id: usize| {
+        let (paint, glyph) = theme.piece_style(game.pieces(), id, glyphs);
+        let name = &game.pieces()[id].name;
+        (if name.is_empty() { glyph.to_string() } else { name.clone() }, paint)
+    };
+    if !game.next_hidden() {
+        let (name, paint) = piece(game.next_queue()[0]);
+        parts.push((format!("Next {}", name), paint));
+    }
+    parts.push((format!("Lv {}", game.level()), Paint::White));
+    let lines = match game.line_goal() {
+        Some(goal) => format!("Lines {}/{}", game.lines_cleared(), goal),
+        None => format!("Lines {}", game.lines_cleared()),
+    };
+    parts.push((lines, Paint::White));
+    if let Some(held_id) = game.held_piece_id() {
+        let (name, paint) = piece(held_id);
+        let paint = if game.hold_locked() { Paint::DarkGrey } else { paint };
+        parts.push((format!("Hold {}", name), paint));
+    }
+
+    let right = BOARD_LEFT_X + 1 + board_columns as u16;
+    let mut x = BOARD_LEFT_X + 1;
+    for (i, (text, paint)) in parts.into_iter().enumerate() {
+        // The gaps blank out the frame edge underneath.
+        let text = if i == 0 { text } else { format!("  {}", text) };
+        let end = x + text.chars().count() as u16;
+        if end > right {
+            break;
+        }
+        r.draw_text(x, BOARD_TOP_Y - 1, &text, paint)?;
+        x = end;
+    }
+    Ok(())
+}
+
+/// Draws what goes over the board (the game over message, the pause menu or the quit
+/// question) and the status line beneath it, which shows `hint` when there is no status
+/// message. Text is cut off at the right edge of `r`.
+fn draw_messages<R: Renderer>(
+    game: &Game,
+    board_top_y: u16,
+    board_columns: usize,
+    board_rows: usize,
+    hint: Option<&str>,
+    r: &mut R,
+) -> io::Result<()> {
+    let board_left_x = BOARD_LEFT_X;
+    let (columns, _) = r.size()?;
+    let centered_x = |msg: &str| board_left_x + (board_columns.saturating_sub(msg.chars().count()) / 2) as u16;
+    let mut draw_text = |x: u16, y: u16, msg: &str, paint: Paint| {
+        let room = columns.saturating_sub(x) as usize;
+        r.draw_text(x, y, &msg.chars().take(room).collect::<String>(), paint)
+    };
+    if game.is_game_over() {
+        let won = game.goal_reached() && matches!(game.options().mode, GameMode::Marathon { .. });
+        let (msg, paint) = if won { ("YOU WIN!", Paint::Green) } else { ("GAME OVER", Paint::Red) };
+        draw_text(centered_x(msg), board_top_y + (board_rows / 2) as u16, msg, paint)?;
+        if let Some(reason) = game.game_over_reason().filter(|&reason| reason != GameOverReason::GoalReached) {
+            let label = reason.label();
+            draw_text(centered_x(label), board_top_y + (board_rows / 2) as u16 + 1, label, paint)?;
+        }
+    } else if game.is_confirming_quit() {
+        let msg = "Quit? (Y/N)";
+        draw_text(centered_x(msg), board_top_y + (board_rows / 2) as u16, msg, Paint::Yellow)?;
+    } else if game.is_paused() {
+        let msg = "PAUSED";
+        let menu_y = (board_top_y + (board_rows / 2) as u16).saturating_sub(3);
+        draw_text(centered_x(msg), menu_y, msg, Paint::Cyan)?;
+        for (i, selection) in PauseSelection::ALL.into_iter().enumerate() {
+            let (entry, paint) = if selection == game.pause_selection() {
+                (format!("> {} <", selection.label()), Paint::Yellow)
+            } else {
+                (selection.label().to_string(), Paint::White)
+            };
+            draw_text(centered_x(&entry), menu_y + 2 + i as u16, &entry, paint)?;
+        }
+    }
+
+    let status_y = board_top_y + board_rows as u16 + 1;
+    if let Some(msg) = game.status_message() {
+        draw_text(centered_x(msg), status_y, msg, Paint::Green)?;
+    } else if let Some(hint) = hint {
+        draw_text(centered_x(hint), status_y, hint, Paint::DarkGrey)?;
+    }
+    Ok(())
+}
+
+/// Formats a duration as a race clock, "m:ss.cc".
+pub fn format_time(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!("{}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+/// Formats a duration as whole minutes and seconds, "mm:ss".
+pub fn format_clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// Renders frames into a fixed-size character grid, ignoring colors. Useful for
+/// snapshot tests and anything else that wants a deterministic text picture of the game.
+pub struct StringRenderer {
+    width: usize,
+    rows: Vec<Vec<char>>,
+}
+
+impl StringRenderer {
+    /// Creates a blank grid of `width` columns by `height` rows. Drawing outside it is clipped.
+    pub fn new(width: usize, height: usize) -> Self {
+        StringRenderer { width, rows: vec![vec![' '; width]; height] }
+    }
+
+    /// Returns row `y` of the grid with trailing spaces removed.
+    pub fn row(&self, y: usize) -> String {
+        self.rows[y].iter().collect::<String>().trim_end().to_string()
+    }
+
+    fn put(&mut self, x: u16, y: u16, text: &str) {
+        let Some(row) = self.rows.get_mut(y as usize) else { return };
+        for (i, ch) in text.chars().enumerate() {
+            let col = x as usize + i;
+            if col >= self.width {
+                break;
+            }
+            row[col] = ch;
+        }
+    }
+}
+
+impl Renderer for StringRenderer {
+    fn clear(&mut self) -> io::Result<()> {
+        for row in &mut self.rows {
+            row.fill(' ');
+        }
+        Ok(())
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, _paint: Paint) -> io::Result<()> {
+        self.put(x, y, glyph);
+        Ok(())
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, _paint: Paint) -> io::Result<()> {
+        self.put(x, y, text);
+        Ok(())
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok((self.width as u16, self.rows.len() as u16))
+    }
+}
+
+/// A run of neighbouring cells in one row that changed to the same paint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub x: u16,
+    pub y: u16,
+    pub text: String,
+    pub paint: Paint,
+}
+
+/// Keeps the frame being drawn and the one on screen, so a frontend can write only
+/// the cells that changed instead of clearing and redrawing everything, which
+/// flickers on slow terminals. Draw a frame, then send [`FrameBuffer::changes`].
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    cells: Vec<(char, Paint)>,
+    /// What the screen shows; `None` until the first frame goes out, when everything
+    /// has to be drawn.
+    shown: Option<Vec<(char, Paint)>>,
+}
+
+const BLANK: (char, Paint) = (' ', Paint::White);
+
+impl FrameBuffer {
+    /// A buffer for a screen of `width` columns by `height` rows; drawing outside it is clipped.
+    pub fn new(width: u16, height: u16) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        FrameBuffer { width, height, cells: vec![BLANK; width * height], shown: None }
+    }
+
+    /// Whether the next [`FrameBuffer::changes`] covers the whole screen, which the
+    /// frontend should clear first.
+    pub fn needs_full_redraw(&self) -> bool {
+        self.shown.is_none()
+    }
+
+    /// The cells that differ from what is on screen, grouped into spans, and takes the
+    /// frame as shown. The first frame is sent whole.
+    pub fn changes(&mut self) -> Vec<Span> {
+        let mut spans: Vec<Span> = Vec::new();
+        for (i, &(ch, paint)) in self.cells.iter().enumerate() {
+            if self.shown.as_ref().is_some_and(|shown| shown[i] == (ch, paint)) {
+                continue;
+            }
+            let (x, y) = ((i % self.width) as u16, (i / self.width) as u16);
+            match spans.last_mut() {
+                Some(span) if span.y == y && span.paint == paint && span.x + span.text.chars().count() as u16 == x => {
+                    span.text.push(ch);
+                }
+                _ => spans.push(Span { x, y, text: ch.to_string(), paint }),
+            }
+        }
+        self.shown = Some(self.cells.clone());
+        spans
+    }
+
+    fn put(&mut self, x: u16, y: u16, text: &str, paint: Paint) {
+        if y as usize >= self.height {
+            return;
+        }
+        let row = y as usize * self.width;
+        for (i, ch) in text.chars().enumerate() {
+            let col = x as usize + i;
+            if col >= self.width {
+                break;
+            }
+            self.cells[row + col] = (ch, paint);
+        }
+    }
+}
+
+impl Renderer for FrameBuffer {
+    fn clear(&mut self) -> io::Result<()> {
+        self.cells.fill(BLANK);
+        Ok(())
+    }
+
+    fn draw_cell(&mut self, x: u16, y: u16, glyph: &str, paint: Paint) -> io::Result<()> {
+        self.put(x, y, glyph, paint);
+        Ok(())
+    }
+
+    fn draw_text(&mut self, x: u16, y: u16, text: &str, paint: Paint) -> io::Result<()> {
+        self.put(x, y, text, paint);
+        Ok(())
+    }
+
+    fn present(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> io::Result<(u16, u16)> {
+        Ok((self.width as u16, self.height as u16))
+    }
+}
+
+impl fmt::Display for StringRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.rows.len() {
+            writeln!(f, "{}", self.row(y))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::game::{standard_pieces, GameOptions, GravityDir, Setup};
+    use crate::input::KeyBindings;
+
+    #[test]
+    fn draws_board_frame_and_panel() {
+        let frames = [(false, " ╔════════╗", " ╚════════╝", " ║"), (true, " +--------+", " +--------+", " |")];
+        for (ascii, top, bottom, side) in frames {
+            let game = Game::new(4, 4, GameOptions { show_ghost: false, ascii, ..GameOptions::default() });
+            let (columns, rows) = min_screen_size(&game);
+            let mut screen = StringRenderer::new(columns as usize, rows as usize);
+            draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+            assert_eq!(screen.row(0), format!("{}  Time 00:00", top));
+            assert!(screen.row(5).starts_with(bottom));
+            assert!(screen.row(2).starts_with(side));
+            assert!(screen.row(2).contains("Score"));
+            assert!(screen.row(3).ends_with("00000000"));
+        }
+    }
+
+    #[test]
+    fn ascii_glyphs_keep_every_screen_plain_ascii() {
+        for (theme, render_style, high_contrast) in [
+            (Theme::Classic, RenderStyle::Blocks, false),
+            (Theme::Monochrome, RenderStyle::Outline, false),
+            (Theme::Colorblind, RenderStyle::Blocks, true),
+        ] {
+            let options = GameOptions { ascii: true, theme, render_style, high_contrast, ..GameOptions::default() };
+            let mut game = Game::new(10, 20, options);
+            for _ in 0..6 {
+                game.hard_drop();
+                game.update(Duration::from_secs(1));
+            }
+            game.toggle_heights();
+            let bindings = KeyBindings::default();
+            let controls = Controls { lines: bindings.help_lines(true), hint: bindings.hint_line(true) };
+            let mut screen = StringRenderer::new(60, 30);
+            draw_game(&game, &controls, &mut screen).unwrap();
+            let text = screen.to_string();
+            assert!(text.is_ascii(), "{}", text);
+            if theme == Theme::Classic {
+                assert!(text.contains("[][]"));
+            }
+        }
+    }
+
+    #[test]
+    fn split_screen_draws_each_game_in_its_own_half() {
+        let options = GameOptions { show_ghost: false, ..GameOptions::default() };
+        let mut games = [Game::new(4, 4, options.clone()), Game::new(4, 4, options)];
+        games[0].set_status_message("one".to_string());
+        games[1].set_status_message("two".to_string());
+        let games = [&games[0], &games[1]];
+        let (columns, rows) = min_split_size(&games);
+        assert_eq!((columns, rows), (min_screen_size(games[0]).0 * 2, min_screen_size(games[0]).1));
+
+        let controls = [Controls::default(), Controls::default()];
+        let mut screen = StringRenderer::new(columns as usize, rows as usize);
+        draw_split(&games, &controls, &mut screen).unwrap();
+        let half = columns as usize / 2;
+        let (left, right): (Vec<String>, Vec<String>) =
+            screen.rows.iter().map(|row| (row[..half].iter().collect(), row[half..].iter().collect())).unzip();
+        assert!(left.concat().contains("one") && !left.concat().contains("two"));
+        assert!(right.concat().contains("two") && !right.concat().contains("one"));
+        assert_eq!(screen.row(0).matches('╔').count(), 2);
+
+        let mut small = StringRenderer::new(columns as usize - 1, rows as usize);
+        draw_split(&games, &controls, &mut small).unwrap();
+        assert_eq!(small.row(0).trim_end(), "Terminal too small");
+    }
+
+    #[test]
+    fn big_mode_doubles_every_cell() {
+        let options = GameOptions { show_ghost: false, danger_rows: 0, big: true, ..GameOptions::default() };
+        let mut game = Game::new(4, 4, options);
+        let (columns, rows) = min_screen_size(&game);
+        assert_eq!((columns, rows), (8 * 2 + 5 + PANEL_WIDTH, 8 + 2 * 5 + 1));
+        let mut screen = StringRenderer::new(columns as usize, rows as usize);
+        game.hard_drop();
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+        assert_eq!(screen.row(0), " ╔════════════════╗  Time 00:00");
+        assert!(screen.row(9).starts_with(" ╚════════════════╝"));
+        // Whatever piece dropped reaches the bottom board row, drawn two screen rows tall.
+        assert!(screen.row(8).contains("████"));
+        assert_eq!(screen.rows[7][..19], screen.rows[8][..19]);
+        assert_eq!(board_cell_at(&game, columns, rows, 2 + 7, 8), Some((1, 3)));
+    }
+
+    #[test]
+    fn high_contrast_uses_heavy_borders_a_score_box_and_no_background() {
+        for big in [false, true] {
+            let options = GameOptions {
+                high_contrast: true,
+                big,
+                theme: Theme::Colorblind,
+                show_ghost: false,
+                seed: Some(2),
+                ..GameOptions::default()
+            };
+            let game = Game::new(4, 4, options);
+            let (columns, rows) = min_screen_size(&game);
+            let mut screen = StringRenderer::new(columns as usize, rows as usize);
+            draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+            let text = screen.to_string();
+            assert!(screen.row(0).starts_with(" ┏━━"));
+            assert!(!text.contains('·') && !text.contains('║'));
+            assert!(text.contains("┃ Score        ┃") && text.contains("┃ Level 1      ┃"));
+            // The falling piece is outlined rather than filled with its pattern.
+            assert!(screen.row(1).contains("[══"), "{:?}", screen.row(1));
+        }
+    }
+
+    #[test]
+    fn tall_stack_puts_a_warning_in_the_frame() {
+        let options = GameOptions { danger_rows: 4, danger_flash: false, ..GameOptions::default() };
+        let mut game = Game::new(4, 4, options);
+        let (columns, rows) = min_screen_size(&game);
+        let mut screen = StringRenderer::new(columns as usize, rows as usize);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        assert!(screen.row(0).starts_with(" ╔════════╗ "));
+
+        // On a four-row board every locked block is within the top four rows.
+        while game.max_stack_height() == 0 {
+            game.hard_drop();
+            game.update(Duration::from_secs(1));
+        }
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        assert!(screen.row(0).starts_with(" ╔═DANGER═╗ "));
+    }
+
+    #[test]
+    fn frame_buffer_sends_only_what_changed() {
+        let mut game = Game::new(10, 20, GameOptions { seed: Some(1), ..GameOptions::default() });
+        let mut frame = FrameBuffer::new(60, 26);
+        draw_game(&game, &Controls::default(), &mut frame).unwrap();
+        assert!(frame.needs_full_redraw());
+        let full: usize = frame.changes().iter().map(|span| span.text.chars().count()).sum();
+        assert_eq!(full, 60 * 26);
+
+        draw_game(&game, &Controls::default(), &mut frame).unwrap();
+        assert!(!frame.needs_full_redraw());
+        assert_eq!(frame.changes(), vec![]);
+
+        // Moving the piece touches its old and new cells and the ghost, not the rest.
+        game.try_shift(1);
+        draw_game(&game, &Controls::default(), &mut frame).unwrap();
+        let spans = frame.changes();
+        let cells: usize = spans.iter().map(|span| span.text.chars().count()).sum();
+        assert!(!spans.is_empty() && spans.len() <= 16, "{} spans", spans.len());
+        assert!(cells * 20 < full, "{} of {} cells redrawn", cells, full);
+    }
+
+    #[test]
+    fn outline_style_borders_each_piece() {
+        let o = [(0, 0), (1, 0), (0, 1), (1, 1)];
+        let i = [(0, 0), (1, 0), (2, 0)];
+        let outline = |glyphs: &Glyphs, blocks: &[(isize, isize)]| -> Vec<String> {
+            blocks.iter().map(|&(x, y)| piece_glyph(RenderStyle::Outline, glyphs, blocks, x, y, "██")).collect()
+        };
+        assert_eq!(outline(&UNICODE_GLYPHS, &o), ["┌─", "─┐", "└─", "─┘"]);
+        assert_eq!(outline(&UNICODE_GLYPHS, &i).concat(), "[════]");
+        assert_eq!(outline(&ASCII_GLYPHS, &o), ["+-", "-+", "+-", "-+"]);
+        assert_eq!(outline(&ASCII_GLYPHS, &i).concat(), "[====]");
+        assert_eq!(piece_glyph(RenderStyle::Blocks, &UNICODE_GLYPHS, &i, 0, 0, "██"), "██");
+        assert_eq!("Outline".parse(), Ok(RenderStyle::Outline));
+    }
+
+    #[test]
+    fn pause_menu_highlights_selection() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.toggle_pause();
+        game.move_pause_selection(1);
+        let mut screen = StringRenderer::new(60, 26);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+        let text = screen.to_string();
+        assert!(text.contains("PAUSED"));
+        assert!(text.contains("> Restart <"));
+        assert!(text.contains("Quit to Menu"));
+
+        // The quit question takes the menu's place until it is answered.
+        game.ask_to_quit();
+        let mut screen = StringRenderer::new(60, 26);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let text = screen.to_string();
+        assert!(text.contains("Quit? (Y/N)") && !text.contains("PAUSED"));
+    }
+
+    #[test]
+    fn next_queue_is_trimmed_to_the_screen_height() {
+        let game = Game::new(10, 20, GameOptions::default());
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let panel: Vec<String> = screen.rows.iter().map(|row| row[25..].iter().collect()).collect();
+        // 23 rows leave room for four of the five queued pieces, then the hold box.
+        let hold_row = panel.iter().position(|line| line.starts_with("Hold")).unwrap();
+        assert_eq!(hold_row, 8 + 3 * 4);
+        let queued = panel[8..hold_row].iter().filter(|line| line.contains("██")).count();
+        assert!(queued >= 4);
+    }
+
+    #[test]
+    fn previews_center_each_piece_in_the_same_box() {
+        let pieces = crate::game::standard_pieces();
+        let preview_box = preview_box(&pieces, GravityDir::Down);
+        assert_eq!(preview_box, (4, 2));
+        let layout = |piece, box_size| preview_layout(piece, box_size, false, GravityDir::Down);
+        let offsets: Vec<(u16, u16)> = pieces.iter().map(|piece| layout(piece, preview_box).1).collect();
+        // I fills the box, O sits two columns in, the three-wide pieces one.
+        assert_eq!(offsets, [(0, 0), (2, 0), (1, 0), (1, 0), (1, 0), (1, 0), (1, 0)]);
+
+        // Empty edges of a custom bitmap are trimmed before centering.
+        let dot = Piece { rotations: vec![(3, vec![0, 0, 0, 0, 1, 0, 0, 0, 0])], ..pieces[0].clone() };
+        assert_eq!(layout(&dot, (4, 3)), (vec![(0, 0)], (3, 1)));
+
+        // Sideways, pieces are turned as on the board and the box is sized for that.
+        let sideways_box = super::preview_box(&pieces, GravityDir::Left);
+        assert_eq!(sideways_box, (4, 4));
+        // T spawns pointing up, so falling leftward it points right.
+        let (mut t_cells, offset) = preview_layout(&pieces[2], sideways_box, false, GravityDir::Left);
+        t_cells.sort();
+        assert_eq!((t_cells, offset), (vec![(0, 0), (0, 1), (0, 2), (1, 1)], (2, 0)));
+
+        let only_o: Vec<Piece> = vec![pieces[1].clone()];
+        let game = Game::new(10, 20, GameOptions { pieces: only_o.into(), ..GameOptions::default() });
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let panel: Vec<String> = screen.rows.iter().map(|row| row[25..].iter().collect()).collect();
+        assert!(panel[8].starts_with("  ████"), "{:?}", panel[8]);
+    }
+
+    #[test]
+    fn held_piece_dims_until_hold_can_be_used_again() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        game.hold_piece();
+        // The hold heading and the paints of the held piece's blocks under it.
+        let hold_box = |game: &Game| {
+            let mut frame = FrameBuffer::new(41, 23);
+            draw_game(game, &Controls::default(), &mut frame).unwrap();
+            let row = |y: usize| &frame.cells[y * 41 + 25..(y + 1) * 41];
+            let y = (0..23).find(|&y| row(y).iter().map(|&(c, _)| c).collect::<String>().starts_with("H")).unwrap();
+            let heading: String = row(y).iter().map(|&(c, _)| c).collect();
+            let paints: Vec<Paint> =
+                (y + 1..y + 3).flat_map(row).filter(|&&(c, _)| c == '█').map(|&(_, paint)| paint).collect();
+            (heading.trim_end().to_string(), paints)
+        };
+        let (heading, paints) = hold_box(&game);
+        assert_eq!(heading, "HOLD LOCKED");
+        assert!(!paints.is_empty() && paints.iter().all(|&paint| paint == Paint::DarkGrey));
+
+        game.hard_drop();
+        let (heading, paints) = hold_box(&game);
+        assert_eq!(heading, "Hold");
+        assert!(!paints.is_empty() && paints.iter().all(|&paint| paint != Paint::DarkGrey));
+    }
+
+    #[test]
+    fn themes_parse_by_name_and_pattern_themes_tell_pieces_apart() {
+        assert_eq!("Pastel".parse::<Theme>(), Ok(Theme::Pastel));
+        assert!("neon".parse::<Theme>().is_err());
+
+        let pieces = standard_pieces();
+        for (theme, set) in [Theme::Monochrome, Theme::Colorblind].into_iter().zip([&UNICODE_GLYPHS, &ASCII_GLYPHS]) {
+            let mut glyphs: Vec<&str> = (0..pieces.len()).map(|id| theme.piece_style(&pieces, id, set).1).collect();
+            glyphs.sort_unstable();
+            glyphs.dedup();
+            assert_eq!(glyphs.len(), pieces.len());
+        }
+        // Locked cells keep the style of the piece they came from.
+        let (mono, ascii) = (Theme::Monochrome, &ASCII_GLYPHS);
+        assert_eq!(mono.cell_style(&pieces, pieces[3].color, ascii), mono.piece_style(&pieces, 3, ascii));
+    }
+
+    #[test]
+    fn race_clock_format() {
+        assert_eq!(format_time(Duration::from_millis(83_456)), "1:23.45");
+        assert_eq!(format_time(Duration::from_millis(5_009)), "0:05.00");
+        assert_eq!(format_clock(Duration::from_millis(83_999)), "01:23");
+        assert_eq!(format_clock(Duration::from_secs(6000)), "100:00");
+    }
+
+    #[test]
+    fn ultra_countdown_sits_above_the_score() {
+        let options = GameOptions { mode: GameMode::Ultra { time_limit: Duration::from_secs(120) }, ..GameOptions::default() };
+        let game = Game::new(10, 20, options);
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+        assert!(screen.row(0).ends_with("Time 2:00.00"));
+        assert!(screen.row(2).ends_with("Score"));
+    }
+
+    #[test]
+    fn stats_block_replaces_controls_when_toggled() {
+        let mut game = Game::new(10, 20, GameOptions { preview_count: 1, ..GameOptions::default() });
+        let controls = Controls { lines: vec!["Up: Rotate".to_string()], ..Controls::default() };
+        // Tall enough for the whole stats block under a single preview.
+        let mut screen = StringRenderer::new(41, 26);
+        draw_game(&game, &controls, &mut screen).unwrap();
+        assert!(screen.to_string().contains("Controls"));
+
+        game.toggle_stats();
+        draw_game(&game, &controls, &mut screen).unwrap();
+        let text = screen.to_string();
+        assert!(!text.contains("Controls") && !text.contains("Rotate"));
+        assert!(text.contains("Pieces 1") && text.contains("PPS 0.00") && text.contains("Tpl 0   Tet 0"));
+        assert!(text.contains("Faults 0"));
+    }
+
+    #[test]
+    fn screen_positions_map_back_to_board_cells() {
+        let game = Game::new(10, 20, GameOptions::default());
+        // One spare column and row on each side, as in draws_active_piece_and_ghost.
+        assert_eq!(board_cell_at(&game, 43, 25, 3, 2), Some((0, 0)));
+        assert_eq!(board_cell_at(&game, 43, 25, 4, 2), Some((0, 0)));
+        assert_eq!(board_cell_at(&game, 43, 25, 22, 21), Some((9, 19)));
+        assert_eq!(board_cell_at(&game, 43, 25, 2, 5), None);
+        assert_eq!(board_cell_at(&game, 43, 25, 23, 5), None);
+        assert_eq!(board_cell_at(&game, 43, 25, 5, 22), None);
+        assert_eq!(board_cell_at(&game, 30, 10, 5, 5), None);
+
+        assert_eq!(board_center(&game, 43, 25), Some((13, 12)));
+        assert_eq!(board_cell_at(&game, 43, 25, 13, 12), Some((5, 10)));
+        assert_eq!(board_center(&game, 30, 10), None);
+    }
+
+    #[test]
+    fn mirror_flips_the_board_on_screen_only() {
+        let setup = Setup::parse("board = \"#.........\"", &standard_pieces(), 10, 20).unwrap();
+        let options = GameOptions { setup: Some(Arc::new(setup)), ..GameOptions::default() };
+        let plain = Game::new(10, 20, options.clone());
+        let mirrored = Game::new(10, 20, GameOptions { mirror: true, ..options });
+        assert_eq!(plain.board(), mirrored.board());
+        // As in screen_positions_map_back_to_board_cells, the bottom row is screen row 21.
+        for (game, garbage_x) in [(&plain, 3), (&mirrored, 21)] {
+            let mut screen = StringRenderer::new(43, 25);
+            draw_game(game, &Controls::default(), &mut screen).unwrap();
+            assert_eq!(screen.rows[21][garbage_x], '█');
+            assert_ne!(screen.rows[21][24 - garbage_x], '█');
+        }
+        assert_eq!(board_cell_at(&mirrored, 43, 25, 3, 2), Some((9, 0)));
+        assert_eq!(board_cell_at(&mirrored, 43, 25, 22, 21), Some((0, 19)));
+    }
+
+    #[test]
+    fn leftward_gravity_turns_the_board_on_screen() {
+        let setup = Setup::parse("board = \"#.........\"", &standard_pieces(), 10, 20).unwrap();
+        let setup = Some(Arc::new(setup));
+        let game = Game::new(10, 20, GameOptions { setup, gravity_dir: GravityDir::Left, ..GameOptions::default() });
+        // Twenty cells across and ten down, with the panel to the right.
+        // The side panel is sized for previews turned the same way.
+        assert_eq!(min_screen_size(&game), (61, 19));
+        let mut screen = StringRenderer::new(61, 19);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        assert_eq!(screen.row(0).chars().filter(|&c| c == '═').count(), 40);
+        // The bottom-left block lies against the left wall, at the top, and the new
+        // piece appears at the right wall.
+        assert_eq!(screen.rows[1][2], '█');
+        assert_eq!(board_cell_at(&game, 61, 19, 2, 1), Some((0, 19)));
+        let blocks_in = |columns: std::ops::Range<usize>| {
+            let cells = (1..11).flat_map(|y| columns.clone().map(move |x| (x, y)));
+            cells.filter(|&(x, y)| screen.rows[y][x] == '█').count()
+        };
+        assert_eq!(blocks_in(4..34), 0);
+        assert!(blocks_in(34..42) > 0);
+    }
+
+    #[test]
+    fn height_overlay_labels_columns_and_marks_holes() {
+        // A flat S piece leaves a hole under its overhang.
+        let only_s: Vec<Piece> = vec![standard_pieces()[5].clone()];
+        let options = GameOptions { pieces: only_s.into(), show_ghost: false, ..GameOptions::default() };
+        let mut game = Game::new(4, 6, options);
+        game.hard_drop();
+        game.update(Duration::from_secs(1));
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let text = screen.to_string();
+        assert!(!text.contains("▒▒"));
+
+        game.toggle_heights();
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let rows: Vec<String> = screen.rows.iter().map(|row| row.iter().collect()).collect();
+        let bottom = rows.iter().find(|row| row.contains('╚')).unwrap();
+        assert!(bottom.contains("╚1 2 2 0 ╝"), "{:?}", bottom);
+        assert_eq!(rows.iter().filter(|row| row.contains("▒▒")).count(), 1);
+    }
+
+    #[test]
+    fn garbage_meter_rises_beside_the_board() {
+        let options = GameOptions { garbage_delay: Duration::from_millis(500), ..GameOptions::default() };
+        let mut game = Game::new(4, 6, options);
+        game.queue_garbage(2);
+        game.update(Duration::from_millis(600));
+        game.queue_garbage(1);
+        let mut screen = StringRenderer::new(41, 23);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+        let bottom = screen.rows.iter().position(|row| row.contains(&'╚')).unwrap();
+        // The meter sits in the gap right of the frame, one row per line.
+        let meter_x = screen.rows[bottom].iter().position(|&c| c == '╝').unwrap() + 1;
+        let meter: Vec<bool> = screen.rows[..bottom].iter().map(|row| row[meter_x] == '█').collect();
+        assert_eq!(meter.iter().filter(|&&lit| lit).count(), 3);
+        assert!(meter[bottom - 3..].iter().all(|&lit| lit));
+    }
+
+    #[test]
+    fn small_screen_shows_only_a_warning() {
+        let game = Game::new(10, 20, GameOptions::default());
+        let mut screen = StringRenderer::new(30, 10);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+        assert_eq!(screen.row(0), "Terminal too small");
+        assert_eq!(screen.row(1), "Need 41x23, have 30x10");
+        assert_eq!(screen.row(2), "  or 24x23");
+        assert!(!screen.to_string().contains('╔'));
+    }
+
+    #[test]
+    fn narrow_screens_get_the_compact_layout() {
+        let game = Game::new(10, 20, GameOptions { seed: Some(3), ..GameOptions::default() });
+        let bindings = KeyBindings::default();
+        let controls = Controls { lines: bindings.help_lines(false), hint: bindings.hint_line(false) };
+        // Too narrow for the side panel: the 24x23 compact layout, three columns in.
+        let mut screen = StringRenderer::new(30, 23);
+        draw_game(&game, &controls, &mut screen).unwrap();
+        let next = &game.pieces()[game.next_queue()[0]].name;
+        // The info line sits between the top corners, showing only what fits there.
+        assert_eq!(screen.row(0), format!("    ╔Score 0  Next {}═════╗", next));
+        assert!(screen.row(21).starts_with("    ╚"));
+        assert_eq!(screen.row(22).trim(), "P: Pause  Q: Quit");
+        assert!(!screen.to_string().contains("Hold"));
+        assert_eq!(board_cell_at(&game, 30, 23, 5, 1), Some((0, 0)));
+        assert_eq!(board_cell_at(&game, 30, 23, 24, 20), Some((9, 19)));
+        assert_eq!(board_center(&game, 30, 23), Some((15, 11)));
+
+        // With room for the panel it comes back, unless --compact is forced.
+        let mut screen = StringRenderer::new(43, 25);
+        draw_game(&game, &controls, &mut screen).unwrap();
+        assert!(screen.to_string().contains("Hold"));
+        let compact = Game::new(10, 20, GameOptions { compact: true, ..GameOptions::default() });
+        assert_eq!(min_screen_size(&compact), (24, 23));
+        draw_game(&compact, &controls, &mut screen).unwrap();
+        assert!(!screen.to_string().contains("Hold"));
+        // Even with room to spare, the info line stops at the frame's corner.
+        assert!(screen.row(1).contains("╔Score 0") && screen.row(1).trim_end().ends_with('╗'));
+        assert!(!fits_screen(&compact, 24, 22));
+
+        // The compact layout is never taller than the full one, so a screen a row too
+        // short for a tall panel gets it.
+        let tall_panel = Game::new(10, 12, GameOptions { high_contrast: true, ..GameOptions::default() });
+        assert_eq!(min_screen_size(&tall_panel), (41, 17));
+        let mut screen = StringRenderer::new(41, 16);
+        draw_game(&tall_panel, &controls, &mut screen).unwrap();
+        assert!(screen.to_string().contains("P: Pause  Q: Quit"));
+        assert!(!screen.to_string().contains("Hold"));
+    }
+
+    #[test]
+    fn draws_active_piece_and_ghost() {
+        let game = Game::new(10, 20, GameOptions::default());
+        // Two spare columns and rows put the layout one cell further right and down.
+        let mut screen = StringRenderer::new(43, 25);
+        draw_game(&game, &Controls::default(), &mut screen).unwrap();
+
+        let blocks = game.active_piece().blocks(game.pieces());
+        let (visible_cells, active_cells) =
+            blocks.fold((0, 0), |(visible, all), (_, y)| (visible + (y >= 0) as usize, all + 1));
+        // Only look inside the board frame; the next-piece preview also uses blocks.
+        let board: String = (2..=21)
+            .map(|y| screen.rows[y][3..23].iter().collect::<String>())
+            .collect();
+        assert_eq!(board.matches("▒▒").count(), active_cells);
+        // The part of the piece still in the buffer rows isn't drawn.
+        assert!(visible_cells > 0);
+        assert_eq!(board.matches("██").count(), visible_cells);
+    }
+
+    #[test]
+    fn smooth_fall_eases_toward_the_piece_and_snaps_to_new_ones() {
+        let mut game = Game::new(10, 20, GameOptions::default());
+        let mut fall = SmoothFall::default();
+        fall.advance(&game, Duration::ZERO);
+        let y = game.active_piece().y;
+        assert_eq!(fall.row(&game), y);
+
+        assert!(game.try_move(0, 8));
+        fall.advance(&game, Duration::from_millis(16));
+        let row = fall.row(&game);
+        assert!(y < row && row < y + 8);
+        let (mut smooth, mut plain) = (StringRenderer::new(41, 24), StringRenderer::new(41, 24));
+        draw_game_smooth(&game, &Controls::default(), &fall, &mut smooth).unwrap();
+        draw_game(&game, &Controls::default(), &mut plain).unwrap();
+        assert_ne!(smooth.to_string(), plain.to_string());
+        fall.advance(&game, Duration::from_secs(1));
+        assert_eq!(fall.row(&game), y + 8);
+
+        game.hard_drop();
+        fall.advance(&game, Duration::ZERO);
+        assert_eq!(fall.row(&game), game.active_piece().y);
+    }
+}
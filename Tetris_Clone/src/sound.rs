@@ -0,0 +1,396 @@
+//! Sound effects for game events, and background music.
+//!
+//! A [`SoundEngine`] turns [`GameEvent`]s into short synthesized [`Effect`]s and plays
+//! music: a music file on a loop, or by default a metronome that ticks faster as the
+//! level rises. Playback needs the `sound` feature and a command-line audio player
+//! (`paplay`, `pw-play`, `aplay` or `afplay`); without either the engine silently does nothing.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::game::GameEvent;
+
+/// A short sound played for something that happened in the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    Rotate,
+    Lock,
+    HardDrop,
+    LineClear,
+    /// A four-line clear gets a bigger sound than smaller clears.
+    Tetris,
+    GameOver,
+    /// One tick of the metronome music.
+    Beat,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 7] = [
+        Effect::Rotate,
+        Effect::Lock,
+        Effect::HardDrop,
+        Effect::LineClear,
+        Effect::Tetris,
+        Effect::GameOver,
+        Effect::Beat,
+    ];
+
+    /// The effect that goes with `event`, if any.
+    pub fn for_event(event: &GameEvent) -> Option<Effect> {
+        match event {
+            GameEvent::Rotated { .. } => Some(Effect::Rotate),
+            GameEvent::Locked { hard_drop: true, .. } => Some(Effect::HardDrop),
+            GameEvent::Locked { hard_drop: false, .. } => Some(Effect::Lock),
+            GameEvent::LinesCleared { count, .. } if *count >= 4 => Some(Effect::Tetris),
+            GameEvent::LinesCleared { count, .. } if *count > 0 => Some(Effect::LineClear),
+            GameEvent::GameOver => Some(Effect::GameOver),
+            _ => None,
+        }
+    }
+
+    /// Tone of the effect: start and end frequency in Hz and length in milliseconds.
+    #[cfg_attr(not(feature = "sound"), allow(dead_code))]
+    fn tone(self) -> (f32, f32, u32) {
+        match self {
+            Effect::Rotate => (880.0, 880.0, 30),
+            Effect::Lock => (220.0, 220.0, 50),
+            Effect::HardDrop => (330.0, 110.0, 80),
+            Effect::LineClear => (523.0, 784.0, 150),
+            Effect::Tetris => (523.0, 1046.0, 400),
+            Effect::GameOver => (440.0, 110.0, 800),
+            Effect::Beat => (1320.0, 1320.0, 15),
+        }
+    }
+}
+
+/// Metronome tempo at level 1, in beats per minute.
+const BASE_TEMPO: u32 = 100;
+/// Extra beats per minute for every level after the first, up to [`MAX_TEMPO`].
+const TEMPO_PER_LEVEL: u32 = 6;
+const MAX_TEMPO: u32 = 200;
+
+/// Time between metronome ticks at `level`.
+fn beat_interval(level: u32) -> Duration {
+    let tempo = (BASE_TEMPO + TEMPO_PER_LEVEL * level.saturating_sub(1)).min(MAX_TEMPO);
+    Duration::from_secs(60) / tempo
+}
+
+/// Plays effects and music if sound is compiled in and an audio player was found.
+pub struct SoundEngine {
+    backend: Option<backend::Backend>,
+    effects_on: bool,
+    /// Music file to loop; `None` plays the metronome.
+    music_file: Option<PathBuf>,
+    music_on: bool,
+    /// The music file while it is playing.
+    music: Option<backend::Playback>,
+    since_beat: Duration,
+    /// Why music couldn't start as asked, until [`SoundEngine::take_warning`] reports it.
+    warning: Option<String>,
+}
+
+impl SoundEngine {
+    /// Sets up playback, with effects unless `muted`. Music starts on if `music_file`
+    /// is given; if it can't be found the metronome is used instead, starting off.
+    /// Without an audio player the engine stays silent.
+    pub fn new(muted: bool, music_file: Option<&Path>) -> Self {
+        let backend = backend::Backend::open();
+        let mut warning = None;
+        let music_file = music_file.and_then(|path| {
+            if path.is_file() {
+                Some(path.to_path_buf())
+            } else {
+                warning = Some(format!("Music file not found: {}", path.display()));
+                None
+            }
+        });
+        let music_on = music_file.is_some();
+        if music_on && backend.is_none() {
+            warning = Some("No audio player found; music off".to_string());
+        }
+        SoundEngine {
+            backend,
+            effects_on: !muted,
+            music_file,
+            music_on,
+            music: None,
+            since_beat: Duration::ZERO,
+            warning,
+        }
+    }
+
+    /// Whether anything can be heard at all.
+    pub fn is_available(&self) -> bool {
+        self.backend.is_some()
+    }
+
+    pub fn play(&mut self, effect: Effect) {
+        if !self.effects_on {
+            return;
+        }
+        if let Some(backend) = &mut self.backend {
+            backend.play(effect);
+        }
+    }
+
+    /// Plays the effect for each event that has one.
+    pub fn play_events(&mut self, events: &[GameEvent]) {
+        for effect in events.iter().filter_map(Effect::for_event) {
+            self.play(effect);
+        }
+    }
+
+    /// A problem with the music to tell the player about, once.
+    pub fn take_warning(&mut self) -> Option<String> {
+        self.warning.take()
+    }
+
+    /// Turns music on or off, independently of the effects, and describes the result.
+    pub fn toggle_music(&mut self) -> String {
+        if self.backend.is_none() {
+            return "No audio player found".to_string();
+        }
+        self.music_on = !self.music_on;
+        if !self.music_on {
+            self.stop_music();
+        }
+        if self.music_on { "Music on" } else { "Music off" }.to_string()
+    }
+
+    /// Keeps the music going for `dt` more of a game at `level`. Music stops while
+    /// `playing` is false, e.g. when the game is paused or over; a music file then
+    /// starts over from the beginning.
+    pub fn update_music(&mut self, dt: Duration, level: u32, playing: bool) {
+        let Some(backend) = &mut self.backend else { return };
+        if !self.music_on || !playing {
+            self.stop_music();
+            return;
+        }
+        match &self.music_file {
+            Some(file) => {
+                // Loop by starting the file again whenever the player finishes it.
+                if self.music.as_mut().is_none_or(|music| music.is_finished()) {
+                    self.music = backend.start(file);
+                }
+            }
+            None => {
+                self.since_beat += dt;
+                let interval = beat_interval(level);
+                if self.since_beat >= interval {
+                    // A long frame plays one tick rather than a burst of them.
+                    self.since_beat = (self.since_beat - interval).min(interval);
+                    backend.play(Effect::Beat);
+                }
+            }
+        }
+    }
+
+    fn stop_music(&mut self) {
+        if let Some(music) = self.music.take() {
+            music.stop();
+        }
+        self.since_beat = Duration::ZERO;
+    }
+}
+
+#[cfg(feature = "sound")]
+mod backend {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command, Stdio};
+
+    use super::Effect;
+
+    /// A music file being played.
+    pub struct Playback(Child);
+
+    impl Playback {
+        pub fn is_finished(&mut self) -> bool {
+            !matches!(self.0.try_wait(), Ok(None))
+        }
+
+        pub fn stop(mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    const SAMPLE_RATE: u32 = 22_050;
+    /// Players to try, with the arguments that go before the file name.
+    const PLAYERS: [(&str, &[&str]); 4] = [("paplay", &[]), ("pw-play", &[]), ("aplay", &["-q"]), ("afplay", &[])];
+
+    /// Plays effects by handing WAV files to a command-line player in the background.
+    pub struct Backend {
+        player: PathBuf,
+        args: &'static [&'static str],
+        dir: PathBuf,
+        playing: Vec<Child>,
+    }
+
+    impl Backend {
+        /// Finds a player and writes the effect files, or `None` if either fails.
+        pub fn open() -> Option<Self> {
+            let (player, args) = PLAYERS.iter().find_map(|&(name, args)| Some((find_in_path(name)?, args)))?;
+            let dir = env::temp_dir().join(format!("tetris-tui-sounds-{}", std::process::id()));
+            fs::create_dir_all(&dir).ok()?;
+            for effect in Effect::ALL {
+                fs::write(dir.join(file_name(effect)), wav(effect)).ok()?;
+            }
+            Some(Backend { player, args, dir, playing: Vec::new() })
+        }
+
+        pub fn play(&mut self, effect: Effect) {
+            // Reap finished players so they don't linger as zombies.
+            self.playing.retain_mut(|child| matches!(child.try_wait(), Ok(None)));
+            // A player that can't start (or has no device) just means no sound.
+            if let Ok(child) = self.spawn(&self.dir.join(file_name(effect))) {
+                self.playing.push(child);
+            }
+        }
+
+        /// Starts playing `file` in the background.
+        pub fn start(&mut self, file: &Path) -> Option<Playback> {
+            self.spawn(file).ok().map(Playback)
+        }
+
+        fn spawn(&self, file: &Path) -> std::io::Result<Child> {
+            Command::new(&self.player)
+                .args(self.args)
+                .arg(file)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+        }
+    }
+
+    impl Drop for Backend {
+        fn drop(&mut self) {
+            for mut child in self.playing.drain(..) {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn find_in_path(name: &str) -> Option<PathBuf> {
+        env::split_paths(&env::var_os("PATH")?).map(|dir| dir.join(name)).find(|path| is_file(path))
+    }
+
+    fn is_file(path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|meta| meta.is_file())
+    }
+
+    fn file_name(effect: Effect) -> String {
+        format!("{:?}.wav", effect).to_lowercase()
+    }
+
+    /// A 16-bit mono WAV file of the effect's tone: a square wave sweeping from the
+    /// start to the end frequency, fading out so it doesn't click.
+    pub(super) fn wav(effect: Effect) -> Vec<u8> {
+        let (from, to, millis) = effect.tone();
+        let samples = SAMPLE_RATE * millis / 1000;
+        let mut data = Vec::with_capacity(samples as usize * 2);
+        let mut phase = 0.0f32;
+        for i in 0..samples {
+            let progress = i as f32 / samples as f32;
+            phase = (phase + (from + (to - from) * progress) / SAMPLE_RATE as f32).fract();
+            let level = if phase < 0.5 { 1.0 } else { -1.0 };
+            let sample = (level * (1.0 - progress) * 6000.0) as i16;
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        let mut wav = Vec::with_capacity(44 + data.len());
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&data);
+        wav
+    }
+}
+
+#[cfg(not(feature = "sound"))]
+mod backend {
+    use std::path::Path;
+
+    use super::Effect;
+
+    /// Stand-ins for builds without the `sound` feature; they can never be created.
+    pub enum Backend {}
+    pub enum Playback {}
+
+    impl Playback {
+        pub fn is_finished(&mut self) -> bool {
+            match *self {}
+        }
+
+        pub fn stop(self) {
+            match self {}
+        }
+    }
+
+    impl Backend {
+        pub fn open() -> Option<Self> {
+            None
+        }
+
+        pub fn play(&mut self, _effect: Effect) {
+            match *self {}
+        }
+
+        pub fn start(&mut self, _file: &Path) -> Option<Playback> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_map_to_effects_with_a_bigger_one_for_tetrises() {
+        assert_eq!(Effect::for_event(&GameEvent::Locked { piece: 0, hard_drop: true }), Some(Effect::HardDrop));
+        assert_eq!(Effect::for_event(&GameEvent::LinesCleared { count: 2, t_spin: false }), Some(Effect::LineClear));
+        assert_eq!(Effect::for_event(&GameEvent::LinesCleared { count: 4, t_spin: false }), Some(Effect::Tetris));
+        assert_eq!(Effect::for_event(&GameEvent::LinesCleared { count: 0, t_spin: true }), None);
+        assert_eq!(Effect::for_event(&GameEvent::Moved { x: 0, y: 1 }), None);
+    }
+
+    #[test]
+    fn metronome_speeds_up_with_level_to_a_limit() {
+        assert_eq!(beat_interval(1), Duration::from_millis(600));
+        assert!(beat_interval(5) < beat_interval(4));
+        assert_eq!(beat_interval(100), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn missing_music_file_is_reported_not_fatal() {
+        let mut engine = SoundEngine::new(true, Some(Path::new("no/such/music.wav")));
+        assert!(engine.take_warning().unwrap().contains("not found"));
+        assert_eq!(engine.take_warning(), None);
+        engine.update_music(Duration::from_secs(1), 1, true);
+    }
+
+    #[cfg(feature = "sound")]
+    #[test]
+    fn effect_files_are_well_formed_wavs() {
+        let wav = backend::wav(Effect::Lock);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        let data_len = u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize;
+        assert_eq!(wav.len(), 44 + data_len);
+        assert_eq!(data_len, 22_050 * 50 / 1000 * 2);
+    }
+}
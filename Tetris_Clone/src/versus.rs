@@ -0,0 +1,193 @@
+//! Local head-to-head play: two games side by side, each sending garbage to the other.
+//!
+//! A [`Versus`] owns both [`Game`]s and advances them together. Clearing lines in one
+//! queues garbage on the other (see [`attack_lines`]), which arrives through the usual
+//! [`Game::queue_garbage`] delay. The first player to top out loses; topping out
+//! together is a draw.
+
+use std::time::Duration;
+
+use crate::game::{Game, GameEvent, GameMode, GameOptions};
+
+/// Garbage lines a clear of `count` rows sends to the opponent: one less than the
+/// lines for doubles and triples, all four for a tetris, and twice the lines for a
+/// T-spin. Singles send nothing.
+pub fn attack_lines(count: u32, t_spin: bool) -> u32 {
+    match (count, t_spin) {
+        (_, true) => 2 * count,
+        (0 | 1, false) => 0,
+        (2 | 3, false) => count - 1,
+        (count, false) => count,
+    }
+}
+
+/// How a match ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The other player topped out first, so this one (0 or 1) won.
+    Won(usize),
+    /// Both players topped out in the same update.
+    Draw,
+}
+
+/// Two players' games and the garbage they have sent each other.
+pub struct Versus {
+    pub games: [Game; 2],
+    /// Garbage lines each player has sent the other.
+    sent: [u32; 2],
+    /// Settings the match was started with, for a rematch.
+    options: GameOptions,
+}
+
+impl Versus {
+    /// Starts two `width` by `height` games with the same settings and the same piece
+    /// sequence. Each plays endless marathon until one tops out; events are always
+    /// recorded, since garbage is sent from them.
+    pub fn new(width: usize, height: usize, options: GameOptions) -> Self {
+        let shared = GameOptions {
+            seed: Some(options.seed.unwrap_or_else(rand::random)),
+            mode: GameMode::Marathon { lines: 0 },
+            record_events: true,
+            practice: false,
+            ..options.clone()
+        };
+        let games = [Game::new(width, height, shared.clone()), Game::new(width, height, shared)];
+        Versus { games, sent: [0, 0], options }
+    }
+
+    /// Starts a fresh match on the same boards with the same settings; without a fixed
+    /// seed, the pieces come in a new order.
+    pub fn restart(&mut self) {
+        *self = Versus::new(self.games[0].width(), self.games[0].height(), self.options.clone());
+    }
+
+    /// Advances both games by `elapsed` and delivers the garbage their clears send.
+    /// Returns every event either game recorded, player one's first. Nothing happens
+    /// once the match is over.
+    pub fn update(&mut self, elapsed: Duration) -> Vec<GameEvent> {
+        if self.outcome().is_some() {
+            return Vec::new();
+        }
+        let mut all_events = Vec::new();
+        for player in 0..2 {
+            self.games[player].update(elapsed);
+            let events = self.games[player].take_events();
+            let attack: u32 = events
+                .iter()
+                .map(|event| match *event {
+                    GameEvent::LinesCleared { count, t_spin } => attack_lines(count, t_spin),
+                    _ => 0,
+                })
+                .sum();
+            if attack > 0 {
+                self.games[1 - player].queue_garbage(attack);
+                self.sent[player] += attack;
+            }
+            all_events.extend(events);
+        }
+        all_events
+    }
+
+    /// How the match ended, or `None` while both are playing. Both games advance in
+    /// every update, so both can top out in the same one.
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.games.each_ref().map(Game::is_game_over) {
+            [false, false] => None,
+            [true, true] => Some(Outcome::Draw),
+            [lost, _] => Some(Outcome::Won(if lost { 1 } else { 0 })),
+        }
+    }
+
+    /// Garbage lines `player` has sent the other player.
+    pub fn sent(&self, player: usize) -> u32 {
+        self.sent[player]
+    }
+
+    /// Whether the match is paused; both games pause together.
+    pub fn is_paused(&self) -> bool {
+        self.games[0].is_paused()
+    }
+
+    /// Whether both games are showing "Quit? (Y/N)"; see [`Versus::ask_to_quit`].
+    pub fn is_confirming_quit(&self) -> bool {
+        self.games[0].is_confirming_quit()
+    }
+
+    /// Asks "Quit? (Y/N)" on both boards, pausing the match until
+    /// [`Versus::cancel_quit`]; answering yes is up to the caller.
+    pub fn ask_to_quit(&mut self) {
+        self.games.iter_mut().for_each(Game::ask_to_quit);
+    }
+
+    /// Takes the quit question down from both boards, resuming the match if asking
+    /// paused it.
+    pub fn cancel_quit(&mut self) {
+        self.games.iter_mut().for_each(Game::cancel_quit);
+    }
+
+    /// Pauses or resumes both games.
+    pub fn toggle_pause(&mut self) {
+        let paused = !self.is_paused();
+        for game in &mut self.games {
+            if game.is_paused() != paused {
+                game.toggle_pause();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Color, Piece, PlayerInput, BUFFER_ROWS};
+
+    #[test]
+    fn clears_send_garbage_to_the_other_player_until_one_tops_out() {
+        assert_eq!([1, 2, 3, 4].map(|count| attack_lines(count, false)), [0, 1, 2, 4]);
+        assert_eq!(attack_lines(2, true), 4);
+
+        // On a four-wide board every 4x2 slab clears two lines, sending one.
+        let rotations = vec![(4, vec![1; 8])];
+        let slab = Piece { name: "B".to_string(), rotations, color: Color(9, 9, 9), spawn_column: 0 };
+        let options = GameOptions {
+            pieces: vec![slab].into(),
+            garbage_delay: Duration::ZERO,
+            line_clear_delay: Duration::ZERO,
+            ..GameOptions::default()
+        };
+        let mut versus = Versus::new(4, 8, options);
+        assert_eq!(versus.games[0].seed(), versus.games[1].seed());
+        let drop = |versus: &mut Versus, player: usize| {
+            versus.games[player].queue_input(PlayerInput::HardDrop);
+            versus.update(Duration::from_millis(1))
+        };
+        drop(&mut versus, 0);
+        assert_eq!((versus.sent(0), versus.games[1].pending_garbage()), (1, (1, 0)));
+
+        versus.toggle_pause();
+        assert!(versus.games.iter().all(Game::is_paused));
+        versus.toggle_pause();
+        assert!(!versus.games.iter().any(Game::is_paused));
+
+        // Player two's next lock takes all the garbage queued so far, more than the
+        // board and its buffer rows hold.
+        for _ in 0..BUFFER_ROWS + 8 {
+            drop(&mut versus, 0);
+        }
+        assert_eq!(versus.outcome(), None);
+        drop(&mut versus, 1);
+        assert_eq!(versus.outcome(), Some(Outcome::Won(0)));
+        assert!(drop(&mut versus, 0).is_empty());
+
+        versus.restart();
+        assert_eq!((versus.outcome(), versus.sent(0)), (None, 0));
+
+        // Both boards buried at once top out in the same update.
+        for game in &mut versus.games {
+            game.queue_garbage(BUFFER_ROWS as u32 + 8);
+            game.queue_input(PlayerInput::HardDrop);
+        }
+        versus.update(Duration::from_millis(1));
+        assert_eq!(versus.outcome(), Some(Outcome::Draw));
+    }
+}